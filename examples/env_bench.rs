@@ -0,0 +1,52 @@
+// Copyright © 2018 William Haugen - Piemyth
+// [This work is licensed under the "BSD 2-Clause License"]
+// Please see the file LICENSE in the source
+// distribution of this software for license terms.
+
+//Plays random actions in the environment as fast as possible
+//and prints how many steps per second it managed.
+//
+//cargo run --release --example env_bench -- [steps]
+
+extern crate galaga;
+extern crate rand;
+
+use galaga::env::Env;
+use galaga::game::Action;
+use galaga::rng::GameRng;
+use rand::Rng;
+use std::env;
+use std::time::Instant;
+
+fn main() {
+    let steps: u64 = env::args()
+        .nth(1)
+        .and_then(|x| x.parse().ok())
+        .unwrap_or(100_000);
+
+    let mut rng = GameRng::new(0);
+    let mut world = Env::new(0);
+    let mut episodes = 0;
+    let mut total = 0;
+    let start = Instant::now();
+
+    for _ in 0..steps {
+        let action = Action::ALL[rng.gen_range(0, Action::ALL.len())];
+        let step = world.step(action);
+        total += step.reward;
+        if step.done {
+            episodes += 1;
+            world.reset(episodes);
+        }
+    }
+
+    let seconds = start.elapsed().as_secs_f64();
+    println!(
+        "{} steps in {:.2}s ({:.0} steps/s), {} episodes, {} points",
+        steps,
+        seconds,
+        steps as f64 / seconds,
+        episodes,
+        total
+    );
+}
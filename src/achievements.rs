@@ -0,0 +1,509 @@
+// Copyright © 2018 William Haugen - Piemyth
+// [This work is licensed under the "BSD 2-Clause License"]
+// Please see the file LICENSE in the source
+// distribution of this software for license terms.
+
+//Achievements unlocked by how games are played, kept from one session to the
+//next. They're worked out from the game's events and a look at the game after
+//each tick or input, so nothing in it needs to know about them. The file is
+//one thing per line:
+//  kills <enemies brought down in every game so far>
+//  unlocked <achievement name>
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use events::Event;
+use game::Game;
+use waves::Kind;
+
+//Ticks in one game to survive for, five minutes.
+static SURVIVETICKS: u64 = 6 * 60 * 5;
+//Enemies to bring down over every game played.
+static LIFETIMEKILLS: u64 = 1000;
+//Multiplier to get a combo up to.
+static TOPCOMBO: u64 = 8;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Achievement {
+    //Clear a stage.
+    FirstStage,
+    //Survive five minutes in one game.
+    Survivor,
+    //Clear a stage without missing a shot.
+    Sharpshooter,
+    //Bring down a thousand enemies over every game.
+    Exterminator,
+    //Clear a stage without moving.
+    Statue,
+    //Get a combo up to the top multiplier.
+    ChainReaction,
+    //Bring down a boss.
+    GiantSlayer,
+}
+
+impl Achievement {
+    pub const ALL: [Achievement; 7] = [
+        Achievement::FirstStage,
+        Achievement::Survivor,
+        Achievement::Sharpshooter,
+        Achievement::Exterminator,
+        Achievement::Statue,
+        Achievement::ChainReaction,
+        Achievement::GiantSlayer,
+    ];
+
+    //Name used for it in the achievements file.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Achievement::FirstStage => "first-stage",
+            Achievement::Survivor => "survivor",
+            Achievement::Sharpshooter => "sharpshooter",
+            Achievement::Exterminator => "exterminator",
+            Achievement::Statue => "statue",
+            Achievement::ChainReaction => "chain-reaction",
+            Achievement::GiantSlayer => "giant-slayer",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Achievement> {
+        Achievement::ALL.iter().find(|x| x.name() == name).cloned()
+    }
+
+    //Shown when it unlocks and on the achievements screen.
+    pub fn title(&self) -> &'static str {
+        match self {
+            Achievement::FirstStage => "Warmed Up",
+            Achievement::Survivor => "Survivor",
+            Achievement::Sharpshooter => "Sharpshooter",
+            Achievement::Exterminator => "Exterminator",
+            Achievement::Statue => "Statue",
+            Achievement::ChainReaction => "Chain Reaction",
+            Achievement::GiantSlayer => "Giant Slayer",
+        }
+    }
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            Achievement::FirstStage => "Clear a stage",
+            Achievement::Survivor => "Survive five minutes in one game",
+            Achievement::Sharpshooter => "Clear a stage without missing a shot",
+            Achievement::Exterminator => "Destroy 1000 enemies in all",
+            Achievement::Statue => "Clear a stage without moving",
+            Achievement::ChainReaction => "Get a combo up to x8",
+            Achievement::GiantSlayer => "Bring down a boss",
+        }
+    }
+}
+
+//How a player's stage has gone so far.
+#[derive(Clone, Debug, PartialEq)]
+struct Stage {
+    //Shots fired and hits made this stage.
+    shots: u64,
+    hits: u64,
+    //Where the ship was last seen and whether it has moved this stage.
+    pos: (i64, i64),
+    moved: bool,
+    //Time was run back during it, so it can't be cleared cleanly any more.
+    rewound: bool,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Achievements {
+    pub unlocked: Vec<Achievement>,
+    //Enemies brought down over every game, counting the one going now.
+    pub kills: u64,
+    //Tick of the game last looked at, to tell if it's the same one.
+    ticks: u64,
+    //Enemies brought down in the game last looked at.
+    seen: u64,
+    stages: Vec<Stage>,
+}
+
+impl Achievements {
+    pub fn new() -> Achievements {
+        Achievements::default()
+    }
+
+    //Read what's been unlocked from the file, none if there isn't one yet.
+    pub fn load(path: &Path) -> Result<Achievements, String> {
+        let mut achievements = Achievements::new();
+        let text = match fs::read_to_string(path) {
+            Ok(x) => x,
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => return Ok(achievements),
+            Err(e) => return Err(format!("{}: {}", path.display(), e)),
+        };
+
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            let error = |x: String| format!("{}:{}: {}", path.display(), number + 1, x);
+            let mut words = line.split_whitespace();
+            match (words.next(), words.next()) {
+                (Some("kills"), Some(x)) => {
+                    achievements.kills = x
+                        .parse()
+                        .map_err(|_| error(format!("'{}' isn't a count", x)))?;
+                }
+                (Some("unlocked"), Some(x)) => match Achievement::from_name(x) {
+                    Some(x) if !achievements.unlocked.contains(&x) => achievements.unlocked.push(x),
+                    Some(_) => {}
+                    None => return Err(error(format!("unknown achievement '{}'", x))),
+                },
+                _ => return Err(error(format!("unknown line '{}'", line))),
+            }
+        }
+        Ok(achievements)
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let mut text = format!("kills {}\n", self.kills);
+        for x in self.unlocked.iter() {
+            text.push_str(&format!("unlocked {}\n", x.name()));
+        }
+        fs::write(path, text)
+    }
+
+    pub fn has(&self, achievement: Achievement) -> bool {
+        self.unlocked.contains(&achievement)
+    }
+
+    //Go through what happened since the last call, giving anything that
+    //was just unlocked. A game that isn't the same one carried on, like
+    //after a restart or handing over to the other player, only counts
+    //towards a stage from here on.
+    pub fn update(&mut self, game: &Game, events: &[Event]) -> Vec<Achievement> {
+        let same = (game.ticks == self.ticks || game.ticks == self.ticks + 1)
+            && self.stages.len() == game.players.len();
+        if !same {
+            self.watch(game);
+        }
+        self.ticks = game.ticks;
+        self.seen = kills(game);
+
+        for (player, stage) in game.players.iter().zip(self.stages.iter_mut()) {
+            if player.ship.pos_x != stage.pos.0 || player.ship.pos_y != stage.pos.1 {
+                stage.moved = true;
+                stage.pos = (player.ship.pos_x, player.ship.pos_y);
+            }
+        }
+
+        let mut earned = Vec::new();
+        for x in events.iter() {
+            match *x {
+                Event::ShotFired { player, .. } => {
+                    if let Some(x) = self.stages.get_mut(player) {
+                        x.shots += 1;
+                    }
+                }
+                Event::EnemyDestroyed { player, kind, .. } => {
+                    if let Some(x) = self.stages.get_mut(player) {
+                        x.hits += 1;
+                    }
+                    self.kills += 1;
+                    if kind == Kind::Boss {
+                        earned.push(Achievement::GiantSlayer);
+                    }
+                }
+                Event::EnemyDamaged { player, .. } | Event::RockHit { player, .. } => {
+                    if let Some(x) = self.stages.get_mut(player) {
+                        x.hits += 1;
+                    }
+                }
+                Event::StageCleared { .. } => {
+                    earned.push(Achievement::FirstStage);
+                    for (player, stage) in game.players.iter().zip(self.stages.iter()) {
+                        if stage.rewound {
+                            continue;
+                        }
+                        if stage.shots > 0 && stage.hits >= stage.shots {
+                            earned.push(Achievement::Sharpshooter);
+                        }
+                        if !stage.moved && player.alive() {
+                            earned.push(Achievement::Statue);
+                        }
+                    }
+                    self.watch(game);
+                }
+                _ => {}
+            }
+        }
+
+        for x in game.players.iter() {
+            if x.stats.ticks >= SURVIVETICKS {
+                earned.push(Achievement::Survivor);
+            }
+            if x.stats.best_multiplier >= TOPCOMBO {
+                earned.push(Achievement::ChainReaction);
+            }
+        }
+        if self.kills >= LIFETIMEKILLS {
+            earned.push(Achievement::Exterminator);
+        }
+
+        let mut unlocked = Vec::new();
+        for x in earned {
+            if !self.has(x) {
+                self.unlocked.push(x);
+                unlocked.push(x);
+            }
+        }
+        unlocked
+    }
+
+    //The game was just sent back in time to how it is now. Kills that got
+    //undone don't count towards every game any more, and the stage going
+    //on keeps what it had so far but can't earn anything for a clean clear.
+    pub fn rewound(&mut self, game: &Game) {
+        let kills = kills(game);
+        self.kills = self.kills.saturating_sub(self.seen.saturating_sub(kills));
+        self.seen = kills;
+        self.ticks = game.ticks;
+        for x in self.stages.iter_mut() {
+            x.rewound = true;
+        }
+    }
+
+    //Start a stage for everyone from how the game is now.
+    fn watch(&mut self, game: &Game) {
+        self.stages = game
+            .players
+            .iter()
+            .map(|x| Stage {
+                shots: 0,
+                hits: 0,
+                pos: (x.ship.pos_x, x.ship.pos_y),
+                moved: false,
+                rewound: false,
+            })
+            .collect();
+    }
+}
+
+//Enemies brought down so far in a game, by everyone in it.
+fn kills(game: &Game) -> u64 {
+    game.players.iter().map(|x| x.stats.kills()).sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use turns::Turns;
+
+    fn shot() -> Event {
+        Event::ShotFired {
+            player: 0,
+            pos: (10, 18),
+        }
+    }
+
+    fn kill(kind: Kind) -> Event {
+        Event::EnemyDestroyed {
+            player: 0,
+            kind,
+            pos: (10, 5),
+            points: 10,
+            multiplier: 1,
+        }
+    }
+
+    fn cleared() -> Event {
+        Event::StageCleared { stage: 0 }
+    }
+
+    //Move the game on a tick with a kill in it, as the game would count it.
+    fn kill_in(game: &mut Game) -> Event {
+        game.ticks += 1;
+        game.players[0].stats.destroy(Kind::Drone);
+        kill(Kind::Drone)
+    }
+
+    #[test]
+    fn clean_stage_unlocks() {
+        let game = Game::seeded(1);
+        let mut achievements = Achievements::new();
+        assert!(achievements.update(&game, &[]).is_empty());
+        let unlocked = achievements.update(&game, &[shot(), kill(Kind::Boss), cleared()]);
+        assert_eq!(
+            unlocked,
+            vec![
+                Achievement::GiantSlayer,
+                Achievement::FirstStage,
+                Achievement::Sharpshooter,
+                Achievement::Statue,
+            ]
+        );
+
+        //Nothing unlocks twice.
+        assert!(achievements
+            .update(&game, &[kill(Kind::Boss), cleared()])
+            .is_empty());
+    }
+
+    #[test]
+    fn missing_or_moving_spoils_a_stage() {
+        let mut game = Game::seeded(1);
+        let mut achievements = Achievements::new();
+        achievements.update(&game, &[]);
+        achievements.update(&game, &[shot(), shot(), kill(Kind::Drone)]);
+        game.players[0].ship.pos_x += 1;
+        let unlocked = achievements.update(&game, &[cleared()]);
+        assert_eq!(unlocked, vec![Achievement::FirstStage]);
+
+        //The next stage starts clean.
+        let unlocked = achievements.update(&game, &[shot(), kill(Kind::Drone), cleared()]);
+        assert_eq!(
+            unlocked,
+            vec![Achievement::Sharpshooter, Achievement::Statue]
+        );
+
+        //No shots at all isn't sharpshooting.
+        let mut achievements = Achievements::new();
+        achievements.update(&game, &[]);
+        let unlocked = achievements.update(&game, &[cleared()]);
+        assert_eq!(unlocked, vec![Achievement::FirstStage, Achievement::Statue]);
+    }
+
+    #[test]
+    fn long_games_and_combos_unlock() {
+        let mut game = Game::seeded(1);
+        let mut achievements = Achievements::new();
+        game.players[0].stats.ticks = SURVIVETICKS - 1;
+        game.players[0].stats.best_multiplier = TOPCOMBO - 1;
+        assert!(achievements.update(&game, &[]).is_empty());
+
+        game.players[0].stats.ticks = SURVIVETICKS;
+        assert_eq!(achievements.update(&game, &[]), vec![Achievement::Survivor]);
+        game.players[0].stats.best_multiplier = TOPCOMBO;
+        assert_eq!(
+            achievements.update(&game, &[]),
+            vec![Achievement::ChainReaction]
+        );
+    }
+
+    #[test]
+    fn kills_add_up_over_games() {
+        let mut game = Game::seeded(1);
+        let mut achievements = Achievements::new();
+        achievements.kills = LIFETIMEKILLS - 2;
+        achievements.update(&game, &[]);
+        let event = kill_in(&mut game);
+        assert!(achievements.update(&game, &[event]).is_empty());
+
+        //A new game carries on from there.
+        let mut game = Game::seeded(2);
+        achievements.update(&game, &[]);
+        let event = kill_in(&mut game);
+        assert_eq!(
+            achievements.update(&game, &[event]),
+            vec![Achievement::Exterminator]
+        );
+        assert_eq!(achievements.kills, LIFETIMEKILLS);
+    }
+
+    #[test]
+    fn rewinding_takes_back_kills() {
+        let mut game = Game::seeded(1);
+        let mut achievements = Achievements::new();
+        achievements.update(&game, &[]);
+        let before = game.clone();
+        let event = kill_in(&mut game);
+        achievements.update(&game, &[event]);
+        let middle = game.clone();
+        let event = kill_in(&mut game);
+        achievements.update(&game, &[event]);
+        assert_eq!(achievements.kills, 2);
+
+        //Going back a tick undoes the second kill only.
+        achievements.rewound(&middle);
+        assert_eq!(achievements.kills, 1);
+
+        //Then back to the start, and a kill that didn't happen the first
+        //time round counts as it's played.
+        achievements.rewound(&before);
+        assert_eq!(achievements.kills, 0);
+        let mut game = before.clone();
+        let event = kill_in(&mut game);
+        achievements.update(&game, &[event]);
+        assert_eq!(achievements.kills, 1);
+    }
+
+    #[test]
+    fn rewinding_spoils_a_stage() {
+        let mut game = Game::seeded(1);
+        let mut achievements = Achievements::new();
+        achievements.update(&game, &[]);
+        let before = game.clone();
+        game.ticks += 1;
+        game.players[0].ship.pos_x += 1;
+        achievements.update(&game, &[shot()]);
+
+        //Taking back the missed shot and the move doesn't make it clean.
+        achievements.rewound(&before);
+        let unlocked = achievements.update(&before, &[shot(), kill(Kind::Drone), cleared()]);
+        assert_eq!(unlocked, vec![Achievement::FirstStage]);
+
+        //The stage after is fine again.
+        let unlocked = achievements.update(&before, &[shot(), kill(Kind::Drone), cleared()]);
+        assert_eq!(
+            unlocked,
+            vec![Achievement::Sharpshooter, Achievement::Statue]
+        );
+    }
+
+    #[test]
+    fn handing_over_starts_a_new_stage() {
+        let mut game = Game::turns(1);
+        let mut turns = Turns::new(&mut game);
+        let mut achievements = Achievements::new();
+        for _ in 0..30 {
+            game.update();
+        }
+        game.clear_events();
+        achievements.update(&game, &[]);
+        achievements.update(&game, &[shot()]);
+
+        //Player 2's stage hasn't missed anything.
+        game.players[0].lives -= 1;
+        turns.update(&mut game);
+        assert_eq!(turns.player, 1);
+        let unlocked = achievements.update(&game, &[shot(), kill(Kind::Drone), cleared()]);
+        assert!(unlocked.contains(&Achievement::Sharpshooter));
+    }
+
+    #[test]
+    fn round_trip_through_a_file() {
+        let path = ::std::env::temp_dir().join("galaga-achievements-test");
+        let mut achievements = Achievements::new();
+        achievements.kills = 42;
+        achievements.unlocked = vec![Achievement::Statue, Achievement::FirstStage];
+        achievements.save(&path).unwrap();
+        let loaded = Achievements::load(&path).unwrap();
+        assert_eq!(loaded.kills, 42);
+        assert_eq!(loaded.unlocked, achievements.unlocked);
+
+        //Bad lines say where they are.
+        for (text, error) in [
+            ("kills 4\nkills lots\n", ":2: 'lots' isn't a count"),
+            (
+                "unlocked statue\nunlocked flying\n",
+                ":2: unknown achievement 'flying'",
+            ),
+            ("\nhighscore 10\n", ":2: unknown line 'highscore 10'"),
+        ]
+        .iter()
+        {
+            fs::write(&path, text).unwrap();
+            let e = Achievements::load(&path).unwrap_err();
+            assert!(e.ends_with(error), "{}", e);
+        }
+        fs::remove_file(&path).unwrap();
+
+        //Nothing's unlocked before there's a file.
+        assert_eq!(Achievements::load(&path).unwrap(), Achievements::new());
+    }
+}
@@ -0,0 +1,127 @@
+// Copyright © 2018 William Haugen - Piemyth
+// [This work is licensed under the "BSD 2-Clause License"]
+// Please see the file LICENSE in the source
+// distribution of this software for license terms.
+
+use controller::Controller;
+use game::{Action, Game};
+use {GRIDSIZE, WIDTH};
+
+//How many rows above the ship something has to be before
+//the pilot stops worrying about it.
+static LOOKAHEAD: i64 = 3;
+
+//Scripted pilot that plays the game with the same actions as the keyboard.
+//Rocks always fall down the column the ship was in when they spawned,
+//so it mostly has to keep moving and get out of the way in time.
+pub struct Pilot {
+    //Which player's ship it flies.
+    player: usize,
+    //Column the pilot is heading for, if it picked one.
+    target: Option<i64>,
+    //Last tick a decision was made on, when used as a controller.
+    last_tick: Option<u64>,
+}
+
+impl Default for Pilot {
+    fn default() -> Pilot {
+        Pilot::new()
+    }
+}
+
+impl Pilot {
+    pub fn new() -> Pilot {
+        Pilot::flying(0)
+    }
+
+    //A pilot for one of the other players' ships.
+    pub fn flying(player: usize) -> Pilot {
+        Pilot {
+            player,
+            target: None,
+            last_tick: None,
+        }
+    }
+
+    //Pick the action for this tick.
+    pub fn decide(&mut self, game: &Game) -> Action {
+        let player = match game.players.get(self.player) {
+            Some(x) if x.alive() => x,
+            _ => return Action::Idle,
+        };
+        let ship = (player.ship.pos_x, player.ship.pos_y);
+
+        //Get out of the way of anything about to land on us.
+        if danger(game, ship.0, ship.1) {
+            self.target = None;
+            return dodge(game, ship);
+        }
+
+        //Shoot anything lined up above, as long as there's a shot left.
+        let lined_up = game
+            .enemies
+            .list
+            .iter()
+            .any(|x| x.pos_x == ship.0 && x.pos_y < ship.1 - 1);
+        if lined_up && player.ship.shots.len() < 5 {
+            return Action::Shoot;
+        }
+
+        //Head for the lowest enemy, since it's the next one to deal with.
+        if self.target.is_none() || self.target == Some(ship.0) {
+            self.target = game
+                .enemies
+                .list
+                .iter()
+                .filter(|x| x.pos_y >= 0 && x.pos_y < ship.1 - LOOKAHEAD)
+                .max_by_key(|x| x.pos_y)
+                .map(|x| x.pos_x);
+        }
+
+        match self.target {
+            Some(x) if x < ship.0 && !danger(game, ship.0 - 1, ship.1) => Action::Left,
+            Some(x) if x > ship.0 && !danger(game, ship.0 + 1, ship.1) => Action::Right,
+            _ => Action::Idle,
+        }
+    }
+}
+
+impl Controller for Pilot {
+    fn poll(&mut self, game: &Game) -> Vec<Action> {
+        //One decision per tick.
+        if self.last_tick == Some(game.ticks) {
+            return Vec::new();
+        }
+        self.last_tick = Some(game.ticks);
+        vec![self.decide(game)]
+    }
+}
+
+//Check if a rock or enemy will reach the square soon.
+fn danger(game: &Game, x: i64, y: i64) -> bool {
+    let near = |pos_x: i64, pos_y: i64| pos_x == x && pos_y <= y && pos_y >= y - LOOKAHEAD;
+
+    game.enemies.rocks.iter().any(|r| near(r.pos_x, r.pos_y))
+        || game.enemies.list.iter().any(|e| near(e.pos_x, e.pos_y))
+        || game.enemies.shots.iter().any(|s| near(s.pos_x, s.pos_y))
+}
+
+//Step to whichever side is clear, preferring the one closer to the middle.
+fn dodge(game: &Game, ship: (i64, i64)) -> Action {
+    let middle = WIDTH / GRIDSIZE / 2;
+    let sides = if ship.0 < middle {
+        [(1, Action::Right), (-1, Action::Left)]
+    } else {
+        [(-1, Action::Left), (1, Action::Right)]
+    };
+
+    for side in sides.iter() {
+        let x = ship.0 + side.0;
+        if x >= 1 && x < WIDTH / GRIDSIZE - 1 && !danger(game, x, ship.1) {
+            return side.1;
+        }
+    }
+
+    //Boxed in on both sides, backing up buys a tick.
+    Action::Down
+}
@@ -0,0 +1,202 @@
+// Copyright © 2018 William Haugen - Piemyth
+// [This work is licensed under the "BSD 2-Clause License"]
+// Please see the file LICENSE in the source
+// distribution of this software for license terms.
+
+use find_folder;
+use opengl_graphics::{GlyphCache, Texture, TextureSettings};
+use std::env;
+use std::path::{Path, PathBuf};
+
+use config::Config;
+use mods::{Pack, MANIFEST};
+use render::{Palette, SPRITES};
+use sprites::Atlas;
+
+//Files loaded out of the assets folder.
+static BACKGROUND: &str = "background.png";
+static FONT: &str = "FiraSans-Regular.ttf";
+static DEFINITIONS: &str = "sprites.txt";
+static CONFIG: &str = "config.txt";
+//How many folders up and down from where the game is run to look for assets.
+static SEARCHDEPTH: u8 = 3;
+
+//Everything the window draws with, loaded from the assets folder or the
+//asset pack picked on the options screen. Whatever can be stood in for is,
+//with what went wrong printed, so only a missing font stops the game.
+pub struct Assets {
+    pub folder: PathBuf,
+    //Its files are used in place of the ones in folder.
+    pub pack: Option<Pack>,
+    //Slowest layer of the background, the stars are enough without it.
+    pub background: Option<Texture>,
+    pub atlas: Atlas,
+    pub glyphs: GlyphCache<'static>,
+    pub config: Config,
+}
+
+impl Assets {
+    //Look for the assets folder around wherever the game is run from.
+    pub fn find() -> Result<PathBuf, String> {
+        find_folder::Search::ParentsThenKids(SEARCHDEPTH, SEARCHDEPTH)
+            .for_folder("assets")
+            .map_err(|_| {
+                let here = env::current_dir()
+                    .map(|x| x.display().to_string())
+                    .unwrap_or_else(|_| "the current folder".to_string());
+                format!(
+                    "Could not find the assets folder, looked in {} and {} folders above and below it",
+                    here, SEARCHDEPTH
+                )
+            })
+    }
+
+    pub fn load(folder: &Path, pack: Option<Pack>) -> Result<Assets, String> {
+        let mut folders: Vec<PathBuf> = pack.iter().map(|x| x.folder.clone()).collect();
+        folders.push(folder.to_path_buf());
+        let folders = &folders[..];
+
+        let font = locate(folders, FONT);
+        let glyphs = GlyphCache::new(&font, (), TextureSettings::new())
+            .map_err(|e| format!("Could not load the font {}: {}", font.display(), e))?;
+
+        let background = match load_background(folders) {
+            Ok(x) => Some(x),
+            Err(e) => {
+                eprintln!("{}, drawing only stars", e);
+                None
+            }
+        };
+
+        let atlas = match Atlas::load(folders, DEFINITIONS) {
+            Ok(x) => x,
+            Err(e) => {
+                eprintln!("Could not load sprites {}, drawing placeholders", e);
+                Atlas::placeholders(&SPRITES)
+            }
+        };
+        for x in atlas.problems.iter() {
+            eprintln!("Could not load {}, drawing a placeholder", x);
+        }
+
+        let config = Config::load(&locate(folders, CONFIG)).unwrap_or_else(|e| {
+            eprintln!("Could not load config {}", e);
+            Config::new()
+        });
+
+        Ok(Assets {
+            folder: folder.to_path_buf(),
+            pack,
+            background,
+            atlas,
+            glyphs,
+            config,
+        })
+    }
+
+    //Folders files are looked for in, the pack's first.
+    pub fn folders(&self) -> Vec<PathBuf> {
+        let mut folders: Vec<PathBuf> = self.pack.iter().map(|x| x.folder.clone()).collect();
+        folders.push(self.folder.clone());
+        folders
+    }
+
+    //Colours to draw with, the pack's if there is one.
+    pub fn palette(&self) -> Palette {
+        self.pack
+            .as_ref()
+            .map(|x| x.palette.clone())
+            .unwrap_or_default()
+    }
+
+    //Load again whichever of the changed files are ours. Anything that
+    //doesn't load keeps the old one. Gives whether there's a new config
+    //or palette.
+    pub fn reload(&mut self, changed: &[PathBuf]) -> bool {
+        let folders = self.folders();
+        let is = |name: &str| {
+            changed
+                .iter()
+                .any(|x| x.file_name().is_some_and(|x| x == name))
+        };
+        let (background, font, config, manifest) =
+            (is(BACKGROUND), is(FONT), is(CONFIG), is(MANIFEST));
+        //Sprites are all packed together, so any image or the definitions
+        //changing packs them all again.
+        let sprites = is(DEFINITIONS)
+            || changed.iter().any(|x| {
+                x.extension().is_some_and(|x| x == "png")
+                    && x.file_name().is_some_and(|x| x != BACKGROUND)
+            });
+        let mut retune = false;
+
+        if background {
+            match load_background(&folders) {
+                Ok(x) => {
+                    println!("Reloaded {}", BACKGROUND);
+                    self.background = Some(x);
+                }
+                Err(e) => eprintln!("{}", e),
+            }
+        }
+        if font {
+            let path = locate(&folders, FONT);
+            match GlyphCache::new(&path, (), TextureSettings::new()) {
+                Ok(x) => {
+                    println!("Reloaded {}", FONT);
+                    self.glyphs = x;
+                }
+                Err(e) => eprintln!("Could not reload {}: {}", path.display(), e),
+            }
+        }
+        if sprites {
+            match Atlas::load(&folders, DEFINITIONS) {
+                Ok(x) => {
+                    println!("Reloaded sprites");
+                    for x in x.problems.iter() {
+                        eprintln!("Could not load {}, drawing a placeholder", x);
+                    }
+                    self.atlas = x;
+                }
+                Err(e) => eprintln!("Could not reload sprites {}", e),
+            }
+        }
+        if config {
+            match Config::load(&locate(&folders, CONFIG)) {
+                Ok(x) => {
+                    println!("Reloaded {}", CONFIG);
+                    self.config = x;
+                    retune = true;
+                }
+                Err(e) => eprintln!("Could not reload config {}", e),
+            }
+        }
+        if let (true, Some(pack)) = (manifest, self.pack.as_mut()) {
+            match Pack::load(&pack.folder) {
+                Ok(x) => {
+                    println!("Reloaded {}", MANIFEST);
+                    *pack = x;
+                    retune = true;
+                }
+                Err(e) => eprintln!("Could not reload the pack {}", e),
+            }
+        }
+        retune
+    }
+}
+
+//Where to load a file from, the first of the folders that has it. If none
+//do it's the last one's, so errors point at where it usually is.
+pub fn locate(folders: &[PathBuf], name: &str) -> PathBuf {
+    folders
+        .iter()
+        .map(|x| x.join(name))
+        .find(|x| x.exists())
+        .unwrap_or_else(|| folders[folders.len() - 1].join(name))
+}
+
+fn load_background(folders: &[PathBuf]) -> Result<Texture, String> {
+    let path = locate(folders, BACKGROUND);
+    Texture::from_path(&path, &TextureSettings::new())
+        .map_err(|e| format!("Could not load {}: {}", path.display(), e))
+}
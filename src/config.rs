@@ -0,0 +1,129 @@
+// Copyright © 2018 William Haugen - Piemyth
+// [This work is licensed under the "BSD 2-Clause License"]
+// Please see the file LICENSE in the source
+// distribution of this software for license terms.
+
+//Tuning values from a config file of name = number lines, with # starting a
+//comment. They only change how the game looks, anything that changed how it
+//plays would throw off replays and online games.
+
+use std::fs;
+use std::io::ErrorKind;
+use std::path::Path;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Config {
+    //How fast the background scrolls, 1 is normal.
+    pub scroll: f64,
+    //Seconds of warp between stages.
+    pub warp: f64,
+    //How many particles are in each burst, 1 is normal.
+    pub particles: f64,
+    //Engine exhaust particles a second.
+    pub exhaust: f64,
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config::new()
+    }
+}
+
+impl Config {
+    pub fn new() -> Config {
+        Config {
+            scroll: 1.0,
+            warp: 1.5,
+            particles: 1.0,
+            exhaust: 40.0,
+        }
+    }
+
+    //Read the config file, anything it leaves out keeps its usual value.
+    //No file at all is the same as an empty one.
+    pub fn load(path: &Path) -> Result<Config, String> {
+        let name = path.display();
+        let text = match fs::read_to_string(path) {
+            Ok(x) => x,
+            Err(ref e) if e.kind() == ErrorKind::NotFound => return Ok(Config::new()),
+            Err(e) => return Err(format!("{}: {}", name, e)),
+        };
+
+        let mut config = Config::new();
+        for (number, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+            let error = |x: String| format!("{}:{}: {}", name, number + 1, x);
+
+            let mut parts = line.splitn(2, '=');
+            let key = parts.next().unwrap_or("").trim();
+            let value: f64 = match parts.next().map(|x| x.trim().parse()) {
+                Some(Ok(x)) if x >= 0.0 => x,
+                Some(_) => return Err(error(format!("'{}' needs a number 0 or more", key))),
+                None => return Err(error("expected name = number".to_string())),
+            };
+            match key {
+                "scroll" => config.scroll = value,
+                "warp" => config.warp = value,
+                "particles" => config.particles = value,
+                "exhaust" => config.exhaust = value,
+                x => return Err(error(format!("unknown setting '{}'", x))),
+            }
+        }
+        Ok(config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //Load text as a config file of the name given.
+    fn load(name: &str, text: &str) -> Result<Config, String> {
+        let path = ::std::env::temp_dir().join(format!("galaga-config-{}.cfg", name));
+        fs::write(&path, text).unwrap();
+        let config = Config::load(&path);
+        fs::remove_file(&path).unwrap();
+        config.map_err(|e| e.replace(&path.display().to_string(), "x"))
+    }
+
+    #[test]
+    fn reads_settings() {
+        let text = "# Slower stars\nscroll = 0.5\n\nexhaust=0 # none\n";
+        let config = load("reads", text).unwrap();
+        assert_eq!(
+            config,
+            Config {
+                scroll: 0.5,
+                exhaust: 0.0,
+                ..Config::new()
+            }
+        );
+        assert_eq!(load("reads", "").unwrap(), Config::new());
+
+        let path = ::std::env::temp_dir().join("galaga-config-missing.cfg");
+        assert_eq!(Config::load(&path).unwrap(), Config::new());
+    }
+
+    #[test]
+    fn errors_say_where() {
+        assert_eq!(
+            load("errors", "warp = 2\nspeed = 3\n").unwrap_err(),
+            "x:2: unknown setting 'speed'"
+        );
+        assert_eq!(
+            load("errors", "\nscroll = fast\n").unwrap_err(),
+            "x:2: 'scroll' needs a number 0 or more"
+        );
+        assert_eq!(
+            load("errors", "particles = -1\n").unwrap_err(),
+            "x:1: 'particles' needs a number 0 or more"
+        );
+        assert_eq!(
+            load("errors", "# a\n\nwarp\n").unwrap_err(),
+            "x:3: expected name = number"
+        );
+    }
+}
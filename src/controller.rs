@@ -0,0 +1,351 @@
+// Copyright © 2018 William Haugen - Piemyth
+// [This work is licensed under the "BSD 2-Clause License"]
+// Please see the file LICENSE in the source
+// distribution of this software for license terms.
+
+//Everything that can fly the ship. The game only ever sees the actions
+//that come out of poll, so it doesn't matter if they came from a person,
+//a file, the pilot or somebody on the other end of a network connection.
+
+use piston::input::{Button, ButtonState, ControllerAxisArgs, Key};
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, ErrorKind, Read, Write};
+use std::net::{TcpListener, TcpStream};
+
+use game::{Action, Game};
+
+//Version written at the top of replay files.
+//Version 1 files only had one player and no player numbers.
+static REPLAYVERSION: u32 = 2;
+//How far a stick has to be pushed before it counts as a move.
+static DEADZONE: f64 = 0.5;
+
+pub trait Controller {
+    //A key or gamepad button changed state.
+    fn button(&mut self, _button: &Button, _state: ButtonState) {}
+
+    //A gamepad stick moved.
+    fn axis(&mut self, _args: &ControllerAxisArgs) {}
+
+    //Actions for the game to do right now. Everything returned between
+    //two game updates happens on the same tick.
+    fn poll(&mut self, game: &Game) -> Vec<Action>;
+}
+
+//Keys mapped to actions, each press is one action.
+pub struct Keyboard {
+    bindings: Vec<(Key, Action)>,
+    pending: Vec<Action>,
+}
+
+//Gamepad buttons and the left stick, using the usual SDL button numbers.
+pub struct Gamepad {
+    id: i32,
+    //Which way the stick is currently pushed, so holding it only moves once.
+    stick: (i8, i8),
+    pending: Vec<Action>,
+}
+
+//Plays back one player's actions from a replay file
+//at the ticks they were recorded.
+pub struct Replay {
+    seed: u64,
+    actions: VecDeque<(u64, Action)>,
+}
+
+//Writes down every action, who did it and when it happened, to save as a replay.
+pub struct Recorder {
+    seed: u64,
+    players: usize,
+    actions: Vec<(u64, usize, Action)>,
+}
+
+//Actions sent as lines of text over a TCP connection,
+//something like `nc localhost 7000` is enough to fly the ship.
+pub struct Remote {
+    stream: TcpStream,
+    buffer: Vec<u8>,
+}
+
+impl Keyboard {
+    pub fn new(bindings: Vec<(Key, Action)>) -> Keyboard {
+        Keyboard {
+            bindings,
+            pending: Vec::new(),
+        }
+    }
+
+    //Arrow keys to move and 'Z' or Enter to shoot.
+    pub fn arrows() -> Keyboard {
+        Keyboard::new(vec![
+            (Key::Up, Action::Up),
+            (Key::Down, Action::Down),
+            (Key::Left, Action::Left),
+            (Key::Right, Action::Right),
+            (Key::Z, Action::Shoot),
+            (Key::Return, Action::Shoot),
+        ])
+    }
+
+    //WASD to move and space to shoot, for the left side of the keyboard.
+    pub fn wasd() -> Keyboard {
+        Keyboard::new(vec![
+            (Key::W, Action::Up),
+            (Key::S, Action::Down),
+            (Key::A, Action::Left),
+            (Key::D, Action::Right),
+            (Key::Space, Action::Shoot),
+        ])
+    }
+}
+
+impl Controller for Keyboard {
+    fn button(&mut self, button: &Button, state: ButtonState) {
+        if state != ButtonState::Press {
+            return;
+        }
+        for x in self.bindings.iter() {
+            if *button == Button::Keyboard(x.0) {
+                self.pending.push(x.1);
+            }
+        }
+    }
+
+    fn poll(&mut self, _game: &Game) -> Vec<Action> {
+        self.pending.drain(..).collect()
+    }
+}
+
+impl Gamepad {
+    pub fn new(id: i32) -> Gamepad {
+        Gamepad {
+            id,
+            stick: (0, 0),
+            pending: Vec::new(),
+        }
+    }
+}
+
+impl Controller for Gamepad {
+    fn button(&mut self, button: &Button, state: ButtonState) {
+        let button = match button {
+            Button::Controller(x) if x.id == self.id && state == ButtonState::Press => x.button,
+            _ => return,
+        };
+        let action = match button {
+            //A and X shoot, then the d-pad.
+            0 | 2 => Action::Shoot,
+            11 => Action::Up,
+            12 => Action::Down,
+            13 => Action::Left,
+            14 => Action::Right,
+            _ => return,
+        };
+        self.pending.push(action);
+    }
+
+    fn axis(&mut self, args: &ControllerAxisArgs) {
+        if args.id != self.id || args.axis > 1 {
+            return;
+        }
+        let pushed = if args.position > DEADZONE {
+            1
+        } else if args.position < -DEADZONE {
+            -1
+        } else {
+            0
+        };
+
+        //Only move when the stick is first pushed in a direction.
+        if args.axis == 0 && pushed != self.stick.0 {
+            self.stick.0 = pushed;
+            match pushed {
+                1 => self.pending.push(Action::Right),
+                -1 => self.pending.push(Action::Left),
+                _ => {}
+            }
+        } else if args.axis == 1 && pushed != self.stick.1 {
+            self.stick.1 = pushed;
+            match pushed {
+                1 => self.pending.push(Action::Down),
+                -1 => self.pending.push(Action::Up),
+                _ => {}
+            }
+        }
+    }
+
+    fn poll(&mut self, _game: &Game) -> Vec<Action> {
+        self.pending.drain(..).collect()
+    }
+}
+
+impl Replay {
+    //Read a replay file written by Recorder::save, giving a replay for each player.
+    //Errors say which line of the file was wrong.
+    pub fn load(path: &str) -> Result<Vec<Replay>, String> {
+        let file = File::open(path).map_err(|e| format!("{}: {}", path, e))?;
+        let mut lines = BufReader::new(file).lines().enumerate();
+        let mut next = || -> Result<Option<(usize, String)>, String> {
+            match lines.next() {
+                Some((number, Ok(x))) => Ok(Some((number + 1, x))),
+                Some((number, Err(e))) => Err(format!("{}:{}: {}", path, number + 1, e)),
+                None => Ok(None),
+            }
+        };
+
+        let header = format!("galaga-replay {}", REPLAYVERSION);
+        let version = match next()? {
+            Some((_, ref x)) if *x == header => REPLAYVERSION,
+            Some((_, ref x)) if x == "galaga-replay 1" => 1,
+            _ => return Err(format!("{}:1: expected '{}'", path, header)),
+        };
+        let seed = match next()? {
+            Some((number, x)) => {
+                let mut words = x.split_whitespace();
+                match (words.next(), words.next().and_then(|x| x.parse().ok())) {
+                    (Some("seed"), Some(seed)) => seed,
+                    _ => return Err(format!("{}:{}: expected 'seed <number>'", path, number)),
+                }
+            }
+            None => return Err(format!("{}:2: expected 'seed <number>'", path)),
+        };
+
+        let players = if version == 1 {
+            1
+        } else {
+            match next()? {
+                Some((number, x)) => {
+                    let mut words = x.split_whitespace();
+                    match (words.next(), words.next().and_then(|x| x.parse().ok())) {
+                        (Some("players"), Some(players)) if players > 0 => players,
+                        _ => {
+                            return Err(format!("{}:{}: expected 'players <number>'", path, number))
+                        }
+                    }
+                }
+                None => return Err(format!("{}:3: expected 'players <number>'", path)),
+            }
+        };
+
+        let mut replays: Vec<Replay> = (0..players)
+            .map(|_| Replay {
+                seed,
+                actions: VecDeque::new(),
+            })
+            .collect();
+        while let Some((number, line)) = next()? {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let mut words = line.split_whitespace();
+            let tick = words.next().and_then(|x| x.parse().ok());
+            let player = if version == 1 {
+                Some(0)
+            } else {
+                words.next().and_then(|x| x.parse::<usize>().ok())
+            };
+            let action = words.next().and_then(Action::from_name);
+            match (tick, player, action) {
+                (Some(tick), Some(player), Some(action)) if player < players => {
+                    replays[player].actions.push_back((tick, action))
+                }
+                _ if version == 1 => {
+                    return Err(format!("{}:{}: expected '<tick> <action>'", path, number))
+                }
+                _ => {
+                    return Err(format!(
+                        "{}:{}: expected '<tick> <player> <action>'",
+                        path, number
+                    ))
+                }
+            }
+        }
+
+        Ok(replays)
+    }
+
+    //Seed of the recorded game, start a Game::seeded with it to play back.
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+}
+
+impl Controller for Replay {
+    fn poll(&mut self, game: &Game) -> Vec<Action> {
+        let mut actions = Vec::new();
+        while self.actions.front().map(|x| x.0 <= game.ticks) == Some(true) {
+            actions.push(self.actions.pop_front().unwrap().1);
+        }
+        actions
+    }
+}
+
+impl Recorder {
+    //Start recording a game for this many players that was started with this seed.
+    pub fn new(seed: u64, players: usize) -> Recorder {
+        Recorder {
+            seed,
+            players,
+            actions: Vec::new(),
+        }
+    }
+
+    pub fn record(&mut self, tick: u64, player: usize, action: Action) {
+        self.actions.push((tick, player, action));
+    }
+
+    //The game went back to tick, forget everything done after it.
+    pub fn rewind(&mut self, tick: u64) {
+        self.actions.retain(|x| x.0 <= tick);
+    }
+
+    pub fn save(&self, path: &str) -> io::Result<()> {
+        let mut file = File::create(path)?;
+        writeln!(file, "galaga-replay {}", REPLAYVERSION)?;
+        writeln!(file, "seed {}", self.seed)?;
+        writeln!(file, "players {}", self.players)?;
+        for x in self.actions.iter() {
+            writeln!(file, "{} {} {}", x.0, x.1, x.2.name())?;
+        }
+        Ok(())
+    }
+}
+
+impl Remote {
+    //Wait for somebody to connect on the address.
+    pub fn listen(address: &str) -> io::Result<Remote> {
+        let listener = TcpListener::bind(address)?;
+        let (stream, _) = listener.accept()?;
+        stream.set_nonblocking(true)?;
+        Ok(Remote {
+            stream,
+            buffer: Vec::new(),
+        })
+    }
+}
+
+impl Controller for Remote {
+    fn poll(&mut self, _game: &Game) -> Vec<Action> {
+        let mut bytes = [0u8; 256];
+        loop {
+            match self.stream.read(&mut bytes) {
+                Ok(0) => break,
+                Ok(read) => self.buffer.extend_from_slice(&bytes[..read]),
+                Err(ref e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(_) => break,
+            }
+        }
+
+        //Only whole lines, anything after the last newline waits for the rest.
+        let mut actions = Vec::new();
+        while let Some(end) = self.buffer.iter().position(|&x| x == b'\n') {
+            let line: Vec<u8> = self.buffer.drain(..end + 1).collect();
+            let line = String::from_utf8_lossy(&line);
+            if let Some(x) = Action::from_name(line.trim()) {
+                actions.push(x);
+            }
+        }
+        actions
+    }
+}
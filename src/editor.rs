@@ -0,0 +1,519 @@
+// Copyright © 2018 William Haugen - Piemyth
+// [This work is licensed under the "BSD 2-Clause License"]
+// Please see the file LICENSE in the source
+// distribution of this software for license terms.
+
+//Making stages from inside the game and saving them to the wave file.
+//A cursor sits on a square of the playfield, moved with the arrows or the
+//mouse, and whichever wave or rock shower is selected gets changed there:
+//  Space or click     add or take away the selected wave's slot at the
+//                     cursor, or move the selected rocks to its column
+//  W / R              new wave with a slot at the cursor / new rocks down
+//                     the cursor's column
+//  Tab                select the next wave or rocks in the stage
+//  X                  delete what's selected, or the stage once it's empty
+//  [ ]                come in a tick earlier or later
+//  - =                a tick less or more between each one
+//  , .                hold a tick less or more, or one rock less or more
+//  P / K              next path / next kind of enemy
+//  1 2 3              put the start or a control point of a curve at the
+//                     cursor, which makes the wave's path a curve
+//  Page Up / Down     previous or next stage, N adds one after this one
+//Previewing, saving and leaving the editor are up to whatever runs it.
+
+use graphics::{self, text, Context, Transformed};
+use opengl_graphics::{GlGraphics, GlyphCache};
+use piston::input::Key;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::Path;
+use std::rc::Rc;
+
+use game::Game;
+use waves::{self, Entry, Kind, Shower, Stage, Wave, Waves};
+use {GRIDSIZE, HEIGHT, WIDTH};
+
+//Seed every preview starts from, so it plays the same each time.
+static PREVIEWSEED: u64 = 1;
+
+#[derive(Clone, Copy, PartialEq)]
+enum Selected {
+    Nothing,
+    Wave(usize),
+    Rocks(usize),
+}
+
+pub struct Editor {
+    pub waves: Waves,
+    //Stage being edited.
+    pub stage: usize,
+    selected: Selected,
+    pub cursor: (i64, i64),
+    //The stage is playing instead of being edited.
+    pub previewing: bool,
+    //What just happened, shown along the bottom.
+    pub message: String,
+}
+
+impl Editor {
+    //Edit a copy of the stages, there's always at least one to work on.
+    pub fn new(waves: &Waves) -> Editor {
+        let mut waves = waves.clone();
+        if waves.stages.is_empty() {
+            waves.stages.push(empty_stage(1));
+        }
+        let selected = if waves.stages[0].waves.is_empty() {
+            Selected::Nothing
+        } else {
+            Selected::Wave(0)
+        };
+
+        Editor {
+            waves,
+            stage: 0,
+            selected,
+            cursor: (WIDTH / GRIDSIZE / 2, 4),
+            previewing: false,
+            message: String::new(),
+        }
+    }
+
+    //Put the cursor on the square, as long as it's on the playfield.
+    pub fn point(&mut self, square: (i64, i64)) {
+        if square.0 >= 0 && square.0 < WIDTH / GRIDSIZE && square.1 >= 0 && square.1 <= LASTROW {
+            self.cursor = square;
+        }
+    }
+
+    //Something edited with a key press.
+    pub fn key(&mut self, key: Key) {
+        self.message.clear();
+        let (column, row) = self.cursor;
+        match key {
+            Key::Up => self.cursor.1 = (row - 1).max(0),
+            Key::Down => self.cursor.1 = (row + 1).min(LASTROW),
+            Key::Left => self.cursor.0 = (column - 1).max(0),
+            Key::Right => self.cursor.0 = (column + 1).min(WIDTH / GRIDSIZE - 1),
+            Key::Space => self.place(),
+            Key::W => self.new_wave(),
+            Key::R => self.new_rocks(),
+            Key::Tab => self.next(),
+            Key::X => self.delete(),
+            Key::LeftBracket => self.change(|at, _, _| *at = nudge(*at, -1)),
+            Key::RightBracket => self.change(|at, _, _| *at = nudge(*at, 1)),
+            Key::Minus => self.change(|_, every, _| *every = nudge(*every, -1)),
+            Key::Equals => self.change(|_, every, _| *every = nudge(*every, 1)),
+            Key::Comma => self.change(|_, _, amount| *amount = nudge(*amount, -1)),
+            Key::Period => self.change(|_, _, amount| *amount = nudge(*amount, 1)),
+            Key::P => {
+                if let Some(x) = self.wave() {
+                    let next = (Entry::ALL.iter().position(|y| *y == x.entry).unwrap_or(0) + 1)
+                        % Entry::ALL.len();
+                    x.entry = Entry::ALL[next];
+                    //A curve needs somewhere to start, right above the first slot.
+                    if x.entry == Entry::Curve && x.curve.is_none() {
+                        let slot = x.slots[0];
+                        x.curve = Some([(slot.0, 0), (slot.0, 0), slot]);
+                    }
+                    if x.entry != Entry::Curve {
+                        x.curve = None;
+                    }
+                }
+            }
+            Key::K => {
+                if let Some(x) = self.wave() {
+                    let next = (Kind::ALL.iter().position(|y| *y == x.kind).unwrap_or(0) + 1)
+                        % Kind::ALL.len();
+                    x.kind = Kind::ALL[next];
+                }
+            }
+            Key::D1 | Key::D2 | Key::D3 => {
+                let point = match key {
+                    Key::D1 => 0,
+                    Key::D2 => 1,
+                    _ => 2,
+                };
+                let cursor = self.cursor;
+                if let Some(x) = self.wave() {
+                    let mut curve = x.curve.unwrap_or([cursor, cursor, cursor]);
+                    curve[point] = cursor;
+                    x.curve = Some(curve);
+                    x.entry = Entry::Curve;
+                }
+            }
+            Key::PageUp => self.select_stage(self.stage.saturating_sub(1)),
+            Key::PageDown => {
+                let last = self.waves.stages.len() - 1;
+                self.select_stage((self.stage + 1).min(last));
+            }
+            Key::N => {
+                let number = self.waves.stages.len() + 1;
+                self.waves
+                    .stages
+                    .insert(self.stage + 1, empty_stage(number));
+                self.select_stage(self.stage + 1);
+            }
+            _ => {}
+        }
+    }
+
+    //Space or a click, change the selected thing at the cursor.
+    pub fn place(&mut self) {
+        let cursor = self.cursor;
+        match self.selected {
+            Selected::Wave(x) => {
+                if !slot_fits(cursor) {
+                    self.message = "Slots can't go on the edges".to_string();
+                    return;
+                }
+                let slots = &mut self.waves.stages[self.stage].waves[x].slots;
+                match slots.iter().position(|y| *y == cursor) {
+                    Some(y) => {
+                        slots.remove(y);
+                    }
+                    None => slots.push(cursor),
+                }
+                //Every wave needs a slot, taking the last one away takes the wave.
+                if slots.is_empty() {
+                    self.delete();
+                }
+            }
+            Selected::Rocks(x) => {
+                self.waves.stages[self.stage].rocks[x].column = Some(column_fits(cursor.0));
+            }
+            Selected::Nothing => self.new_wave(),
+        }
+    }
+
+    //A playable copy of just the stage being edited. Nobody flies the ship
+    //and it can't run out of lives, so the stage can be watched all the way.
+    pub fn preview(&self) -> Game {
+        let waves = Waves {
+            stages: vec![self.waves.stages[self.stage].clone()],
+            scripts: self.waves.scripts.clone(),
+        };
+        let mut game = Game::turns(PREVIEWSEED).with_waves(&Rc::new(waves));
+        game.players[0].lives = u32::MAX;
+        game
+    }
+
+    //Write the stages to the wave file, keeping the comments at its top.
+    //Gives back the stages as they'll be loaded from now on.
+    pub fn save(&mut self, path: &Path) -> Result<Waves, String> {
+        let name = path.display().to_string();
+        let mut text = String::new();
+        if let Ok(old) = fs::read_to_string(path) {
+            for line in old.lines() {
+                if !line.starts_with('#') && !line.trim().is_empty() {
+                    break;
+                }
+                text.push_str(line);
+                text.push('\n');
+            }
+        }
+        text.push_str(&waves::to_string(&self.waves));
+
+        //Only write it if it'll load back.
+        let result = waves::parse(&text, &name, self.waves.scripts.clone()).and_then(|x| {
+            File::create(path)
+                .and_then(|mut file| file.write_all(text.as_bytes()))
+                .map_err(|e| format!("{}: {}", name, e))?;
+            Ok(x)
+        });
+        self.message = match result {
+            Ok(_) => format!("Saved to {}", name),
+            Err(ref e) => format!("Not saved, {}", e),
+        };
+        result
+    }
+
+    fn select_stage(&mut self, stage: usize) {
+        self.stage = stage;
+        self.selected = if self.waves.stages[stage].waves.is_empty() {
+            Selected::Nothing
+        } else {
+            Selected::Wave(0)
+        };
+    }
+
+    //The selected wave, if it's a wave that's selected.
+    fn wave(&mut self) -> Option<&mut Wave> {
+        match self.selected {
+            Selected::Wave(x) => Some(&mut self.waves.stages[self.stage].waves[x]),
+            _ => None,
+        }
+    }
+
+    //When the selected thing starts, the time between each one, and how long
+    //a wave holds or how many rocks there are.
+    fn change<F: Fn(&mut u64, &mut u64, &mut u64)>(&mut self, f: F) {
+        let stage = &mut self.waves.stages[self.stage];
+        match self.selected {
+            Selected::Wave(x) => {
+                let x = &mut stage.waves[x];
+                f(&mut x.at, &mut x.every, &mut x.hold);
+            }
+            Selected::Rocks(x) => {
+                let x = &mut stage.rocks[x];
+                f(&mut x.at, &mut x.every, &mut x.count);
+                //A shower needs at least one rock.
+                x.count = x.count.max(1);
+            }
+            Selected::Nothing => {}
+        }
+    }
+
+    fn new_wave(&mut self) {
+        if !slot_fits(self.cursor) {
+            self.message = "Slots can't go on the edges".to_string();
+            return;
+        }
+        let at = self.at();
+        let stage = &mut self.waves.stages[self.stage];
+        stage.waves.push(Wave {
+            at,
+            every: 0,
+            kind: Kind::Drone,
+            entry: Entry::Straight,
+            slots: vec![self.cursor],
+            hold: 0,
+            script: None,
+            curve: None,
+        });
+        self.selected = Selected::Wave(stage.waves.len() - 1);
+    }
+
+    fn new_rocks(&mut self) {
+        let at = self.at();
+        let stage = &mut self.waves.stages[self.stage];
+        stage.rocks.push(Shower {
+            at,
+            every: 0,
+            count: 1,
+            column: Some(column_fits(self.cursor.0)),
+        });
+        self.selected = Selected::Rocks(stage.rocks.len() - 1);
+    }
+
+    //New things come in with whatever's selected.
+    fn at(&self) -> u64 {
+        let stage = &self.waves.stages[self.stage];
+        match self.selected {
+            Selected::Wave(x) => stage.waves[x].at,
+            Selected::Rocks(x) => stage.rocks[x].at,
+            Selected::Nothing => 0,
+        }
+    }
+
+    //Waves first, then rocks, then back around.
+    fn next(&mut self) {
+        let stage = &self.waves.stages[self.stage];
+        let all: Vec<Selected> = (0..stage.waves.len())
+            .map(Selected::Wave)
+            .chain((0..stage.rocks.len()).map(Selected::Rocks))
+            .collect();
+        if all.is_empty() {
+            return;
+        }
+        let index = all.iter().position(|x| *x == self.selected);
+        self.selected = all[index.map(|x| (x + 1) % all.len()).unwrap_or(0)];
+    }
+
+    fn delete(&mut self) {
+        let stage = &mut self.waves.stages[self.stage];
+        match self.selected {
+            Selected::Wave(x) => {
+                stage.waves.remove(x);
+            }
+            Selected::Rocks(x) => {
+                stage.rocks.remove(x);
+            }
+            Selected::Nothing => {
+                if self.waves.stages.len() > 1 {
+                    self.waves.stages.remove(self.stage);
+                    self.stage = self.stage.min(self.waves.stages.len() - 1);
+                }
+            }
+        }
+        self.select_stage(self.stage);
+    }
+
+    //What's selected, in words.
+    fn describe(&self) -> String {
+        let stage = &self.waves.stages[self.stage];
+        match self.selected {
+            Selected::Wave(x) => {
+                let wave = &stage.waves[x];
+                let mut text = format!(
+                    "Wave {}/{}  at {} every {} hold {}  {} {}",
+                    x + 1,
+                    stage.waves.len(),
+                    wave.at,
+                    wave.every,
+                    wave.hold,
+                    wave.kind.name(),
+                    wave.entry.name()
+                );
+                if let Some(ref y) = wave.script {
+                    text.push_str(&format!(" script {}", y));
+                }
+                text
+            }
+            Selected::Rocks(x) => {
+                let rocks = &stage.rocks[x];
+                let column = match rocks.column {
+                    Some(y) => format!("column {}", y),
+                    None => "at a player".to_string(),
+                };
+                format!(
+                    "Rocks {}/{}  at {} every {} count {}  {}",
+                    x + 1,
+                    stage.rocks.len(),
+                    rocks.at,
+                    rocks.every,
+                    rocks.count,
+                    column
+                )
+            }
+            Selected::Nothing => "Nothing selected, W adds a wave".to_string(),
+        }
+    }
+
+    //Draw the stage over the playfield, in playfield coordinates.
+    pub fn draw(&self, c: &Context, gl: &mut GlGraphics, glyphs: &mut GlyphCache) {
+        let stage = &self.waves.stages[self.stage];
+        let size = GRIDSIZE as f64;
+        let square = |x: (i64, i64)| [(x.0 * GRIDSIZE) as f64, (x.1 * GRIDSIZE) as f64, size, size];
+
+        if !self.previewing {
+            //Faint grid to line things up against.
+            let line = graphics::Line::new([1.0, 1.0, 1.0, 0.06], 0.5);
+            for x in 0..WIDTH / GRIDSIZE {
+                let x = (x * GRIDSIZE) as f64;
+                line.draw([x, 0.0, x, HEIGHT as f64], &c.draw_state, c.transform, gl);
+            }
+            for y in 0..HEIGHT / GRIDSIZE {
+                let y = (y * GRIDSIZE) as f64;
+                line.draw([0.0, y, WIDTH as f64, y], &c.draw_state, c.transform, gl);
+            }
+
+            //Columns rocks come down.
+            for (i, x) in stage.rocks.iter().enumerate() {
+                if let Some(column) = x.column {
+                    let alpha = if self.selected == Selected::Rocks(i) {
+                        0.3
+                    } else {
+                        0.12
+                    };
+                    let stripe = [(column * GRIDSIZE) as f64, 0.0, size, HEIGHT as f64];
+                    graphics::rectangle([1.0, 0.6, 0.2, alpha], stripe, c.transform, gl);
+                }
+            }
+
+            //Formation slots, the selected wave's brighter.
+            for (i, x) in stage.waves.iter().enumerate() {
+                let selected = self.selected == Selected::Wave(i);
+                let color = if selected {
+                    [1.0, 0.85, 0.2, 0.7]
+                } else {
+                    [0.6, 0.6, 0.6, 0.4]
+                };
+                for y in x.slots.iter() {
+                    graphics::rectangle(color, square(*y), c.transform, gl);
+                }
+
+                //Where each one flies in along the curve, and the points bending it.
+                if let (true, Some(curve)) = (selected, x.curve) {
+                    let dot = size / 4.0;
+                    for slot in x.slots.iter() {
+                        let mut tick = 0;
+                        loop {
+                            let point = waves::curve_point(&curve, *slot, tick);
+                            let rect = [
+                                (point.0 * GRIDSIZE) as f64 + (size - dot) / 2.0,
+                                (point.1 * GRIDSIZE) as f64 + (size - dot) / 2.0,
+                                dot,
+                                dot,
+                            ];
+                            graphics::rectangle([0.3, 0.9, 1.0, 0.8], rect, c.transform, gl);
+                            if point == *slot {
+                                break;
+                            }
+                            tick += 1;
+                        }
+                    }
+                    for (i, point) in curve.iter().enumerate() {
+                        graphics::rectangle([1.0, 0.3, 1.0, 0.5], square(*point), c.transform, gl);
+                        let transform = c.transform.trans(
+                            (point.0 * GRIDSIZE) as f64 + 6.0,
+                            (point.1 * GRIDSIZE) as f64 + 15.0,
+                        );
+                        text::Text::new_color([1.0, 1.0, 1.0, 1.0], 12)
+                            .draw(&(i + 1).to_string(), glyphs, &c.draw_state, transform, gl)
+                            .unwrap();
+                    }
+                }
+            }
+
+            graphics::Rectangle::new_border([1.0, 1.0, 1.0, 0.9], 1.0).draw(
+                square(self.cursor),
+                &c.draw_state,
+                c.transform,
+                gl,
+            );
+        }
+
+        let top = format!(
+            "Stage {}/{}  {}",
+            self.stage + 1,
+            self.waves.stages.len(),
+            stage.name
+        );
+        let middle = if self.previewing {
+            "Previewing, 'Enter' to stop".to_string()
+        } else {
+            self.describe()
+        };
+        let bottom = if self.message.is_empty() {
+            "'Enter' Preview  'S' Save  'Q' Back"
+        } else {
+            &self.message
+        };
+        for (i, line) in [top.as_str(), middle.as_str(), bottom].iter().enumerate() {
+            let y = if i < 2 {
+                16 + 18 * i as i64
+            } else {
+                HEIGHT - 8
+            };
+            let transform = c.transform.trans(4.0, y as f64);
+            text::Text::new_color([1.0, 1.0, 1.0, 1.0], 14)
+                .draw(line, glyphs, &c.draw_state, transform, gl)
+                .unwrap();
+        }
+    }
+}
+
+//Lowest row the cursor goes, the same as the lowest a slot can be.
+static LASTROW: i64 = HEIGHT / GRIDSIZE - 5;
+
+fn empty_stage(number: usize) -> Stage {
+    Stage {
+        name: format!("Stage {}", number),
+        waves: Vec::new(),
+        rocks: Vec::new(),
+    }
+}
+
+//Slots go where ships can be, off the edge columns.
+fn slot_fits(square: (i64, i64)) -> bool {
+    square.0 >= 1 && square.0 <= WIDTH / GRIDSIZE - 2
+}
+
+fn column_fits(column: i64) -> i64 {
+    column.max(1).min(WIDTH / GRIDSIZE - 2)
+}
+
+//Add to a number of ticks without going below 0.
+fn nudge(x: u64, by: i64) -> u64 {
+    (x as i64 + by).max(0) as u64
+}
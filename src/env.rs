@@ -0,0 +1,184 @@
+// Copyright © 2018 William Haugen - Piemyth
+// [This work is licensed under the "BSD 2-Clause License"]
+// Please see the file LICENSE in the source
+// distribution of this software for license terms.
+
+//The game wrapped up as a reset/step environment for training agents.
+//Nothing here opens a window, so it runs as fast as the game logic can go.
+
+use game::{Action, Game};
+use {GRIDSIZE, HEIGHT, WIDTH};
+
+//What's in each square of the observation grid.
+//If more than one thing is in a square the later one here wins.
+pub const EMPTY: u8 = 0;
+pub const ROCK: u8 = 1;
+pub const ENEMY: u8 = 2;
+pub const BULLET: u8 = 3;
+pub const SHIP: u8 = 4;
+//A shot fired by an enemy.
+pub const BOLT: u8 = 5;
+
+//Occupancy of the playfield, one byte per grid square stored row by row.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Observation {
+    pub width: usize,
+    pub height: usize,
+    pub grid: Vec<u8>,
+}
+
+//Result of a single step.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Step {
+    pub observation: Observation,
+    //Points scored during the step.
+    pub reward: u64,
+    //The player ship was hit, the episode is over.
+    pub done: bool,
+}
+
+pub struct Env {
+    game: Game,
+    done: bool,
+}
+
+impl Observation {
+    //What's in the square at column x, row y.
+    pub fn get(&self, x: usize, y: usize) -> u8 {
+        self.grid[y * self.width + x]
+    }
+}
+
+impl Env {
+    pub fn new(seed: u64) -> Env {
+        Env {
+            game: Game::seeded(seed),
+            done: false,
+        }
+    }
+
+    //Start a new episode, the same seed always gives the same game.
+    pub fn reset(&mut self, seed: u64) -> Observation {
+        self.game = Game::seeded(seed);
+        self.done = false;
+        self.observe()
+    }
+
+    //Do one action then advance the game by one tick.
+    //Stepping after done just keeps returning the final state.
+    pub fn step(&mut self, action: Action) -> Step {
+        if self.done {
+            return Step {
+                observation: self.observe(),
+                reward: 0,
+                done: true,
+            };
+        }
+
+        let before = self.game.players[0].score;
+        let mut hit = self.game.act(0, action);
+        if !hit {
+            hit = self.game.update();
+        }
+        self.done = hit;
+
+        //The env has nothing to draw, so throw away the events.
+        self.game.clear_events();
+
+        Step {
+            observation: self.observe(),
+            reward: self.game.players[0].score - before,
+            done: hit,
+        }
+    }
+
+    //The game being played, for anything the observation doesn't cover.
+    pub fn game(&self) -> &Game {
+        &self.game
+    }
+
+    //Build the occupancy grid of the current state.
+    pub fn observe(&mut self) -> Observation {
+        let width = (WIDTH / GRIDSIZE) as usize;
+        let height = (HEIGHT / GRIDSIZE) as usize;
+        let mut grid = vec![EMPTY; width * height];
+
+        {
+            let mut place = |pos: (i64, i64), cell: u8| {
+                if pos.0 >= 0 && pos.1 >= 0 && (pos.0 as usize) < width && (pos.1 as usize) < height
+                {
+                    grid[pos.1 as usize * width + pos.0 as usize] = cell;
+                }
+            };
+
+            for x in self.game.enemies.current_rock_pos() {
+                place(x, ROCK);
+            }
+            for x in self.game.enemies.current_pos() {
+                place(x, ENEMY);
+            }
+            for x in self.game.players.iter_mut().filter(|x| x.alive()) {
+                for y in x.ship.shots.iter() {
+                    place(y.get_pos(), BULLET);
+                }
+                place(x.ship.current_pos(), SHIP);
+            }
+            for x in self.game.enemies.shots.iter() {
+                place(x.get_pos(), BOLT);
+            }
+        }
+
+        Observation {
+            width,
+            height,
+            grid,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //Play through an episode with the actions taken in turn, up to ticks.
+    fn episode(env: &mut Env, ticks: usize) -> Vec<Step> {
+        let mut steps = Vec::new();
+        for i in 0..ticks {
+            let step = env.step(Action::ALL[i % Action::ALL.len()]);
+            let done = step.done;
+            steps.push(step);
+            if done {
+                break;
+            }
+        }
+        steps
+    }
+
+    #[test]
+    fn same_seed_same_steps() {
+        let steps = episode(&mut Env::new(7), 400);
+        assert_eq!(episode(&mut Env::new(7), 400), steps);
+        assert_ne!(episode(&mut Env::new(8), 400), steps);
+
+        //Each observation has the ship in it until it's hit.
+        let ship = |x: &Observation| x.grid.iter().filter(|&&y| y == SHIP).count();
+        assert!(steps.iter().all(|x| x.done || ship(&x.observation) == 1));
+    }
+
+    #[test]
+    fn reset_starts_over() {
+        let mut env = Env::new(3);
+        let fresh = Env::new(3).observe();
+        let steps = episode(&mut env, 1000);
+        assert!(steps.last().unwrap().done);
+
+        //Done stays done until a reset.
+        let after = env.step(Action::Shoot);
+        assert!(after.done);
+        assert_eq!(after.reward, 0);
+
+        assert_eq!(env.reset(3), fresh);
+        assert_eq!(env.game().ticks, 0);
+        assert_eq!(episode(&mut env, 1000), steps);
+    }
+}
@@ -0,0 +1,80 @@
+// Copyright © 2018 William Haugen - Piemyth
+// [This work is licensed under the "BSD 2-Clause License"]
+// Please see the file LICENSE in the source
+// distribution of this software for license terms.
+
+//What happened in a game, queued up as it plays so that anything wanting to
+//know, like effects, achievements or sound, can go through the queue instead
+//of being wired into the game. Players are counted from 0 and positions are
+//grid squares.
+
+use waves::Kind;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Event {
+    //A player fired a shot, starting at pos.
+    ShotFired {
+        player: usize,
+        pos: (i64, i64),
+    },
+    //A player's shot brought down an enemy, worth points with the combo
+    //multiplier it was brought down at taken into account.
+    EnemyDestroyed {
+        player: usize,
+        kind: Kind,
+        pos: (i64, i64),
+        points: u64,
+        multiplier: u64,
+    },
+    //A player's shot took a hit point off an enemy that's still flying.
+    EnemyDamaged {
+        player: usize,
+        kind: Kind,
+        pos: (i64, i64),
+    },
+    //A player's shot hit a rock, which doesn't break but is worth points.
+    RockHit {
+        player: usize,
+        pos: (i64, i64),
+        points: u64,
+    },
+    //An enemy flew into a player's ship and went down with it.
+    EnemyCrashed {
+        player: usize,
+        kind: Kind,
+        pos: (i64, i64),
+    },
+    //A rock or an enemy shot struck a player's ship.
+    Impact {
+        player: usize,
+        pos: (i64, i64),
+    },
+    //A player's ship was hit, leaving them with lives.
+    PlayerHit {
+        player: usize,
+        pos: (i64, i64),
+        lives: u32,
+    },
+    //Everything in the stage was dealt with, the next one starts now.
+    StageCleared {
+        stage: usize,
+    },
+    //The game picked up pace, so the stars should warp ahead.
+    Warp,
+}
+
+impl Event {
+    //Player the event is about, if it's about one.
+    pub fn player(&self) -> Option<usize> {
+        match *self {
+            Event::ShotFired { player, .. }
+            | Event::EnemyDestroyed { player, .. }
+            | Event::EnemyDamaged { player, .. }
+            | Event::RockHit { player, .. }
+            | Event::EnemyCrashed { player, .. }
+            | Event::Impact { player, .. }
+            | Event::PlayerHit { player, .. } => Some(player),
+            Event::StageCleared { .. } | Event::Warp => None,
+        }
+    }
+}
@@ -0,0 +1,1380 @@
+// Copyright © 2018 William Haugen - Piemyth
+// [This work is licensed under the "BSD 2-Clause License"]
+// Please see the file LICENSE in the source
+// distribution of this software for license terms.
+
+use rand::Rng;
+use std::mem;
+use std::rc::Rc;
+
+use events::Event;
+use rng::GameRng;
+use script::Context;
+use stats::Stats;
+use waves::{self, Entry, Kind, Stage, Wave, Waves};
+use {GRIDSIZE, HEIGHT, POINTS, SPAWNRATE, WIDTH};
+
+//Lives each player gets when playing with more than one.
+static LIVES: u32 = 3;
+//Most enemies there can be at once, so scripts spawning each other
+//can't run away.
+static MAXENEMIES: usize = 100;
+//Ticks after a kill that the next one has to come in to keep a combo going.
+static COMBOTICKS: u64 = 6 * 2;
+//Highest a combo's multiplier goes.
+static MAXMULTIPLIER: u64 = 8;
+//Ticks an enemy flies by its script before it gives up and falls away,
+//so one that never comes down can't hold up the end of a stage.
+static SCRIPTTICKS: u64 = 6 * 20;
+
+//Everything the player can do in a single input.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Action {
+    Idle,
+    Up,
+    Down,
+    Left,
+    Right,
+    Shoot,
+}
+
+impl Action {
+    pub const ALL: [Action; 6] = [
+        Action::Idle,
+        Action::Up,
+        Action::Down,
+        Action::Left,
+        Action::Right,
+        Action::Shoot,
+    ];
+
+    //Name used for the action in replays and over the network.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Action::Idle => "idle",
+            Action::Up => "up",
+            Action::Down => "down",
+            Action::Left => "left",
+            Action::Right => "right",
+            Action::Shoot => "shoot",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Action> {
+        Action::ALL.iter().find(|x| x.name() == name).cloned()
+    }
+}
+
+//The game itself, without anything to do with drawing it.
+//Renderers read the state from here and draw it however they like.
+//
+//Starting out layout was used from the examples
+//in the Piston Library and this video
+//https://www.youtube.com/watch?v=HCwMb0KslX8
+//
+//Everything about a game in progress. Cloning it takes a snapshot
+//that plays out exactly the same from there on.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Game {
+    pub players: Vec<Player>,
+    pub enemies: Enemy,
+    pub ticks: u64,
+    pub spawnrate: u64,
+    pub rng: GameRng,
+    //Everything that happened since they were last taken.
+    pub events: Vec<Event>,
+    //Lives each player starts with.
+    pub lives: u32,
+    //Stages to play through before carrying on endlessly.
+    pub waves: Rc<Waves>,
+    //Stage being played, anything past the last one is endless.
+    pub stage: usize,
+    //Ticks since the stage started.
+    pub stage_ticks: u64,
+}
+
+//A ship along with everything that belongs to whoever is flying it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Player {
+    pub ship: Ship,
+    pub score: u64,
+    pub lives: u32,
+    //Column the ship starts and respawns in.
+    pub start: i64,
+    //Kills are worth this many times their points while a combo is going.
+    pub multiplier: u64,
+    //Ticks left for another kill to keep the combo going.
+    pub combo_ticks: u64,
+    pub stats: Stats,
+}
+
+//Something a player's shot or ship ran into.
+#[derive(Clone)]
+struct Hit {
+    //Where the shot or ship was, and where what it ran into was.
+    pos: (i64, i64),
+    at: (i64, i64),
+    points: u64,
+    //Kind of enemy hit, and whether it was brought down, which counts
+    //towards a combo.
+    kind: Option<Kind>,
+    kill: bool,
+}
+
+impl Default for Game {
+    fn default() -> Game {
+        Game::new()
+    }
+}
+
+impl Game {
+    pub fn new() -> Game {
+        Game::seeded(GameRng::from_entropy().state())
+    }
+
+    //A game where the same seed and inputs always play out the same.
+    //One player, and one hit is game over.
+    pub fn seeded(seed: u64) -> Game {
+        Game::with_players(seed, &[10], 1)
+    }
+
+    //One player with a few lives, for when players take turns
+    //each with their own game.
+    pub fn turns(seed: u64) -> Game {
+        Game::with_players(seed, &[10], LIVES)
+    }
+
+    //Two players at once, sharing the screen.
+    //Game over once both are out of lives.
+    pub fn coop(seed: u64) -> Game {
+        Game::with_players(seed, &[7, 13], LIVES)
+    }
+
+    //Start a player in each of the columns given.
+    fn with_players(seed: u64, columns: &[i64], lives: u32) -> Game {
+        Game {
+            players: columns.iter().map(|&x| Player::new(x, lives)).collect(),
+            enemies: Enemy {
+                list: Vec::new(),
+                rocks: Vec::new(),
+                shots: Vec::new(),
+            },
+            ticks: 0,
+            spawnrate: SPAWNRATE,
+            rng: GameRng::new(seed),
+            events: Vec::new(),
+            lives,
+            waves: Rc::new(Waves::endless()),
+            stage: 0,
+            stage_ticks: 0,
+        }
+    }
+
+    //Play through the stages first, the game starts over from the first.
+    pub fn with_waves(mut self, waves: &Rc<Waves>) -> Game {
+        self.waves = Rc::clone(waves);
+        self
+    }
+
+    pub fn score(&mut self) -> String {
+        //Get the score to be rendered down in main.
+        if self.players.len() == 1 {
+            let mut score = "Score: ".to_string();
+            score.push_str(&self.players[0].score.to_string());
+            return score;
+        }
+
+        //Everybody's score and lives left when there's more than one player.
+        let scores: Vec<String> = self
+            .players
+            .iter()
+            .enumerate()
+            .map(|(i, x)| format!("P{}: {} x{}", i + 1, x.score, x.lives))
+            .collect();
+        scores.join("  ")
+    }
+
+    //Multipliers of any combos going, for the HUD. Empty if there aren't any.
+    pub fn combo(&self) -> String {
+        if self.players.len() == 1 {
+            return match self.players[0].multiplier {
+                x if x > 1 => format!("x{}", x),
+                _ => String::new(),
+            };
+        }
+        let combos: Vec<String> = self
+            .players
+            .iter()
+            .enumerate()
+            .filter(|(_, x)| x.multiplier > 1)
+            .map(|(i, x)| format!("P{} x{}", i + 1, x.multiplier))
+            .collect();
+        combos.join("  ")
+    }
+
+    //Points from every player added up.
+    pub fn total_score(&self) -> u64 {
+        self.players.iter().map(|x| x.score).sum()
+    }
+
+    //Everyone is out of lives.
+    pub fn over(&self) -> bool {
+        self.players.iter().all(|x| !x.alive())
+    }
+
+    //Update based on event args time
+    //Returns true if the game is over.
+    pub fn update(&mut self) -> bool {
+        if self.over() {
+            return true;
+        }
+
+        let waves = Rc::clone(&self.waves);
+        match waves.stages.get(self.stage) {
+            Some(stage) => self.play_stage(stage),
+            None => self.play_endless(),
+        }
+        self.run_scripts();
+
+        self.ticks += 1;
+        self.stage_ticks += 1;
+
+        for x in self.players.iter_mut().filter(|x| x.alive()) {
+            //A shot going off the top missed, which ends a combo.
+            let missed = x.ship.update(false);
+            x.stats.ticks += 1;
+            x.combo_ticks = x.combo_ticks.saturating_sub(1);
+            if missed || x.combo_ticks == 0 {
+                x.break_combo();
+            }
+        }
+        self.collide(false);
+
+        self.over()
+    }
+
+    //Bring in whatever the stage has coming this tick, moving on to the
+    //next stage once everything has come in and the enemies are all gone.
+    fn play_stage(&mut self, stage: &Stage) {
+        if self.stage_ticks > stage.last() && self.enemies.list.is_empty() {
+            self.events.push(Event::StageCleared { stage: self.stage });
+            self.events.push(Event::Warp);
+            self.stage += 1;
+            self.stage_ticks = 0;
+            let waves = Rc::clone(&self.waves);
+            match waves.stages.get(self.stage) {
+                Some(x) => return self.play_stage(x),
+                None => return self.play_endless(),
+            }
+        }
+
+        for wave in stage.waves.iter() {
+            for (i, slot) in wave.slots.iter().enumerate() {
+                if wave.time(i) == self.stage_ticks {
+                    self.enemies.spawnwave(wave, *slot);
+                }
+            }
+        }
+        for shower in stage.rocks.iter() {
+            for i in 0..shower.count {
+                if shower.time(i) == self.stage_ticks {
+                    let column = match shower.column {
+                        Some(x) => x,
+                        None => self.target(),
+                    };
+                    self.enemies.spawnrock(column);
+                }
+            }
+        }
+    }
+
+    //Enemies and rocks coming in faster and faster for as long as it lasts.
+    fn play_endless(&mut self) {
+        //Spawning system for enemy ships.
+        //Will span more as time goes on to a limit of 5 ships per tick and
+        //One astroid every 3 and 7 game ticks.
+        let ticks = self.stage_ticks;
+        let spawns = ((ticks as f64 / self.spawnrate as f64).sqrt() / (SPAWNRATE * 10) as f64)
+            .clamp(1.0, 5.0);
+        for _ in 0..spawns as u64 {
+            self.enemies.spawnship(&mut self.rng);
+        }
+
+        if (ticks.is_multiple_of(3) && ticks > 240) || (ticks.is_multiple_of(7) && ticks > 60) {
+            let target = self.target();
+            self.enemies.spawnrock(target);
+        }
+
+        //Warp ahead each time the rocks start coming in faster.
+        if ticks == 60 || ticks == 240 {
+            self.events.push(Event::Warp);
+        }
+    }
+
+    //Let every enemy flying by a script decide what it does this tick.
+    fn run_scripts(&mut self) {
+        let waves = Rc::clone(&self.waves);
+        let players: Vec<(i64, i64)> = self
+            .players
+            .iter()
+            .filter(|x| x.alive())
+            .map(|x| (x.ship.pos_x, x.ship.pos_y))
+            .collect();
+        let mut spawns = Vec::new();
+
+        for enemy in self.enemies.list.iter_mut() {
+            let position = (enemy.pos_x, enemy.pos_y);
+            let flight = match enemy.flight {
+                Some(ref mut x) => x,
+                None => continue,
+            };
+            let script = match flight.script {
+                Some(ref x) => waves.scripts.get(x),
+                None => continue,
+            };
+            //Script went missing or has had long enough, just fall.
+            let script = match script {
+                Some(x) if flight.age < SCRIPTTICKS => x,
+                _ => {
+                    flight.step = (0, 1);
+                    continue;
+                }
+            };
+
+            let player = players
+                .iter()
+                .min_by_key(|x| (x.0 - position.0).abs() + (x.1 - position.1).abs())
+                .cloned()
+                .unwrap_or(position);
+            let context = Context {
+                position,
+                age: flight.age,
+                tick: self.ticks,
+                player,
+                slot: flight.slot,
+            };
+            let orders = script.run(&context, &mut self.rng);
+
+            flight.step = orders.step;
+            if orders.fire {
+                self.enemies.shots.push(Bullet {
+                    pos_x: position.0,
+                    pos_y: position.1 + 1,
+                });
+            }
+            for (name, x, y) in orders.spawns {
+                spawns.push((flight.kind, name, (x, y)));
+            }
+        }
+
+        for (kind, name, place) in spawns {
+            if self.enemies.list.len() < MAXENEMIES {
+                self.enemies.spawnscript(kind, name, place);
+            }
+        }
+    }
+
+    //Rocks go for a player, picked at random if there's more than one.
+    fn target(&mut self) -> i64 {
+        let targets: Vec<i64> = self
+            .players
+            .iter()
+            .filter(|x| x.alive())
+            .map(|x| x.ship.pos_x)
+            .collect();
+        if targets.len() > 1 {
+            targets[self.rng.gen_range(0, targets.len())]
+        } else {
+            targets[0]
+        }
+    }
+
+    //Update a player's movement or shoot depending on input,
+    //then check if that caused any collisions.
+    //Returns true if the game is over.
+    pub fn act(&mut self, player: usize, action: Action) -> bool {
+        let fired = match self.players.get_mut(player) {
+            Some(x) if x.alive() => {
+                let shots = x.ship.shots.len();
+                x.ship.kmove(action);
+                x.ship.shots.get(shots).map(|x| x.get_pos())
+            }
+            _ => return self.over(),
+        };
+        if let Some(pos) = fired {
+            self.record(Event::ShotFired { player, pos });
+        }
+        self.collide(true);
+
+        self.over()
+    }
+
+    //One whole tick played the same way every time, each player's actions
+    //in player order and then everything else moves.
+    //Returns true if the game is over.
+    pub fn advance(&mut self, actions: &[Vec<Action>]) -> bool {
+        for (player, list) in actions.iter().enumerate() {
+            for x in list.iter() {
+                self.act(player, *x);
+            }
+        }
+        self.update()
+    }
+
+    //Check every player's ship and shots against the enemies and rocks.
+    //Movement is true if this is for a player input, so nothing else moves.
+    fn collide(&mut self, movement: bool) {
+        let active: Vec<usize> = (0..self.players.len())
+            .filter(|&x| self.players[x].alive())
+            .collect();
+        let hits = {
+            let mut ships: Vec<&mut Ship> = self
+                .players
+                .iter_mut()
+                .filter(|x| x.alive())
+                .map(|x| &mut x.ship)
+                .collect();
+            self.enemies.update(&mut ships, movement)
+        };
+
+        //Shots only ever hit enemies and rocks, so players can't hurt each other.
+        let mut events = Vec::new();
+        for (index, hits) in active.into_iter().zip(hits) {
+            let player = &mut self.players[index];
+            let (crash, scored) = player.ship.collision(hits);
+            for x in scored {
+                //Kills one after another raise the multiplier.
+                let points = if x.kill {
+                    player.multiplier = if player.combo_ticks > 0 {
+                        (player.multiplier + 1).min(MAXMULTIPLIER)
+                    } else {
+                        1
+                    };
+                    player.combo_ticks = COMBOTICKS;
+                    x.points * player.multiplier
+                } else {
+                    x.points
+                };
+                player.score += points;
+                let pos = x.at;
+                events.push(match x.kind {
+                    Some(kind) if x.kill => Event::EnemyDestroyed {
+                        player: index,
+                        kind,
+                        pos,
+                        points,
+                        multiplier: player.multiplier,
+                    },
+                    Some(kind) => Event::EnemyDamaged {
+                        player: index,
+                        kind,
+                        pos,
+                    },
+                    None => Event::RockHit {
+                        player: index,
+                        pos,
+                        points,
+                    },
+                });
+            }
+
+            if let Some(x) = crash {
+                events.push(match x.kind {
+                    Some(kind) => Event::EnemyCrashed {
+                        player: index,
+                        kind,
+                        pos: x.at,
+                    },
+                    None => Event::Impact {
+                        player: index,
+                        pos: x.at,
+                    },
+                });
+                player.break_combo();
+                player.lives -= 1;
+                events.push(Event::PlayerHit {
+                    player: index,
+                    pos: player.ship.current_pos(),
+                    lives: player.lives,
+                });
+                if player.alive() {
+                    let start = player.start;
+                    player.ship.restart(start);
+                }
+            }
+        }
+        for x in events {
+            self.record(x);
+        }
+    }
+
+    //Queue up an event, counting it towards the stats of whoever it's about.
+    fn record(&mut self, event: Event) {
+        if let Some(x) = event.player().and_then(|x| self.players.get_mut(x)) {
+            x.stats.count(&event);
+        }
+        self.events.push(event);
+    }
+
+    //Hash of everything that decides how the game plays out from here,
+    //two games with the same checksum are almost certainly the same.
+    //Events waiting to be taken only change how it looks, so they're left
+    //out. This is FNV-1a over all the numbers.
+    pub fn checksum(&self) -> u64 {
+        let mut hash: u64 = 0xCBF2_9CE4_8422_2325;
+        {
+            let mut feed = |x: i64| {
+                for byte in x.to_le_bytes().iter() {
+                    hash ^= *byte as u64;
+                    hash = hash.wrapping_mul(0x0000_0100_0000_01B3);
+                }
+            };
+
+            feed(self.ticks as i64);
+            feed(self.spawnrate as i64);
+            feed(self.rng.state() as i64);
+            feed(self.stage as i64);
+            feed(self.stage_ticks as i64);
+            for x in self.players.iter() {
+                feed(x.score as i64);
+                feed(x.lives as i64);
+                feed(x.multiplier as i64);
+                feed(x.combo_ticks as i64);
+                feed(x.stats.shots as i64);
+                feed(x.stats.hits as i64);
+                feed(x.stats.rocks as i64);
+                feed(x.stats.ticks as i64);
+                feed(x.stats.best_multiplier as i64);
+                for y in x.stats.destroyed.iter() {
+                    feed(*y as i64);
+                }
+                feed(x.ship.pos_x);
+                feed(x.ship.pos_y);
+                feed(x.ship.shots.len() as i64);
+                for y in x.ship.shots.iter() {
+                    feed(y.pos_x);
+                    feed(y.pos_y);
+                }
+            }
+            feed(self.enemies.shots.len() as i64);
+            for x in self.enemies.shots.iter() {
+                feed(x.pos_x);
+                feed(x.pos_y);
+            }
+            for list in [&self.enemies.list, &self.enemies.rocks].iter() {
+                feed(list.len() as i64);
+                for x in list.iter() {
+                    feed(x.pos_x);
+                    feed(x.pos_y);
+                    if let Some(ref y) = x.flight {
+                        for z in y.script.iter().flat_map(|z| z.bytes()) {
+                            feed(z as i64);
+                        }
+                        feed(y.slot.0);
+                        feed(y.slot.1);
+                        feed(y.hold as i64);
+                        feed(y.age as i64);
+                        feed(y.diving as i64);
+                        feed(Entry::ALL.iter().position(|z| *z == y.entry).unwrap_or(0) as i64);
+                        feed(Kind::ALL.iter().position(|z| *z == y.kind).unwrap_or(0) as i64);
+                        feed(y.hp as i64);
+                        for z in y.curve.iter().flat_map(|z| z.iter()) {
+                            feed(z.0);
+                            feed(z.1);
+                        }
+                    }
+                }
+            }
+        }
+        hash
+    }
+
+    //Everything that happened since the last time, oldest first.
+    pub fn take_events(&mut self) -> Vec<Event> {
+        mem::take(&mut self.events)
+    }
+
+    //Throw away events waiting to be taken, for when nothing is taking them.
+    pub fn clear_events(&mut self) {
+        self.events.clear();
+    }
+
+    //Restarts all sprites to default position or clear them
+    //Set all game values to 0.
+    pub fn restart(&mut self) {
+        let lives = self.lives;
+        for x in self.players.iter_mut() {
+            *x = Player::new(x.start, lives);
+        }
+        self.enemies.restart();
+        self.events.clear();
+        self.events.push(Event::Warp);
+        self.ticks = 0;
+        self.stage = 0;
+        self.stage_ticks = 0;
+    }
+}
+
+impl Player {
+    fn new(start: i64, lives: u32) -> Player {
+        let mut ship = Ship {
+            pos_x: 0,
+            pos_y: 0,
+            shots: Vec::new(),
+            flight: None,
+        };
+        ship.restart(start);
+
+        Player {
+            ship,
+            score: 0,
+            lives,
+            start,
+            multiplier: 1,
+            combo_ticks: 0,
+            stats: Stats::new(),
+        }
+    }
+
+    //Still has lives left, so the ship is in play.
+    pub fn alive(&self) -> bool {
+        self.lives > 0
+    }
+
+    fn break_combo(&mut self) {
+        self.multiplier = 1;
+        self.combo_ticks = 0;
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Ship {
+    pub pos_x: i64,
+    pub pos_y: i64,
+    pub shots: Vec<Bullet>,
+    //Where an enemy from a wave is headed, None for everything else.
+    pub flight: Option<Flight>,
+}
+
+//How an enemy from a wave gets to its formation slot and what it
+//does once it's there.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Flight {
+    pub kind: Kind,
+    pub entry: Entry,
+    //Column and row of the slot.
+    pub slot: (i64, i64),
+    //Ticks left to sit in the slot before diving.
+    pub hold: u64,
+    //Ticks since it came in.
+    pub age: u64,
+    //Has left the formation and is heading down.
+    pub diving: bool,
+    //Script it flies by instead, if it has one.
+    pub script: Option<String>,
+    //Move the script asked for this tick.
+    pub step: (i64, i64),
+    //Start and control points when it comes in along a curve.
+    pub curve: Option<[(i64, i64); 3]>,
+    //Shots left before it's brought down.
+    pub hp: u32,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Enemy {
+    pub list: Vec<Ship>,
+    pub rocks: Vec<Ship>,
+    //Shots fired by enemies, heading down.
+    pub shots: Vec<Bullet>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Bullet {
+    pub pos_x: i64,
+    pub pos_y: i64,
+}
+
+impl Ship {
+    //Moving the ship around or shooting
+    fn kmove(&mut self, action: Action) {
+        let updated_pos = match action {
+            Action::Up => (0, -1),
+            Action::Down => (0, 1),
+            Action::Left => (-1, 0),
+            Action::Right => (1, 0),
+            _ => (0, 0),
+        };
+
+        //Only allow 5 shots on the screen at a time.
+        if self.shots.len() < 5 && action == Action::Shoot {
+            let new_bullet = Bullet {
+                pos_x: self.pos_x,
+                pos_y: self.pos_y - 1,
+            };
+
+            self.shots.push(new_bullet);
+        }
+
+        //Set bounds fo where the ship can move.
+        if self.pos_x + updated_pos.0 < (WIDTH / GRIDSIZE - 1)
+            && self.pos_y + updated_pos.1 < (HEIGHT / GRIDSIZE - 3)
+            && self.pos_x + updated_pos.0 >= 1
+            && self.pos_y + updated_pos.1 > 3
+        {
+            self.pos_x += updated_pos.0;
+            self.pos_y += updated_pos.1;
+        }
+    }
+
+    //Update with gametick. Moved bool is to indicate if the player moved,
+    //or it was wiht the regular update of a gametick.
+    //Returns true if a shot went off the top without hitting anything.
+    fn update(&mut self, moved: bool) -> bool {
+        //Update aspects of the ship, mainly for the shots.
+        let mut index: usize = 0;
+        let mut to_remove: Vec<usize> = Vec::new();
+        if !moved {
+            for x in self.shots.iter_mut() {
+                x.update();
+
+                //If bullet goes above screen
+                if x.get_pos().1 < 0 {
+                    to_remove.push(index);
+                } else {
+                    index += 1;
+                }
+            }
+        }
+
+        let missed = !to_remove.is_empty();
+        self.remove_shots(to_remove);
+        missed
+    }
+
+    //Give current posotion of ship.
+    pub fn current_pos(&mut self) -> (i64, i64) {
+        (self.pos_x, self.pos_y)
+    }
+
+    fn remove_shots(&mut self, index: Vec<usize>) {
+        //Removing bullets that were found as out of bounds or hit something.
+        for x in index {
+            if x < self.shots.len() {
+                self.shots.remove(x);
+            }
+        }
+    }
+
+    //Take out the shots that hit something, giving what hit the ship itself
+    //if anything did and what the shots hit.
+    fn collision(&mut self, hits: Vec<Hit>) -> (Option<Hit>, Vec<Hit>) {
+        let mut index: usize = 0;
+        let mut scored: Vec<Hit> = Vec::new();
+        let mut to_remove: Vec<usize> = Vec::new();
+        let mut matched: bool = false;
+
+        //Check collision of the shots
+        for x in self.shots.iter_mut() {
+            let x = x.get_pos();
+            for y in hits.iter() {
+                //Check for coordinates to see if match.
+                //If there was a match, the hit goes back to be scored
+                //and displayed.
+                if x.0 == y.pos.0 && x.1 == y.pos.1 {
+                    to_remove.push(index);
+                    scored.push(y.clone());
+                    matched = true;
+                }
+            }
+            if !matched {
+                index += 1;
+            } else {
+                matched = false;
+            }
+        }
+
+        //Remove all shots that have a collision
+        self.remove_shots(to_remove);
+
+        //Check to see if player ship was hit or not.
+        let ship_pos = self.current_pos();
+        let crash = hits.into_iter().find(|x| x.pos == ship_pos);
+
+        (crash, scored)
+    }
+
+    //Clear the shots and reset ship to its starting column.
+    fn restart(&mut self, pos_x: i64) {
+        self.shots.clear();
+        self.pos_x = pos_x;
+        self.pos_y = 26;
+    }
+
+    //Points for shooting this down.
+    fn points(&self) -> u64 {
+        match self.flight {
+            Some(ref x) => x.kind.points(x.diving),
+            None => POINTS,
+        }
+    }
+
+    //Move an enemy on a tick. Ones that aren't from a wave just fall,
+    //the rest fly to their slot, wait there and then dive the way their
+    //kind does, some going after the nearest of the players.
+    //Returns true if it fires this tick.
+    fn fly(&mut self, players: &[(i64, i64)]) -> bool {
+        let (x, y) = (self.pos_x, self.pos_y);
+        let flight = match self.flight {
+            Some(ref mut x) => x,
+            None => {
+                self.pos_y += 1;
+                return false;
+            }
+        };
+        flight.age += 1;
+
+        //Scripts say where to go themselves, as long as it's on screen.
+        //Going up from above the top leaves for good.
+        if flight.script.is_some() {
+            self.pos_x = (x + flight.step.0).max(1).min(WIDTH / GRIDSIZE - 2);
+            self.pos_y = (y + flight.step.1).max(-2);
+            flight.step = (0, 0);
+            return false;
+        }
+
+        let mut dove = false;
+        if !flight.diving {
+            let (column, row) = match flight.curve {
+                Some(ref x) => waves::curve_point(x, flight.slot, flight.age),
+                None => flight.slot,
+            };
+            if (x, y) != flight.slot {
+                self.pos_x += (column - x).signum();
+                self.pos_y += (row - y).signum();
+                return false;
+            }
+            //Kamikazes don't wait around.
+            if flight.hold > 0 && flight.kind != Kind::Kamikaze {
+                flight.hold -= 1;
+                return flight.kind.fires(flight.age, false, false);
+            }
+            flight.diving = true;
+            dove = true;
+        }
+
+        let target = players
+            .iter()
+            .min_by_key(|z| (z.0 - x).abs() + (z.1 - y).abs())
+            .map_or(x, |z| z.0);
+        let toward = (target - x).signum();
+        let weave = if (flight.age / 2).is_multiple_of(2) {
+            1
+        } else {
+            -1
+        };
+        let step = match flight.kind {
+            Kind::Kamikaze => toward,
+            Kind::Butterfly if flight.age.is_multiple_of(2) => toward,
+            Kind::Boss if flight.age.is_multiple_of(3) => toward,
+            Kind::Zigzagger => weave,
+            _ if flight.entry == Entry::Zigzag => weave,
+            _ => 0,
+        };
+        self.pos_x = (x + step).max(1).min(WIDTH / GRIDSIZE - 2);
+        //Bosses are heavy and only come down every other tick.
+        if flight.kind != Kind::Boss || flight.age.is_multiple_of(2) {
+            self.pos_y += 1;
+        }
+        flight.kind.fires(flight.age, true, dove)
+    }
+}
+
+impl Bullet {
+    //Moves the bullet up the screen
+    fn update(&mut self) {
+        self.pos_y -= 1;
+    }
+
+    //Give shot's position in the form of a tuple.
+    pub fn get_pos(&self) -> (i64, i64) {
+        (self.pos_x, self.pos_y)
+    }
+}
+
+impl Enemy {
+    //Spawn ship's randomly on the x position.
+    fn spawnship(&mut self, rng: &mut GameRng) {
+        let pos_x: i64 = rng.gen_range(1, WIDTH / GRIDSIZE - 1);
+        let new_ship = Ship {
+            pos_x,
+            pos_y: -1,
+            shots: Vec::new(),
+            flight: None,
+        };
+        self.list.push(new_ship);
+    }
+
+    //Bring in an enemy from a wave, headed for its slot.
+    fn spawnwave(&mut self, wave: &Wave, slot: (i64, i64)) {
+        let columns = WIDTH / GRIDSIZE;
+        let (pos_x, pos_y) = match (wave.entry, wave.curve) {
+            (Entry::Curve, Some(x)) => x[0],
+            (Entry::Swoop, _) if slot.0 < columns / 2 => (columns - 2, -1),
+            (Entry::Swoop, _) => (1, -1),
+            _ => (slot.0, -1),
+        };
+        self.list.push(Ship {
+            pos_x,
+            pos_y,
+            shots: Vec::new(),
+            flight: Some(Flight {
+                kind: wave.kind,
+                entry: wave.entry,
+                slot,
+                hold: wave.hold,
+                age: 0,
+                diving: false,
+                script: wave.script.clone(),
+                step: (0, 0),
+                curve: wave.curve,
+                hp: wave.kind.hp(),
+            }),
+        });
+    }
+
+    //Bring in an enemy a script asked for, flying by the script named.
+    fn spawnscript(&mut self, kind: Kind, script: String, place: (i64, i64)) {
+        let pos_x = place.0.max(1).min(WIDTH / GRIDSIZE - 2);
+        let pos_y = place.1.max(-1);
+        self.list.push(Ship {
+            pos_x,
+            pos_y,
+            shots: Vec::new(),
+            flight: Some(Flight {
+                kind,
+                entry: Entry::Straight,
+                slot: (pos_x, pos_y),
+                hold: 0,
+                age: 0,
+                diving: true,
+                script: Some(script),
+                step: (0, 0),
+                curve: None,
+                hp: kind.hp(),
+            }),
+        });
+    }
+
+    //Creats a rock, set x position to the player ship's
+    //current x position.
+    fn spawnrock(&mut self, pos_x: i64) {
+        let new_ship = Ship {
+            pos_x,
+            pos_y: -1,
+            shots: Vec::new(),
+            flight: None,
+        };
+        self.rocks.push(new_ship);
+    }
+
+    //Check collision for enemy ships, giving what happened if one was
+    //there. A shot only takes one hit point, ships with more left are
+    //just damaged and worth nothing yet. Anything else brings it down.
+    fn ship_collision(&mut self, y: (i64, i64), shot: bool) -> Option<Hit> {
+        //Either on the position or one below it, so nothing passes through.
+        let index = self
+            .list
+            .iter()
+            .position(|x| x.pos_x == y.0 && (x.pos_y == y.1 || x.pos_y == y.1 + 1))?;
+        let x = (self.list[index].pos_x, self.list[index].pos_y);
+
+        if let (true, Some(flight)) = (shot, self.list[index].flight.as_mut()) {
+            if flight.hp > 1 {
+                flight.hp -= 1;
+                return Some(Hit {
+                    pos: y,
+                    at: x,
+                    points: 0,
+                    kind: Some(flight.kind),
+                    kill: false,
+                });
+            }
+        }
+        let ship = self.list.remove(index);
+        Some(Hit {
+            pos: y,
+            at: x,
+            points: ship.points(),
+            kind: Some(ship.flight.as_ref().map(|x| x.kind).unwrap_or(Kind::Drone)),
+            kill: true,
+        })
+    }
+
+    //Check if an enemy shot hit the position, the shot is used up if so.
+    fn shot_collision(&mut self, y: (i64, i64)) -> bool {
+        match self.shots.iter().position(|x| x.get_pos() == y) {
+            Some(x) => {
+                self.shots.remove(x);
+                true
+            }
+            None => false,
+        }
+    }
+
+    //Check collision of rocks, giving where the rock hit was.
+    fn rock_collision(&mut self, y: (i64, i64)) -> Option<(i64, i64)> {
+        let mut hit = None;
+        for x in self.rocks.iter_mut() {
+            let x = x.current_pos();
+            //Needs to check if it is on position or below one.
+            //Can pass through if it doesn't check below one.
+            if x.0 == y.0 && (x.1 == y.1 || x.1 == y.1 + 1) {
+                //Don't remove since rocks to get destroyed.
+                hit = Some(x);
+            }
+
+            //If there was a hit on that rock, exit out of loop
+            //No need to continue on.
+            if hit.is_some() {
+                break;
+            }
+        }
+
+        //Return result if rock was hit or not.
+        hit
+    }
+
+    //Check if anything ran into a player's ship, giving what it was.
+    fn crash(&mut self, ship_pos: (i64, i64)) -> Option<Hit> {
+        let hit = Hit {
+            pos: ship_pos,
+            at: ship_pos,
+            points: 0,
+            kind: None,
+            kill: false,
+        };
+        if let Some(x) = self.ship_collision(ship_pos, false) {
+            return Some(Hit {
+                at: x.at,
+                kind: x.kind,
+                ..hit
+            });
+        }
+        if let Some(at) = self.rock_collision(ship_pos) {
+            return Some(Hit { at, ..hit });
+        }
+        if self.shot_collision(ship_pos) {
+            return Some(hit);
+        }
+        None
+    }
+
+    //Update aspects of the ships, check for collisions with shots or ships.
+    //Returns a hit list for each of the player ships given, along with the
+    //points each hit was worth.
+    fn update(&mut self, ships: &mut [&mut Ship], movement: bool) -> Vec<Vec<Hit>> {
+        let mut all_hits: Vec<Vec<Hit>> = Vec::new();
+
+        for ship in ships.iter_mut() {
+            let ship_pos = ship.current_pos();
+            let mut hits: Vec<Hit> = Vec::new();
+            let mut prev_hits: Vec<(i64, i64)> = Vec::new();
+            let mut prev: bool = false;
+
+            //Checks the positions of all the shots
+            //Removes a the ship if hit and adds to a hit list
+            //hit list will get passed back to player ship
+            //and have collisions checked for there.
+            for x in ship.shots.iter_mut() {
+                let x = x.get_pos();
+                for y in prev_hits.iter_mut() {
+                    if x.0 == y.0 && x.1 == y.1 {
+                        prev = true;
+                    }
+                }
+
+                //If either ship or rock were hit, push the positions on the hit
+                //list for player ship to remove. Rocks don't break but are
+                //still worth points.
+                if prev {
+                    continue;
+                }
+                let hit = match self.ship_collision(x, true) {
+                    Some(hit) => Some(hit),
+                    None => self.rock_collision(x).map(|at| Hit {
+                        pos: x,
+                        at,
+                        points: POINTS,
+                        kind: None,
+                        kill: false,
+                    }),
+                };
+                if let Some(hit) = hit {
+                    hits.push(hit);
+                    prev_hits.push(x);
+                }
+            }
+
+            //Checks collision with player ship.
+            if let Some(hit) = self.crash(ship_pos) {
+                hits.push(hit);
+            }
+            all_hits.push(hits);
+        }
+
+        //If the update wasn't for a player input, move the rocks and ships.
+        if !movement {
+            let players: Vec<(i64, i64)> = ships.iter().map(|x| (x.pos_x, x.pos_y)).collect();
+            for x in self.list.iter_mut() {
+                if x.fly(&players) && x.pos_y >= 0 {
+                    self.shots.push(Bullet {
+                        pos_x: x.pos_x,
+                        pos_y: x.pos_y,
+                    });
+                }
+            }
+            for x in self.rocks.iter_mut() {
+                x.pos_y += 1;
+            }
+            for x in self.shots.iter_mut() {
+                x.pos_y += 1;
+            }
+
+            //Check collision against player ships again after the move.
+            for (ship, hits) in ships.iter_mut().zip(all_hits.iter_mut()) {
+                if let Some(hit) = self.crash(ship.current_pos()) {
+                    hits.push(hit);
+                }
+            }
+        }
+
+        //Remove any enemies or rocks that have gone below where the player can go,
+        //and enemies a script flew back off the top.
+        let mut index = 0;
+        for x in self.current_pos() {
+            //If enemy goes below
+            if x.1 > (HEIGHT / GRIDSIZE - 4) || x.1 < -1 {
+                self.list.remove(index);
+            } else {
+                //Only need to increment index if a ship wasn't removed.
+                index += 1;
+            }
+        }
+        //Reset index for use with rock positions.
+        index = 0;
+        for x in self.current_rock_pos() {
+            //If enemy goes below
+            if x.1 > (HEIGHT / GRIDSIZE - 4) {
+                self.rocks.remove(index);
+            } else {
+                index += 1;
+            }
+        }
+        //Enemy shots that went past the players.
+        self.shots.retain(|x| x.pos_y <= HEIGHT / GRIDSIZE - 4);
+
+        //Return the lists of all hits made on rocks and enemy ships.
+        all_hits
+    }
+
+    //Grabs the positions of all the ships.
+    pub fn current_pos(&mut self) -> Vec<(i64, i64)> {
+        let mut current_pos: Vec<(i64, i64)> = Vec::new();
+        for ships in self.list.iter() {
+            current_pos.push((ships.pos_x, ships.pos_y))
+        }
+        current_pos
+    }
+
+    //Grabs the positions of all rocks.
+    pub fn current_rock_pos(&mut self) -> Vec<(i64, i64)> {
+        let mut current_pos: Vec<(i64, i64)> = Vec::new();
+        for rock in self.rocks.iter() {
+            current_pos.push((rock.pos_x, rock.pos_y))
+        }
+        current_pos
+    }
+
+    //Clears all enemies and rocks on screen
+    fn restart(&mut self) {
+        self.list.clear();
+        self.rocks.clear();
+        self.shots.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //Put something that just falls right where player 0's next shot goes.
+    fn ahead(game: &Game) -> Ship {
+        let ship = &game.players[0].ship;
+        Ship {
+            pos_x: ship.pos_x,
+            pos_y: ship.pos_y - 1,
+            shots: Vec::new(),
+            flight: None,
+        }
+    }
+
+    //Shoot down an enemy put in the way.
+    fn kill(game: &mut Game) {
+        let enemy = ahead(game);
+        game.enemies.list.push(enemy);
+        game.act(0, Action::Shoot);
+    }
+
+    #[test]
+    fn kills_in_a_row_raise_the_multiplier() {
+        let mut game = Game::seeded(1);
+        let mut score = 0;
+        for i in 1..MAXMULTIPLIER + 3 {
+            kill(&mut game);
+            let multiplier = i.min(MAXMULTIPLIER);
+            score += POINTS * multiplier;
+            assert_eq!(game.players[0].multiplier, multiplier);
+            assert_eq!(game.players[0].score, score);
+        }
+        assert_eq!(game.players[0].stats.best_multiplier, MAXMULTIPLIER);
+        assert_eq!(game.combo(), format!("x{}", MAXMULTIPLIER));
+    }
+
+    #[test]
+    fn missing_breaks_the_combo() {
+        let mut game = Game::seeded(1);
+        kill(&mut game);
+        kill(&mut game);
+        assert_eq!(game.players[0].multiplier, 2);
+
+        //A shot about to go off the top.
+        let shot = Bullet { pos_x: 3, pos_y: 0 };
+        game.players[0].ship.shots.push(shot);
+        game.update();
+        assert_eq!(game.players[0].multiplier, 1);
+        assert_eq!(game.combo(), "");
+        kill(&mut game);
+        assert_eq!(game.players[0].multiplier, 1);
+    }
+
+    #[test]
+    fn combo_runs_out_of_time() {
+        let mut game = Game::seeded(1);
+        kill(&mut game);
+        for _ in 1..COMBOTICKS {
+            game.update();
+        }
+        kill(&mut game);
+        assert_eq!(game.players[0].multiplier, 2);
+
+        for _ in 0..COMBOTICKS {
+            game.update();
+        }
+        assert_eq!(game.players[0].multiplier, 1);
+        kill(&mut game);
+        assert_eq!(game.players[0].multiplier, 1);
+        assert_eq!(game.players[0].score, POINTS * 4);
+    }
+
+    #[test]
+    fn shots_say_what_they_hit() {
+        let mut game = Game::seeded(1);
+        let pos = (10, 25);
+        let shot = Event::ShotFired { player: 0, pos };
+
+        kill(&mut game);
+        let destroyed = Event::EnemyDestroyed {
+            player: 0,
+            kind: Kind::Drone,
+            pos,
+            points: POINTS,
+            multiplier: 1,
+        };
+        assert_eq!(game.take_events(), vec![shot.clone(), destroyed]);
+
+        let boss = "none".to_string();
+        game.enemies.spawnscript(Kind::Boss, boss, pos);
+        game.act(0, Action::Shoot);
+        let damaged = Event::EnemyDamaged {
+            player: 0,
+            kind: Kind::Boss,
+            pos,
+        };
+        assert_eq!(game.take_events(), vec![shot.clone(), damaged]);
+
+        game.enemies.list.clear();
+        let rock = ahead(&game);
+        game.enemies.rocks.push(rock);
+        game.act(0, Action::Shoot);
+        let hit = Event::RockHit {
+            player: 0,
+            pos,
+            points: POINTS,
+        };
+        assert_eq!(game.take_events(), vec![shot, hit]);
+
+        //Stats are counted from the same events.
+        let stats = &game.players[0].stats;
+        assert_eq!((stats.shots, stats.hits, stats.rocks), (3, 3, 1));
+        assert_eq!(stats.destroyed_of(Kind::Drone), 1);
+    }
+
+    #[test]
+    fn crashes_say_what_hit_the_ship() {
+        let mut game = Game::coop(1);
+        let one = game.players[0].ship.current_pos();
+        let two = game.players[1].ship.current_pos();
+        let lost = |player, pos, lives| Event::PlayerHit { player, pos, lives };
+
+        game.enemies.list.push(Ship {
+            pos_x: one.0,
+            pos_y: one.1,
+            shots: Vec::new(),
+            flight: None,
+        });
+        game.act(0, Action::Idle);
+        let crashed = Event::EnemyCrashed {
+            player: 0,
+            kind: Kind::Drone,
+            pos: one,
+        };
+        assert_eq!(game.take_events(), vec![crashed, lost(0, one, 2)]);
+
+        game.enemies.shots.push(Bullet {
+            pos_x: two.0,
+            pos_y: two.1,
+        });
+        game.act(1, Action::Idle);
+        let impact = Event::Impact {
+            player: 1,
+            pos: two,
+        };
+        assert_eq!(game.take_events(), vec![impact, lost(1, two, 2)]);
+
+        game.enemies.spawnrock(one.0);
+        game.enemies.rocks[0].pos_y = one.1;
+        game.act(0, Action::Idle);
+        let impact = Event::Impact {
+            player: 0,
+            pos: one,
+        };
+        assert_eq!(game.take_events(), vec![impact, lost(0, one, 1)]);
+    }
+
+    #[test]
+    fn events_are_only_taken_once() {
+        let mut game = Game::seeded(1);
+        kill(&mut game);
+        assert_eq!(game.take_events().len(), 2);
+        assert!(game.take_events().is_empty());
+
+        kill(&mut game);
+        game.clear_events();
+        assert!(game.events.is_empty());
+
+        //Starting over warps the stars, and nothing from before is left.
+        kill(&mut game);
+        game.restart();
+        assert_eq!(game.take_events(), vec![Event::Warp]);
+    }
+}
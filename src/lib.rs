@@ -0,0 +1,49 @@
+// Copyright © 2018 William Haugen - Piemyth
+// [This work is licensed under the "BSD 2-Clause License"]
+// Please see the file LICENSE in the source
+// distribution of this software for license terms.
+
+//The game as a library, so it can be played, drawn or driven
+//by something other than the window in main.
+
+extern crate find_folder;
+extern crate glutin_window;
+extern crate graphics;
+extern crate image;
+extern crate libc;
+extern crate opengl_graphics;
+extern crate piston;
+extern crate rand;
+
+pub mod achievements;
+pub mod ai;
+pub mod assets;
+pub mod config;
+pub mod controller;
+pub mod editor;
+pub mod env;
+pub mod events;
+pub mod game;
+pub mod mods;
+pub mod net;
+pub mod particles;
+pub mod render;
+pub mod rewind;
+pub mod rng;
+pub mod rollback;
+pub mod save;
+pub mod screen;
+pub mod script;
+pub mod sprites;
+pub mod starfield;
+pub mod stats;
+pub mod tui;
+pub mod turns;
+pub mod watch;
+pub mod waves;
+
+pub static WIDTH: i64 = 400;
+pub static HEIGHT: i64 = 600;
+pub static GRIDSIZE: i64 = 20;
+pub static SPAWNRATE: u64 = 10;
+pub static POINTS: u64 = 25;
@@ -3,682 +3,1179 @@
 // Please see the file LICENSE in the source
 // distribution of this software for license terms.
 
-extern crate find_folder;
+extern crate galaga;
 extern crate glutin_window;
 extern crate graphics;
 extern crate opengl_graphics;
 extern crate piston;
-extern crate rand;
 
+use galaga::achievements::{Achievement, Achievements};
+use galaga::ai::Pilot;
+use galaga::assets::Assets;
+use galaga::controller::{Controller, Gamepad, Keyboard, Recorder, Remote, Replay};
+use galaga::editor::Editor;
+use galaga::env::Env;
+use galaga::game::{Action, Game};
+use galaga::mods::{self, Pack};
+use galaga::net::{self, Lockstep, TcpLink};
+use galaga::render::Renderer;
+use galaga::rewind::Rewind;
+use galaga::rollback::Rollback;
+use galaga::script::Scripts;
+use galaga::turns::Turns;
+use galaga::watch::Watch;
+use galaga::waves::Waves;
+use galaga::{save, screen, stats, tui, GRIDSIZE, HEIGHT, WIDTH};
 use glutin_window::GlutinWindow;
-use graphics::{clear, text, Image, Transformed};
-use opengl_graphics::{GlGraphics, GlyphCache, OpenGL, Texture};
+use graphics::{clear, text, Transformed};
+use opengl_graphics::{GlGraphics, OpenGL};
 use piston::event_loop::*;
 use piston::input::*;
 use piston::window::WindowSettings;
-use rand::Rng;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::rc::Rc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+//Where a game still going when the window closes gets saved.
+static SAVEFILE: &str = "galaga.save";
+//Choices from the options screen, kept for next time.
+static OPTIONSFILE: &str = "galaga.options";
+//Where how each finished game went gets written.
+static STATSFOLDER: &str = "stats";
+//Achievements unlocked so far, kept for next time.
+static ACHIEVEMENTSFILE: &str = "galaga.achievements";
+//Seconds an achievement's toast shows for when it unlocks.
+static TOASTTIME: f64 = 3.0;
+//How long the title screen sits idle before the demo starts, in game ticks.
+static ATTRACTTICKS: u64 = 6 * 8;
+//How long the demo keeps showing after the pilot gets hit.
+static DEMOOVERTICKS: u64 = 6 * 2;
+
+//How an online game is kept in sync with the other player.
+enum Online {
+    Lockstep(Lockstep),
+    Rollback(Box<Rollback<TcpLink>>),
+}
 
-static WIDTH: i64 = 400;
-static HEIGHT: i64 = 600;
-static GRIDSIZE: i64 = 20;
-static SPAWNRATE: u64 = 10;
-static POINTS: u64 = 25;
+//What the window is showing.
+#[derive(PartialEq)]
+enum Mode {
+    Title,
+    //The pilot is playing a game by itself until someone presses a key.
+    Demo,
+    Playing,
+    //Making stages for the wave file.
+    Editor,
+    //Picking an asset pack.
+    Options,
+    //Looking through what's been unlocked.
+    Achievements,
+}
 
-//Starting out layout was used from the examples
-//in the Piston Library and this video
-//https://www.youtube.com/watch?v=HCwMb0KslX8
 //Glyphs were pulled from the piston examples on github:
 //https://github.com/PistonDevelopers/opengl_graphics/blob/master/examples/hello_world.rs
-struct Game {
-    gl: GlGraphics,
-    ship: Ship,
-    enemies: Enemy,
-    ticks: u64,
-    spawnrate: u64,
-    score: u64,
-}
-
-impl Game {
-    //Gets screen and renders ship all the sprites on the screen.
-    fn render(&mut self, arg: &RenderArgs, player: &Texture, fighter: &Texture, rock: &Texture) {
-        self.ship.render(&mut self.gl, arg, player);
-        self.enemies.render(&mut self.gl, arg, fighter, rock);
-    }
-
-    fn score(&mut self) -> String {
-        //Get the score to be rendered down in main.
-        let mut score = "Score: ".to_string();
-        score.push_str(&self.score.to_string());
-
-        score
+fn main() {
+    //Find the assets folder to load the stages and everything else from.
+    let folder = Assets::find().unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        process::exit(1);
+    });
+
+    //Stages to play through before the game goes endless, from the
+    //assets folder or the file given with --waves.
+    let waves_path = argument("--waves")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| folder.join("waves.toml"));
+    let mut waves = Rc::new(Waves::load(&waves_path).unwrap_or_else(|e| {
+        eprintln!("Could not load waves {}", e);
+        process::exit(1);
+    }));
+
+    //Play in the terminal instead of a window.
+    if env::args().any(|x| x == "--tui") {
+        tui::run(&waves);
+        return;
     }
 
-    //Update based on event args time
-    fn update(&mut self) -> bool {
-        //Spawning system for enemy ships.
-        //Will span more as time goes on to a limit of 5 ships per tick and
-        //One astroid every 3 and 7 game ticks.
-        let mut spawns =
-            (self.ticks as f64 / self.spawnrate as f64).sqrt() / (SPAWNRATE * 10) as f64;
-
-        if spawns < 1.0 {
-            spawns = 1.0;
-        }
-        if spawns > 5.0 {
-            spawns = 5.0;
-        }
-        for _ in 0..spawns as u64 {
-            self.enemies.spawnship();
-        }
-
-        if (self.ticks % 3 == 0 && self.ticks > 240) || (self.ticks % 7 == 0 && self.ticks > 60) {
-            self.enemies.spawnrock(self.ship.current_pos().0);
-        }
-
-        self.ticks += 1;
-
-        self.ship.update(false);
-        let hits = self.enemies
-            .update(self.ship.current_pos(), self.ship.get_shots(), false);
-
-        let result = self.ship.collision(hits);
-        self.score += result.1;
-
-        if result.0 {
-            return true;
+    //Scripts get read in again whenever they change.
+    let scripts_folder = Waves::scripts_folder(&waves_path);
+    let mut scripts_changed = Scripts::modified(&scripts_folder);
+
+    //Online co-op, connect before anything else so a window isn't sitting
+    //there frozen while waiting for the other player.
+    let online = connect(&waves);
+
+    //Let the pilot play without a window for a number of ticks.
+    //Online, the pilots on both ends play together instead.
+    if env::args().any(|x| x == "--soak") {
+        let ticks = argument("--soak")
+            .and_then(|x| x.parse().ok())
+            .unwrap_or(100_000);
+        match online {
+            Some((Online::Lockstep(x), seed)) => net_soak(x, seed, ticks, &waves),
+            Some((Online::Rollback(x), _)) => rollback_soak(*x, ticks),
+            None => soak(ticks),
         }
-        false
+        return;
     }
 
-    //Update Ship's movement or shoot depending on input
-    //Returns a tuple left value indicates a reset, right value indicates game over.
-    fn pressed(&mut self, btn: &Button, game_over: bool) -> (bool, bool) {
-        if btn == &Button::Keyboard(Key::R) {
-            return (true, false);
-        }
-        self.ship.kmove(btn);
-        let hits = self.enemies
-            .update(self.ship.current_pos(), self.ship.get_shots(), true);
+    //If there's an error with opengl, change the version
+    //and uncomment the .opengl() argument for the window
+    //below when window is created.
+    let opengl = OpenGL::V3_2;
 
-        let result = self.ship.collision(hits);
-        if !game_over {
-            self.score += result.1;
+    //get the window framework, it can be resized and everything
+    //gets scaled to fit with black bars filling the extra space.
+    let mut window: GlutinWindow = WindowSettings::new("galaga", [WIDTH as u32, HEIGHT as u32])
+        //.opengl(opengl)
+        .exit_on_esc(true)
+        .resizable(true)
+        .build()
+        .unwrap();
+    let mut fullscreen = false;
+
+    //Initialize the game and what draws it
+    let mut game = Game::new().with_waves(&waves);
+    let mut renderer = Renderer::new(GlGraphics::new(opengl));
+    let mut mode = Mode::Title;
+
+    //Where each player's actions come from, set up when a game starts.
+    let mut controllers: Vec<Box<dyn Controller>> = Vec::new();
+    //A gamepad or remote connection asked for on the command line.
+    //It flies the only ship alone, or player 2's in co-op.
+    let mut external: Option<Box<dyn Controller>> = None;
+    if let Some(path) = argument("--replay") {
+        let replays = Replay::load(&path).unwrap_or_else(|e| {
+            eprintln!("Could not load replay {}", e);
+            process::exit(1);
+        });
+        let seed = replays[0].seed();
+        game = if replays.len() > 1 {
+            Game::coop(seed)
+        } else {
+            Game::seeded(seed)
+        }
+        .with_waves(&waves);
+        mode = Mode::Playing;
+        for x in replays {
+            controllers.push(Box::new(x));
         }
-        (false, result.0)
+    } else if let Some(address) = argument("--remote") {
+        println!("Waiting for a connection on {}", address);
+        let remote = Remote::listen(&address).unwrap_or_else(|e| {
+            eprintln!("Could not listen on {}: {}", address, e);
+            process::exit(1);
+        });
+        external = Some(Box::new(remote));
+    } else if env::args().any(|x| x == "--gamepad") {
+        external = Some(Box::new(Gamepad::new(0)));
     }
 
-    //Restarts all sprites to default position or clear them
-    //Set all game values to 0.
-    fn restart(&mut self) {
-        self.ship.restart();
-        self.enemies.restart();
-        self.ticks = 0;
-        self.score = 0;
+    //Playing online, the other player's actions come over the connection.
+    let mut net: Option<Online> = None;
+    if let Some((x, seed)) = online {
+        game = Game::coop(seed).with_waves(&waves);
+        mode = Mode::Playing;
+        controllers = controllers_for(1, &mut external);
+        net = Some(x);
     }
-}
 
-struct Ship {
-    pos_x: i64,
-    pos_y: i64,
-    shots: Vec<Bullet>,
-}
-
-struct Enemy {
-    list: Vec<Ship>,
-    rocks: Vec<Ship>,
-}
+    //Save the actions of each game played to a replay file.
+    let record = argument("--record");
+    let mut recorder: Option<Recorder> = None;
 
-struct Bullet {
-    pos_x: i64,
-    pos_y: i64,
-}
-
-impl Ship {
-    //renders the ship, also will render the shots when created.
-    fn render(&mut self, gl: &mut GlGraphics, args: &RenderArgs, texture: &Texture) {
-        use graphics;
-
-        let ship = Image::new().rect(graphics::rectangle::square(
-            (self.pos_x * GRIDSIZE) as f64,
-            (self.pos_y * GRIDSIZE) as f64,
-            GRIDSIZE as f64,
-        ));
-
-        gl.draw(args.viewport(), |c, gl| {
-            //Draw the image with the texture
-            let draw_state = graphics::DrawState::new_alpha();
-            ship.draw(texture, &draw_state, c.transform, gl)
-        });
-
-        for mut x in (&mut self.shots).iter_mut() {
-            x.render(gl, args);
-        }
+    //Asset packs in the mods folder, and the one picked last time.
+    let mods = mods::mods_folder(&folder);
+    let (mut packs, problems) = Pack::list(&mods);
+    for x in problems {
+        eprintln!("Could not load asset pack {}", x);
     }
+    let chosen = Pack::chosen(Path::new(OPTIONSFILE));
+    let pack = packs
+        .iter()
+        .find(|x| {
+            x.folder
+                .file_name()
+                .map(|x| x.to_string_lossy().into_owned())
+                == chosen
+        })
+        .cloned();
+
+    //Load all of the images, fonts and tuning from the assets folder.
+    let mut assets = Assets::load(&folder, pack).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        process::exit(1);
+    });
+    renderer.tune(&assets.config, &assets.palette());
+    //Pack highlighted on the options screen, 0 being none.
+    let mut option = 0;
+
+    let mut achievements = Achievements::load(Path::new(ACHIEVEMENTSFILE)).unwrap_or_else(|e| {
+        eprintln!("Could not load achievements {}", e);
+        Achievements::new()
+    });
+    //Achievements just unlocked and how long the first has been showing.
+    let mut toasts: Vec<(Achievement, f64)> = Vec::new();
+
+    //Anything in the assets gets loaded again when it's saved.
+    let mut watch = Watch::new(&assets.folders());
 
-    //Moving the ship around or shooting
-    fn kmove(&mut self, btn: &Button) {
-        let updated_pos = match btn {
-            Button::Keyboard(Key::Up) => (0, -1),
-            Button::Keyboard(Key::Down) => (0, 1),
-            Button::Keyboard(Key::Left) => (-1, 0),
-            Button::Keyboard(Key::Right) => (1, 0),
-            _ => (0, 0),
+    //ups is the number of times it will run per second.
+    let mut events = Events::new(EventSettings::new()).ups(6);
+    //Ticks spent on the title screen, or in the demo after the pilot got hit.
+    let mut idle = 0;
+    let mut pilot = Pilot::new();
+    //Going back in time while backspace is held, if the game allows it.
+    let mut rewind: Option<Rewind> = None;
+    let mut rewinding = false;
+    //There's a saved game to continue.
+    let mut saved = Path::new(SAVEFILE).exists();
+    //Each player's game when taking turns.
+    let mut turns: Option<Turns> = None;
+    //Stages being edited, while in the editor.
+    let mut editor: Option<Editor> = None;
+    //Last size the window was drawn at, for finding where the mouse is.
+    let mut view: Option<RenderArgs> = None;
+    let mut game_over = false;
+    //Stats for the game that just ended have been written out.
+    let mut written = false;
+    let mut reset = false;
+    let mut last_frame = Instant::now();
+    while let Some(e) = events.next(&mut window) {
+        let score = match turns {
+            Some(ref x) => x.score(&game),
+            None => game.score(),
         };
+        //Everybody's scores need to fit along the bottom with two players.
+        let score_size = if game.players.len() > 1 || turns.is_some() {
+            20
+        } else {
+            32
+        };
+        let combo = game.combo();
+        //Initial window render
+        if let Some(r) = e.render_args() {
+            //Time since last frame, for animating particles.
+            let dt = last_frame.elapsed().as_secs_f64();
+            last_frame = Instant::now();
+            view = Some(r);
+            //The editor only has a game going while it's previewing.
+            let previewing = editor.as_ref().map(|x| x.previewing) == Some(true);
+            let stopped = mode == Mode::Title
+                || mode == Mode::Options
+                || mode == Mode::Achievements
+                || (mode == Mode::Editor && !previewing);
+            renderer.animate(&mut game, dt, game_over || stopped);
+
+            let background = &renderer.background;
+            let meter = rewind.as_ref().map(|x| x.meter());
+            renderer.gl.draw(r.viewport(), |c, gl| {
+                //Clear the screen
+                clear([0.0, 0.0, 0.0, 1.0], gl);
+                let c = screen::view(c, &r);
 
-        //Only allow 5 shots on the screen at a time.
-        if self.shots.len() < 5 && btn == &Button::Keyboard(Key::Z) {
-            let new_bullet = Bullet {
-                pos_x: self.pos_x,
-                pos_y: self.pos_y - 1,
-            };
-
-            self.shots.push(new_bullet);
-        }
+                //Render the scrolling background and stars
+                background.draw(&c, gl, assets.background.as_ref());
 
-        //Set bounds fo where the ship can move.
-        if self.pos_x + updated_pos.0 < (WIDTH / GRIDSIZE - 1)
-            && self.pos_y + updated_pos.1 < (HEIGHT / GRIDSIZE - 3)
-            && self.pos_x + updated_pos.0 >= 1
-            && self.pos_y + updated_pos.1 > 3
-        {
-            self.pos_x += updated_pos.0;
-            self.pos_y += updated_pos.1;
-        }
-    }
+                //Position and render the score on the screen
+                if mode == Mode::Playing || mode == Mode::Demo {
+                    let transform = c.transform.trans(1.0, (HEIGHT) as f64);
+                    text::Text::new_color([1.0, 1.0, 1.0, 1.0], score_size)
+                        .draw(&score, &mut assets.glyphs, &c.draw_state, transform, gl)
+                        .unwrap();
 
-    //Update with gametick. Moved bool is to indicate if the player moved,
-    //or it was wiht the regular update of a gametick.
-    fn update(&mut self, moved: bool) {
-        //Update aspects of the ship, mainly for the shots.
-        let mut index: usize = 0;
-        let mut to_remove: Vec<usize> = Vec::new();
-        if !moved {
-            for x in (&mut self.shots).iter_mut() {
-                x.update();
-
-                //If bullet goes above screen
-                if x.get_pos().1 < 0 {
-                    to_remove.push(index);
-                } else {
-                    index += 1;
+                    //Multiplier of any combo going, in the top corner.
+                    let transform = c.transform.trans((WIDTH - GRIDSIZE * 6) as f64, 36.0);
+                    text::Text::new_color(graphics::color::hex("FFD933"), 24)
+                        .draw(&combo, &mut assets.glyphs, &c.draw_state, transform, gl)
+                        .unwrap();
                 }
-            }
-        }
-
-        self.remove_shots(to_remove);
-    }
 
-    //Gets the shots for checking position and possibly
-    //removing them.
-    fn get_shots(&mut self) -> &mut Vec<Bullet> {
-        &mut self.shots
-    }
-
-    //Give current posotion of ship.
-    fn current_pos(&mut self) -> (i64, i64) {
-        (self.pos_x, self.pos_y)
-    }
+                //How much rewind is left, along the top.
+                if let (Some(x), Mode::Playing) = (meter, &mode) {
+                    let back = [0.3, 0.3, 0.3, 0.8];
+                    graphics::rectangle(back, [4.0, 4.0, 100.0, 6.0], c.transform, gl);
+                    graphics::rectangle(
+                        graphics::color::hex("33CCFF"),
+                        [4.0, 4.0, 100.0 * x, 6.0],
+                        c.transform,
+                        gl,
+                    );
+                }
+            });
 
-    fn remove_shots(&mut self, index: Vec<usize>) {
-        //Removing bullets that were found as out of bounds or hit something.
-        for x in index {
-            if x < self.shots.len() {
-                self.shots.remove(x);
+            if !stopped {
+                renderer.render(&mut game, &r, &assets.atlas, &mut assets.glyphs);
             }
-        }
-    }
 
-    fn collision(&mut self, hits: Vec<(i64, i64)>) -> (bool, u64) {
-        let mut index: usize = 0;
-        let mut score = 0;
-        let mut to_remove: Vec<usize> = Vec::new();
-        let mut matched: bool = false;
-
-        //Check collision of the shots
-        for x in (&mut self.shots).iter_mut() {
-            let x = x.get_pos();
-            for y in (&hits).iter() {
-                //Check for coordinates to see if match.
-                //If there was a match, add points to score to be
-                //Returned back then displayed.
-                if x.0 == y.0 && x.1 == y.1 {
-                    to_remove.push(index);
-                    score += POINTS;
-                    matched = true;
+            if let (Mode::Editor, Some(ref x)) = (&mode, &editor) {
+                renderer.gl.draw(r.viewport(), |c, gl| {
+                    let c = screen::view(c, &r);
+                    x.draw(&c, gl, &mut assets.glyphs);
+                });
+            } else if mode == Mode::Options {
+                //Every pack with the highlighted one marked, and what it is.
+                let mut lines = vec!["Skin".to_string()];
+                let names = Some("Default".to_string())
+                    .into_iter()
+                    .chain(packs.iter().map(|x| x.name.clone()));
+                let using = assets.pack.as_ref().map(|x| x.folder.clone());
+                for (i, name) in names.enumerate() {
+                    let folder = if i == 0 {
+                        None
+                    } else {
+                        Some(packs[i - 1].folder.clone())
+                    };
+                    let cursor = if i == option { ">" } else { " " };
+                    let used = if folder == using { "  (in use)" } else { "" };
+                    lines.push(format!("{} {}{}", cursor, name, used));
                 }
-            }
-            if !matched {
-                index += 1;
-            } else {
-                matched = false;
-            }
-        }
+                let about = match option {
+                    0 => vec!["The game's own assets".to_string()],
+                    x => {
+                        let x = &packs[x - 1];
+                        let mut about = vec![x.description.clone()];
+                        if !x.author.is_empty() {
+                            about.push(format!("by {}", x.author));
+                        }
+                        about
+                    }
+                };
+                let glyphs = &mut assets.glyphs;
+                renderer.gl.draw(r.viewport(), |c, gl| {
+                    let c = screen::view(c, &r);
+                    let transform = c.transform.trans((WIDTH / 4 - 5) as f64, 80.0);
+                    text::Text::new_color([1.0, 1.0, 1.0, 1.0], 32)
+                        .draw("OPTIONS", glyphs, &c.draw_state, transform, gl)
+                        .unwrap();
 
-        //Remove all shots that have a collision
-        self.remove_shots(to_remove);
+                    for (i, x) in lines.iter().enumerate() {
+                        let transform = c.transform.trans(40.0, (140 + 30 * i) as f64);
+                        text::Text::new_color([1.0, 1.0, 1.0, 1.0], 24)
+                            .draw(x, glyphs, &c.draw_state, transform, gl)
+                            .unwrap();
+                    }
+                    let bottom = HEIGHT as f64 - 90.0;
+                    for (i, x) in about.iter().enumerate() {
+                        let transform = c.transform.trans(20.0, bottom + 20.0 * i as f64);
+                        text::Text::new_color([0.8, 0.8, 0.8, 1.0], 16)
+                            .draw(x, glyphs, &c.draw_state, transform, gl)
+                            .unwrap();
+                    }
+                    let transform = c.transform.trans(20.0, HEIGHT as f64 - 20.0);
+                    text::Text::new_color([1.0, 1.0, 1.0, 1.0], 16)
+                        .draw(
+                            "'Enter' Use  'Q' Back",
+                            glyphs,
+                            &c.draw_state,
+                            transform,
+                            gl,
+                        )
+                        .unwrap();
+                });
+            } else if mode == Mode::Achievements {
+                //Everything there is to unlock, with what's been unlocked marked.
+                let list: Vec<(String, String, bool)> = Achievement::ALL
+                    .iter()
+                    .map(|x| {
+                        let name = x.title().to_string();
+                        (name, x.description().to_string(), achievements.has(*x))
+                    })
+                    .collect();
+                let summary = format!(
+                    "{} of {} unlocked  {} enemies destroyed",
+                    achievements.unlocked.len(),
+                    Achievement::ALL.len(),
+                    achievements.kills
+                );
+                let glyphs = &mut assets.glyphs;
+                renderer.gl.draw(r.viewport(), |c, gl| {
+                    let c = screen::view(c, &r);
+                    let transform = c.transform.trans((WIDTH / 8) as f64, 80.0);
+                    text::Text::new_color([1.0, 1.0, 1.0, 1.0], 32)
+                        .draw("ACHIEVEMENTS", glyphs, &c.draw_state, transform, gl)
+                        .unwrap();
 
-        //Reset matched for ship.
-        matched = false;
-        let ship_pos = self.current_pos();
-        //Check to see if player ship was hit or not.
-        for x in hits {
-            if ship_pos.0 == x.0 && ship_pos.1 == x.1 {
-                matched = true;
-            }
+                    for (i, x) in list.iter().enumerate() {
+                        //Locked ones are greyed out.
+                        let color = if x.2 {
+                            [1.0, 0.85, 0.2, 1.0]
+                        } else {
+                            [0.5, 0.5, 0.5, 1.0]
+                        };
+                        let transform = c.transform.trans(30.0, (130 + 52 * i) as f64);
+                        text::Text::new_color(color, 24)
+                            .draw(&x.0, glyphs, &c.draw_state, transform, gl)
+                            .unwrap();
+                        let transform = transform.trans(0.0, 20.0);
+                        text::Text::new_color(color, 16)
+                            .draw(&x.1, glyphs, &c.draw_state, transform, gl)
+                            .unwrap();
+                    }
+                    let transform = c.transform.trans(20.0, HEIGHT as f64 - 45.0);
+                    text::Text::new_color([0.8, 0.8, 0.8, 1.0], 16)
+                        .draw(&summary, glyphs, &c.draw_state, transform, gl)
+                        .unwrap();
+                    let transform = c.transform.trans(20.0, HEIGHT as f64 - 20.0);
+                    text::Text::new_color([1.0, 1.0, 1.0, 1.0], 16)
+                        .draw("'Q' Back", glyphs, &c.draw_state, transform, gl)
+                        .unwrap();
+                });
+            } else if mode != Mode::Playing {
+                let (title, mut prompt) = if mode == Mode::Title {
+                    (
+                        "GALAGA",
+                        vec![
+                            "'Z' 1 Player  'X' Co-op",
+                            "'C' 2 Players Taking Turns",
+                            "'E' Level Editor  'O' Options",
+                            "'A' Achievements",
+                        ],
+                    )
+                } else {
+                    ("DEMO", vec!["Press Any Key"])
+                };
+                if mode == Mode::Title && saved {
+                    prompt.push("'Enter' Continue");
+                }
+                renderer.gl.draw(r.viewport(), |c, gl| {
+                    let c = screen::view(c, &r);
+                    let transform = c
+                        .transform
+                        .trans((WIDTH / 4 + 15) as f64, (HEIGHT / 2 - 20) as f64);
 
-            if matched {
-                break;
-            }
-        }
+                    text::Text::new_color([1.0, 1.0, 1.0, 1.0], 32)
+                        .draw(title, &mut assets.glyphs, &c.draw_state, transform, gl)
+                        .unwrap();
 
-        (matched, score)
-    }
+                    for (i, x) in prompt.iter().enumerate() {
+                        let transform = c.transform.trans(
+                            (WIDTH / 4 - 5) as f64,
+                            (HEIGHT / 2 + 10 + 30 * i as i64) as f64,
+                        );
 
-    //Clear the shots and reset ship to default position.
-    fn restart(&mut self) {
-        self.shots.clear();
-        self.pos_x = 10;
-        self.pos_y = 26;
-    }
-}
+                        text::Text::new_color([1.0, 1.0, 1.0, 1.0], 24)
+                            .draw(x, &mut assets.glyphs, &c.draw_state, transform, gl)
+                            .unwrap();
+                    }
+                });
+            } else if let Some(card) = turns.as_ref().and_then(|x| x.card()) {
+                //Whose turn it is, before they start playing.
+                renderer.gl.draw(r.viewport(), |c, gl| {
+                    let c = screen::view(c, &r);
+                    let transform = c
+                        .transform
+                        .trans((WIDTH / 4 - 15) as f64, (HEIGHT / 2 - 20) as f64);
 
-impl Bullet {
-    //Draw the bullet on the screen
-    fn render(&self, gl: &mut GlGraphics, args: &RenderArgs) {
-        use graphics;
-
-        let square = graphics::rectangle::square(
-            (self.pos_x * GRIDSIZE + GRIDSIZE / 4) as f64,
-            (self.pos_y * GRIDSIZE) as f64,
-            (GRIDSIZE / 2) as f64,
-        );
-
-        gl.draw(args.viewport(), |c, gl| {
-            let transform = c.transform;
-
-            graphics::rectangle(
-                //Yellow in hex color
-                graphics::color::hex("FFFF00"),
-                square,
-                transform,
-                gl,
-            );
-        });
-    }
+                    text::Text::new_color([1.0, 1.0, 1.0, 1.0], 32)
+                        .draw(&card, &mut assets.glyphs, &c.draw_state, transform, gl)
+                        .unwrap();
+                });
+            } else if game_over {
+                //How everyone did under each of their scores, not for the demo.
+                let mut lines: Vec<(String, u32)> = Vec::new();
+                let games = match (&mode, &turns) {
+                    (Mode::Playing, Some(x)) => x.games(&game).to_vec(),
+                    (Mode::Playing, None) => vec![&game],
+                    _ => Vec::new(),
+                };
+                let players: Vec<_> = games.iter().flat_map(|x| x.players.iter()).collect();
+                for (i, x) in players.iter().enumerate() {
+                    let heading = if players.len() > 1 {
+                        format!("Player {}  {}", i + 1, x.score)
+                    } else {
+                        format!("Score {}", x.score)
+                    };
+                    lines.push((heading, 24));
+                    lines.extend(x.stats.lines().into_iter().map(|x| (x, 16)));
+                }
+                renderer.gl.draw(r.viewport(), |c, gl| {
+                    let c = screen::view(c, &r);
+                    let transform = c.transform.trans((WIDTH / 4 + 15) as f64, 120.0);
+                    text::Text::new_color([1.0, 1.0, 1.0, 1.0], 32)
+                        .draw(
+                            "GAME OVER",
+                            &mut assets.glyphs,
+                            &c.draw_state,
+                            transform,
+                            gl,
+                        )
+                        .unwrap();
 
-    //Moves the bullet up the screen
-    fn update(&mut self) {
-        self.pos_y -= 1;
-    }
+                    let mut y = 170.0;
+                    for (line, size) in lines.iter() {
+                        if *size > 16 {
+                            y += 10.0;
+                        }
+                        let transform = c.transform.trans(GRIDSIZE as f64, y);
+                        text::Text::new_color([1.0, 1.0, 1.0, 1.0], *size)
+                            .draw(line, &mut assets.glyphs, &c.draw_state, transform, gl)
+                            .unwrap();
+                        y += *size as f64 + 6.0;
+                    }
+
+                    let transform = c
+                        .transform
+                        .trans((WIDTH / 4 - 5) as f64, (HEIGHT - GRIDSIZE * 6) as f64);
 
-    //Give shot's position in the form of a tuple.
-    fn get_pos(&self) -> (i64, i64) {
-        (self.pos_x, self.pos_y)
-    }
-}
+                    text::Text::new_color([1.0, 1.0, 1.0, 1.0], 24)
+                        .draw(
+                            "Press 'R' To Restart",
+                            &mut assets.glyphs,
+                            &c.draw_state,
+                            transform,
+                            gl,
+                        )
+                        .unwrap();
+                });
+            }
 
-impl Enemy {
-    //renders the ship, also will render the shots when created.
-    fn render(
-        &mut self,
-        gl: &mut GlGraphics,
-        args: &RenderArgs,
-        shiptexture: &Texture,
-        rocktexture: &Texture,
-    ) {
-        use graphics;
-
-        //Render all enemy ships in positions
-        for ships in (&mut self.list).iter_mut() {
-            let new_ship = Image::new().rect(graphics::rectangle::square(
-                (ships.pos_x * GRIDSIZE) as f64,
-                (ships.pos_y * GRIDSIZE) as f64,
-                GRIDSIZE as f64,
-            ));
+            //Anything just unlocked pops up along the top, one at a time.
+            if let Some(x) = toasts.first_mut() {
+                x.1 += dt;
+            }
+            toasts.retain(|x| x.1 < TOASTTIME);
+            if let Some(&(x, age)) = toasts.first() {
+                //Slide in and back out at either end.
+                let slide = (age.min(TOASTTIME - age) * 4.0).min(1.0);
+                let glyphs = &mut assets.glyphs;
+                renderer.gl.draw(r.viewport(), |c, gl| {
+                    let c = screen::view(c, &r);
+                    let y = -50.0 + 60.0 * slide;
+                    let back = [0.1, 0.1, 0.2, 0.85];
+                    let rect = [40.0, y, (WIDTH - 80) as f64, 44.0];
+                    graphics::rectangle(back, rect, c.transform, gl);
+                    let transform = c.transform.trans(50.0, y + 17.0);
+                    text::Text::new_color([1.0, 0.85, 0.2, 1.0], 14)
+                        .draw("Achievement unlocked", glyphs, &c.draw_state, transform, gl)
+                        .unwrap();
+                    let transform = transform.trans(0.0, 20.0);
+                    text::Text::new_color([1.0, 1.0, 1.0, 1.0], 18)
+                        .draw(x.title(), glyphs, &c.draw_state, transform, gl)
+                        .unwrap();
+                });
+            }
 
-            gl.draw(args.viewport(), |c, gl| {
-                //Draw the image with the texture
-                let draw_state = graphics::DrawState::new_alpha();
-                new_ship.draw(shiptexture, &draw_state, c.transform, gl)
+            //Black bars over anything outside of the playfield.
+            renderer.gl.draw(r.viewport(), |c, gl| {
+                screen::letterbox(&c, gl, &r);
             });
         }
 
-        //Render all rocks in their positions.
-        for rock in (&mut self.rocks).iter_mut() {
-            let new_rock = Image::new().rect(graphics::rectangle::square(
-                (rock.pos_x * GRIDSIZE) as f64,
-                (rock.pos_y * GRIDSIZE) as f64,
-                GRIDSIZE as f64,
-            ));
-
-            gl.draw(args.viewport(), |c, gl| {
-                //Draw the image with the texture
-                let draw_state = graphics::DrawState::new_alpha();
-                new_rock.draw(rocktexture, &draw_state, c.transform, gl)
-            });
-        }
-    }
+        //Update the game data and render everything
+        //if the game over conditions haven't occured.
+        if let Some(_u) = e.update_args() {
+            //Pick up edited scripts straight away. Not online though, the
+            //other player wouldn't have the same ones.
+            let changed = Scripts::modified(&scripts_folder);
+            if changed != scripts_changed && net.is_none() {
+                scripts_changed = changed;
+                match waves.reload_scripts(&waves_path) {
+                    Ok(x) => {
+                        println!("Reloaded scripts from {}", scripts_folder.display());
+                        waves = Rc::new(x);
+                        game.waves = Rc::clone(&waves);
+                        if let Some(ref mut x) = turns {
+                            x.waiting.waves = Rc::clone(&waves);
+                        }
+                        if let Some(ref mut x) = editor {
+                            x.waves.scripts = waves.scripts.clone();
+                        }
+                    }
+                    //Keep flying by the old ones until the mistake is fixed.
+                    Err(e) => eprintln!("Could not reload scripts {}", e),
+                }
+            }
 
-    //Spawn ship's randomly on the x position.
-    fn spawnship(&mut self) {
-        let mut rng = rand::thread_rng();
-        let pos_x: i64 = rng.gen_range(1, WIDTH / GRIDSIZE - 1);
-        let new_ship = Ship {
-            pos_x,
-            pos_y: -1,
-            shots: Vec::new(),
-        };
-        self.list.push(new_ship);
-    }
+            //Swap in edited assets. If one doesn't load, say why and keep the old one.
+            if assets.reload(&watch.changed()) {
+                renderer.tune(&assets.config, &assets.palette());
+            }
 
-    //Creats a rock, set x position to the player ship's
-    //current x position.
-    fn spawnrock(&mut self, pos_x: i64) {
-        let new_ship = Ship {
-            pos_x,
-            pos_y: -1,
-            shots: Vec::new(),
-        };
-        self.rocks.push(new_ship);
-    }
+            match mode {
+                Mode::Title => {
+                    //Nobody's playing, start the demo.
+                    idle += 1;
+                    if idle >= ATTRACTTICKS {
+                        game = Game::new().with_waves(&waves);
+                        renderer.restart();
+                        pilot = Pilot::new();
+                        game_over = false;
+                        idle = 0;
+                        mode = Mode::Demo;
+                    }
+                }
+                Mode::Demo => {
+                    if !game_over {
+                        game_over = game.update();
+                    } else {
+                        //Show the pilot blowing up for a bit, then back to the title.
+                        idle += 1;
+                        if idle >= DEMOOVERTICKS {
+                            idle = 0;
+                            mode = Mode::Title;
+                        }
+                    }
+                }
+                Mode::Options | Mode::Achievements => {}
+                Mode::Editor => {
+                    //Play the stage over again each time it's cleared.
+                    if let Some(ref x) = editor {
+                        if x.previewing {
+                            game.update();
+                            if game.stage > 0 {
+                                game = x.preview();
+                                renderer.restart();
+                            }
+                        }
+                    }
+                }
+                Mode::Playing => {
+                    //Nothing moves while the ready card is up.
+                    let ready = turns.as_mut().map(|x| x.wait()) == Some(true);
+                    if let Some(ref mut x) = net {
+                        //Online the game only moves on once both players' actions are in.
+                        if !game_over {
+                            match x.step(&mut game) {
+                                Ok(over) => game_over = over,
+                                Err(e) => {
+                                    eprintln!("{}", e);
+                                    game_over = true;
+                                }
+                            }
+                        }
+                    } else if rewinding
+                        && rewind.as_mut().map(|x| x.rewind(&mut game)) == Some(true)
+                    {
+                        //Holding backspace runs time backwards instead, even after dying.
+                        //The past keeps the scripts as they are now.
+                        game.waves = Rc::clone(&waves);
+                        game_over = false;
+                        renderer.background.set_speed(1.0);
+                        if let Some(ref mut x) = recorder {
+                            x.rewind(game.ticks);
+                        }
+                        if argument("--replay").is_none() {
+                            achievements.rewound(&game);
+                        }
+                    } else if !game_over && !ready {
+                        if let Some(ref mut x) = rewind {
+                            x.record(&game);
+                        }
+                        game_over = game.update();
+                        //Taking turns, it's only over once both players are out.
+                        if let Some(ref mut x) = turns {
+                            x.update(&mut game);
+                            game_over = x.over(&game);
+                        }
+                        if game_over {
+                            save_replay(&recorder, &record);
+                        }
+                    }
+                }
+            }
+        }
 
-    //Check collision for enemy ships
-    fn ship_collision(&mut self, y: (i64, i64)) -> bool {
-        let mut hit: bool = false;
-        let mut index = 0;
-        for x in self.current_pos() {
-            //First checks if the x coordinate is the same.
-            //Then checks to see if either the y coordinates match
-            //or they are one apart.
-            if x.0 == y.0 {
-                if x.1 == y.1 || x.1 == y.1 + 1 {
-                    //Remove if hit and set hit to true.
-                    hit = true;
-                    self.list.remove(index);
-                } else {
-                    //Increment if there wasn't a revmoval of list.
-                    index += 1;
+        //Listen for some key presses
+        if let Some(key) = e.button_args() {
+            //F11 switches between fullscreen and a window.
+            if key.state == ButtonState::Press && key.button == Button::Keyboard(Key::F11) {
+                screen::toggle_fullscreen(&window, &mut fullscreen);
+            } else if key.button == Button::Keyboard(Key::Backspace) && mode == Mode::Playing {
+                rewinding = key.state == ButtonState::Press;
+            } else if key.state == ButtonState::Press && mode == Mode::Editor {
+                //Enter plays the stage or goes back to editing it, S saves to
+                //the wave file and Q leaves. Everything else edits.
+                if let Some(ref mut x) = editor {
+                    let before = x.waves.clone();
+                    match key.button {
+                        Button::Keyboard(Key::Q) => mode = Mode::Title,
+                        Button::Keyboard(Key::Return) => x.previewing = !x.previewing,
+                        Button::Keyboard(Key::S) => {
+                            if let Ok(saved) = x.save(&waves_path) {
+                                waves = Rc::new(saved);
+                            }
+                        }
+                        Button::Keyboard(k) => x.key(k),
+                        Button::Mouse(MouseButton::Left) => x.place(),
+                        _ => {}
+                    }
+                    //The preview starts over with every change.
+                    let restart = key.button == Button::Keyboard(Key::Return) || x.waves != before;
+                    if x.previewing && restart {
+                        game = x.preview();
+                        renderer.restart();
+                    }
+                }
+                if mode == Mode::Title {
+                    editor = None;
+                    game = Game::new().with_waves(&waves);
+                    renderer.restart();
+                    game_over = false;
+                    idle = 0;
+                }
+            } else if key.state == ButtonState::Press && mode == Mode::Options {
+                //Up and down pick a pack, Enter switches to it and Q goes back.
+                match key.button {
+                    Button::Keyboard(Key::Up) => option = option.saturating_sub(1),
+                    Button::Keyboard(Key::Down) => option = (option + 1).min(packs.len()),
+                    Button::Keyboard(Key::Return) => {
+                        let pack = if option == 0 {
+                            None
+                        } else {
+                            Some(packs[option - 1].clone())
+                        };
+                        match Assets::load(&folder, pack) {
+                            Ok(x) => {
+                                assets = x;
+                                renderer.tune(&assets.config, &assets.palette());
+                                watch = Watch::new(&assets.folders());
+                                if let Err(e) =
+                                    Pack::remember(Path::new(OPTIONSFILE), assets.pack.as_ref())
+                                {
+                                    eprintln!("Could not save options to {}: {}", OPTIONSFILE, e);
+                                }
+                            }
+                            //Keep what's there if the pack can't be used.
+                            Err(e) => eprintln!("{}", e),
+                        }
+                    }
+                    Button::Keyboard(Key::Q) => {
+                        idle = 0;
+                        mode = Mode::Title;
+                    }
+                    _ => {}
                 }
+            } else if key.state == ButtonState::Press && mode == Mode::Achievements {
+                if key.button == Button::Keyboard(Key::Q) {
+                    idle = 0;
+                    mode = Mode::Title;
+                }
+            } else if key.state == ButtonState::Press
+                && mode == Mode::Title
+                && key.button == Button::Keyboard(Key::A)
+            {
+                idle = 0;
+                mode = Mode::Achievements;
+            } else if key.state == ButtonState::Press
+                && mode == Mode::Title
+                && key.button == Button::Keyboard(Key::O)
+            {
+                //Look again so packs added while running show up.
+                let (list, problems) = Pack::list(&mods);
+                for x in problems {
+                    eprintln!("Could not load asset pack {}", x);
+                }
+                packs = list;
+                option = assets
+                    .pack
+                    .as_ref()
+                    .and_then(|x| packs.iter().position(|y| y.folder == x.folder))
+                    .map_or(0, |x| x + 1);
+                idle = 0;
+                mode = Mode::Options;
+            } else if key.state == ButtonState::Press
+                && mode == Mode::Title
+                && key.button == Button::Keyboard(Key::E)
+            {
+                idle = 0;
+                editor = Some(Editor::new(&waves));
+                turns = None;
+                mode = Mode::Editor;
+            } else if key.state == ButtonState::Press && mode == Mode::Title {
+                //Any key keeps the demo from starting, Z starts a game,
+                //X starts one for two players at once and C one where they take turns.
+                //Enter picks up the saved game if there is one.
+                idle = 0;
+                let seed = game.rng.state();
+                let mut resume = false;
+                let start = match key.button {
+                    Button::Keyboard(Key::Z) => Some(Game::seeded(seed).with_waves(&waves)),
+                    Button::Keyboard(Key::X) => Some(Game::coop(seed).with_waves(&waves)),
+                    Button::Keyboard(Key::C) => Some(Game::turns(seed).with_waves(&waves)),
+                    Button::Keyboard(Key::Return) if saved => match save::load(SAVEFILE) {
+                        Ok(x) => {
+                            resume = true;
+                            Some(x.with_waves(&waves))
+                        }
+                        Err(e) => {
+                            //Move it out of the way so it isn't offered again,
+                            //but keep it around to look at.
+                            let bad = format!("{}.bad", SAVEFILE);
+                            eprintln!("Could not continue {}, moved it to {}", e, bad);
+                            if fs::rename(SAVEFILE, &bad).is_err() {
+                                let _ = fs::remove_file(SAVEFILE);
+                            }
+                            saved = false;
+                            None
+                        }
+                    },
+                    _ => None,
+                };
+                if resume {
+                    //A save only gets continued once.
+                    let _ = fs::remove_file(SAVEFILE);
+                    saved = false;
+                    renderer.restart();
+                }
+                if let Some(x) = start {
+                    game = x;
+                    turns = if key.button == Button::Keyboard(Key::C) {
+                        Some(Turns::new(&mut game))
+                    } else {
+                        None
+                    };
+                    controllers = controllers_for(game.players.len(), &mut external);
+                    //Turns swap whole games around, so there's nothing to rewind to.
+                    rewind = if turns.is_some() {
+                        None
+                    } else if env::args().any(|x| x == "--scrub") {
+                        Some(Rewind::unlimited())
+                    } else {
+                        Some(Rewind::new())
+                    };
+                    mode = Mode::Playing;
+                    reset = !resume;
+                }
+            } else if key.state == ButtonState::Press && mode == Mode::Demo {
+                idle = 0;
+                mode = Mode::Title;
+            } else if key.state == ButtonState::Press
+                && key.button == Button::Keyboard(Key::R)
+                && net.is_none()
+            {
+                //If a user pushes r, reset the game
+                reset = true;
             } else {
-                //Another increment since didn't make it as far.
-                index += 1;
+                for x in controllers.iter_mut() {
+                    x.button(&key.button, key.state);
+                }
             }
-
-            //If hit, no need to continue,.
-            if hit {
-                break;
+        }
+        //The mouse moves the editor's cursor around the playfield.
+        if let (Some(position), Some(r), Some(x)) = (e.mouse_cursor_args(), view, editor.as_mut()) {
+            let position = screen::unview(position, &r);
+            let size = GRIDSIZE as f64;
+            x.point((
+                (position[0] / size).floor() as i64,
+                (position[1] / size).floor() as i64,
+            ));
+        }
+        if let Some(args) = e.controller_axis_args() {
+            for x in controllers.iter_mut() {
+                x.axis(&args);
             }
         }
 
-        //Return rresult if ship was hit or not.
-        hit
-    }
-
-    //Check collision of rocks.
-    fn rock_collision(&mut self, y: (i64, i64)) -> bool {
-        let mut hit: bool = false;
-        for x in (&mut self.rocks).iter_mut() {
-            let mut x = x.current_pos();
-            //Needs to check if it is on position or below one.
-            //Can pass through if it doesn't check below one.
-            if x.0 == y.0 && (x.1 == y.1 || x.1 == y.1 + 1) {
-                //Don't remove since rocks to get destroyed.
-                hit = true;
+        //Move the ships or shoot with whatever the pilot or controllers want to do.
+        let mut actions: Vec<(usize, Action)> = Vec::new();
+        match mode {
+            Mode::Title | Mode::Editor | Mode::Options | Mode::Achievements => {}
+            Mode::Demo if game_over => {}
+            Mode::Demo => actions.extend(pilot.poll(&game).into_iter().map(|x| (0, x))),
+            Mode::Playing => {
+                for (player, x) in controllers.iter_mut().enumerate() {
+                    actions.extend(x.poll(&game).into_iter().map(|x| (player, x)));
+                }
             }
-
-            //If there was a hit on that rock, exit out of loop
-            //No need to continue on.
-            if hit {
+        }
+        for (player, action) in actions {
+            if game_over || turns.as_ref().map(|x| x.ready > 0) == Some(true) {
                 break;
             }
-        }
-
-        //Return result if rock was hit or not.
-        hit
-    }
-
-    //Update aspects of the ships, check for collisions with shots or ship
-    fn update(
-        &mut self,
-        ship_pos: (i64, i64),
-        shot_pos: &mut Vec<Bullet>,
-        movement: bool,
-    ) -> Vec<(i64, i64)> {
-        let mut hits: Vec<(i64, i64)> = Vec::new();
-        let mut prev_hits: Vec<(i64, i64)> = Vec::new();
-        let mut prev: bool = false;
-
-        //Checks the positions of all the shots
-        //Removes a the ship if hit and adds to a hit list
-        //hit list will get passed back to player ship
-        //and have collisions checked for there.
-        for x in shot_pos.iter_mut() {
-            let x = x.get_pos();
-            for y in (&mut prev_hits).iter_mut() {
-                if x.0 == y.0 && x.1 == y.1 {
-                    prev = true;
-                }
+            //Nothing the players do counts while time is going backwards.
+            if rewinding && rewind.is_some() {
+                break;
             }
-
-            //If either ship or rock were hit, push the positions on the hit
-            //list for player ship to remove.
-            if !prev && (self.ship_collision(x) || self.rock_collision(x)) {
-                hits.push(x);
-                prev_hits.push(x);
-                prev = false;
+            //Online, actions go out to the other player first and happen
+            //once the tick they're for gets simulated.
+            if let Some(ref mut x) = net {
+                x.push(action);
+                continue;
+            }
+            if let Some(ref mut x) = recorder {
+                x.record(game.ticks, player, action);
+            }
+            //If everyone has collided with a block, game over
+            game_over = game.act(player, action);
+            if let Some(ref mut x) = turns {
+                x.update(&mut game);
+                game_over = x.over(&game);
+            }
+            if game_over && mode == Mode::Playing {
+                save_replay(&recorder, &record);
             }
         }
 
-        //Checks collision with player ship.
-        if self.ship_collision(ship_pos) || self.rock_collision(ship_pos) {
-            hits.push(ship_pos);
-        }
-
-        //If the update wasn't for a player input, move the rocks and ships.
-        if !movement {
-            for x in (&mut self.list).iter_mut() {
-                x.pos_y += 1;
+        //If a user decided to restart the game.
+        //Call restart to reset everything about the agme.
+        if reset {
+            match turns {
+                Some(ref mut x) => x.restart(&mut game),
+                None => game.restart(),
             }
-            for x in (&mut self.rocks).iter_mut() {
-                x.pos_y += 1;
+            renderer.restart();
+            if let Some(ref mut x) = rewind {
+                x.restart();
             }
-
-            //Check collision against player ship again after the move.
-            if self.ship_collision(ship_pos) || self.rock_collision(ship_pos) {
-                hits.push(ship_pos);
+            reset = false;
+            game_over = false;
+            //Replays only cover games played on one screen, not turns.
+            if record.is_some() && turns.is_none() {
+                recorder = Some(Recorder::new(game.rng.state(), game.players.len()));
             }
         }
 
-        //Remove any enemies or rocks that have gone below where the player can go.
-        let mut index = 0;
-        for x in self.current_pos() {
-            //If enemy goes below
-            if x.1 > (HEIGHT / GRIDSIZE - 4) {
-                self.list.remove(index);
-            } else {
-                //Only need to increment index if a ship wasn't removed.
-                index += 1;
+        //Whatever happened this time round goes out to everything that wants
+        //to know, first the effects.
+        let events = game.take_events();
+        renderer.effects(&events, game_over);
+
+        //Then anything unlocked by it. Replays were already played, so
+        //they don't count.
+        if mode == Mode::Playing && argument("--replay").is_none() {
+            let unlocked = achievements.update(&game, &events);
+            if !unlocked.is_empty() {
+                toasts.extend(unlocked.into_iter().map(|x| (x, 0.0)));
+                save_achievements(&achievements);
             }
         }
-        //Reset index for use with rock positions.
-        index = 0;
-        for x in self.current_rock_pos() {
-            //If enemy goes below
-            if x.1 > (HEIGHT / GRIDSIZE - 4) {
-                self.rocks.remove(index);
-            } else {
-                index += 1;
+
+        //Keep how the game went once it's over.
+        if !game_over {
+            written = false;
+        } else if !written && mode == Mode::Playing && argument("--replay").is_none() {
+            written = true;
+            let games = match turns {
+                Some(ref x) => x.games(&game).to_vec(),
+                None => vec![&game],
+            };
+            if let Err(e) = stats::write(Path::new(STATSFOLDER), &games) {
+                eprintln!("Could not write stats to {}: {}", STATSFOLDER, e);
             }
+            save_achievements(&achievements);
         }
-
-        //Return the list of all hits made on rocks and enemy ships.
-        hits
     }
 
-    //Grabs the positions of all the ships.
-    fn current_pos(&mut self) -> Vec<(i64, i64)> {
-        let mut current_pos: Vec<(i64, i64)> = Vec::new();
-        for ships in (&self.list).iter() {
-            current_pos.push((ships.pos_x, ships.pos_y))
+    //Keep the game that was still going when the window closed too.
+    if !game_over {
+        save_replay(&recorder, &record);
+    }
+    save_achievements(&achievements);
+
+    //Save it to continue later as well. Taking turns, online games and
+    //replays can't be picked back up, so those are let go.
+    let replaying = argument("--replay").is_some();
+    if mode == Mode::Playing && !game_over && turns.is_none() && net.is_none() && !replaying {
+        if let Err(e) = save::save(&game, SAVEFILE) {
+            eprintln!("Could not save the game to {}: {}", SAVEFILE, e);
         }
-        current_pos
     }
+}
+
+//Value given after a command line flag, like the file in --replay file.
+fn argument(flag: &str) -> Option<String> {
+    let mut args = env::args().skip_while(|x| x != flag);
+    args.next();
+    args.next()
+}
 
-    //Grabs the positions of all rocks.
-    fn current_rock_pos(&mut self) -> Vec<(i64, i64)> {
-        let mut current_pos: Vec<(i64, i64)> = Vec::new();
-        for rock in (&self.rocks).iter() {
-            current_pos.push((rock.pos_x, rock.pos_y))
+impl Online {
+    //Something the local player did, for the next tick.
+    fn push(&mut self, action: Action) {
+        match self {
+            Online::Lockstep(x) => x.push(action),
+            Online::Rollback(x) => x.push(action),
         }
-        current_pos
     }
 
-    //Clears all enemies and rocks on screen
-    fn restart(&mut self) {
-        self.list.clear();
-        self.rocks.clear();
+    //Move the game on a tick if it can, giving whether it's over.
+    fn step(&mut self, game: &mut Game) -> Result<bool, String> {
+        match self {
+            Online::Lockstep(x) => {
+                x.step(game)?;
+                Ok(game.over())
+            }
+            Online::Rollback(x) => {
+                x.step()?;
+                *game = x.snapshot();
+                Ok(x.over())
+            }
+        }
     }
 }
 
-fn main() {
-    //If there's an error with opengl, change the version
-    //and uncomment the .opengl() argument for the window
-    //below when window is created.
-    let opengl = OpenGL::V3_2;
-
-    //get the window framework
-    let mut window: GlutinWindow = WindowSettings::new(
-        "galaga",
-        [400,600],
-        )
-        //.opengl(opengl)
-        .exit_on_esc(true)
-        .build()
-        .unwrap();
-
-    //Initialize the game
-    let mut game = Game {
-        gl: GlGraphics::new(opengl),
-        ship: Ship {
-            pos_x: 10,
-            pos_y: 26,
-            shots: Vec::new(),
-        },
-        enemies: Enemy {
-            list: Vec::new(),
-            rocks: Vec::new(),
-        },
-        ticks: 0,
-        spawnrate: SPAWNRATE,
-        score: 0,
+//Host or join an online game if asked to on the command line,
+//giving the connection and the seed both sides start with.
+fn connect(waves: &Rc<Waves>) -> Option<(Online, u64)> {
+    let result = if let Some(address) = argument("--host") {
+        println!("Waiting for the other player on {}", address);
+        let seed = Game::new().rng.state();
+        net::host(&address, seed).map(|x| (x, 0, seed))
+    } else if let Some(address) = argument("--join") {
+        net::join(&address).map(|(x, seed)| (x, 1, seed))
+    } else {
+        return None;
     };
 
-    //Load all of the images and fonts from assets folder.
-    let assets = find_folder::Search::ParentsThenKids(3, 3)
-        .for_folder("assets")
-        .unwrap();
-    let background = assets.join("background.png");
-    let ship = assets.join("ship.png");
-    let fighter = assets.join("enemy.png");
-    let rock = assets.join("rock.png");
-    let font = assets.join("FiraSans-Regular.ttf");
-    //Create the image object and attach a square Rectangle object inside.
-    //Used for background.
-    let image = Image::new().rect(graphics::rectangle::square(0.0, 0.0, HEIGHT as f64));
-
-    //A texture to use with the image, using the paths
-    //created above from assets
-    let background_texture =
-        Texture::from_path(background, &opengl_graphics::TextureSettings::new()).unwrap();
-    let ship_texture = Texture::from_path(ship, &opengl_graphics::TextureSettings::new()).unwrap();
-    let fighter_texture =
-        Texture::from_path(fighter, &opengl_graphics::TextureSettings::new()).unwrap();
-    let rock_texture = Texture::from_path(rock, &opengl_graphics::TextureSettings::new()).unwrap();
-
-    //Convert font into a glyphcache
-    let mut glyphs = GlyphCache::new(font, (), opengl_graphics::TextureSettings::new()).unwrap();
+    //Lockstep unless asked for rollback.
+    let result = result.and_then(|(stream, local, seed)| {
+        let online = if env::args().any(|x| x == "--rollback") {
+            let game = Game::coop(seed).with_waves(waves);
+            Online::Rollback(Box::new(Rollback::with_game(
+                TcpLink::new(stream)?,
+                local,
+                game,
+            )))
+        } else {
+            Online::Lockstep(Lockstep::new(stream, local)?)
+        };
+        Ok((online, seed))
+    });
+
+    match result {
+        Ok(x) => Some(x),
+        Err(e) => {
+            eprintln!("Could not connect: {}", e);
+            process::exit(1);
+        }
+    }
+}
 
-    //ups is the number of times it will run per second.
-    let mut events = Events::new(EventSettings::new()).ups(6);
-    let mut game_over = false;
-    let mut reset = false;
-    while let Some(e) = events.next(&mut window) {
-        let score = game.score();
-        //Initial window render
-        if let Some(r) = e.render_args() {
-            game.gl.draw(r.viewport(), |c, gl| {
-                //Clear the screen
-                clear([0.0, 0.0, 0.0, 1.0], gl);
-                let draw_state = graphics::DrawState::new_alpha();
+//Controllers for a new game, one for each player. The gamepad or remote
+//connection from the command line takes the last player, otherwise one
+//player gets the arrow keys and two split the keyboard.
+fn controllers_for(
+    players: usize,
+    external: &mut Option<Box<dyn Controller>>,
+) -> Vec<Box<dyn Controller>> {
+    let mut controllers: Vec<Box<dyn Controller>> = Vec::new();
+    if players > 1 {
+        controllers.push(Box::new(Keyboard::wasd()));
+    }
+    controllers.push(match external.take() {
+        Some(x) => x,
+        None => Box::new(Keyboard::arrows()),
+    });
+    controllers
+}
 
-                //Render the background image
-                image.draw(&background_texture, &draw_state, c.transform, gl);
+//Write out the replay if one is being recorded.
+fn save_replay(recorder: &Option<Recorder>, path: &Option<String>) {
+    if let (Some(recorder), Some(path)) = (recorder, path) {
+        if let Err(e) = recorder.save(path) {
+            eprintln!("Could not save replay to {}: {}", path, e);
+        }
+    }
+}
 
-                //Position and render the score on the screen
-                let transform = c.transform.trans(1.0, (HEIGHT) as f64);
-                text::Text::new_color([1.0, 1.0, 1.0, 1.0], 32)
-                    .draw(&score, &mut glyphs, &c.draw_state, transform, gl)
-                    .unwrap();
-            });
+fn save_achievements(achievements: &Achievements) {
+    if let Err(e) = achievements.save(Path::new(ACHIEVEMENTSFILE)) {
+        eprintln!("Could not save achievements to {}: {}", ACHIEVEMENTSFILE, e);
+    }
+}
 
-            game.render(&r, &ship_texture, &fighter_texture, &rock_texture);
+//Have pilots on both ends of an online game play it together without a
+//window, printing the checksum every so often so the two can be compared.
+fn net_soak(mut lockstep: Lockstep, seed: u64, ticks: u64, waves: &Rc<Waves>) {
+    let mut game = Game::coop(seed).with_waves(waves);
+    let mut pilot = Pilot::flying(lockstep.local());
 
-            if game_over {
-                game.gl.draw(r.viewport(), |c, gl| {
-                    //Position the text in the location.
-                    let transform = c.transform
-                        .trans((WIDTH / 4 + 15) as f64, (HEIGHT / 2 - 20) as f64);
+    while game.ticks < ticks && !game.over() {
+        if game.ticks.is_multiple_of(100) {
+            println!("tick {} checksum {:016x}", game.ticks, game.checksum());
+        }
+        lockstep.push(pilot.decide(&game));
+        loop {
+            match lockstep.step(&mut game) {
+                Ok(true) => break,
+                Ok(false) => thread::sleep(Duration::from_millis(1)),
+                Err(e) => {
+                    eprintln!("{}", e);
+                    process::exit(1);
+                }
+            }
+        }
+    }
 
-                    text::Text::new_color([1.0, 1.0, 1.0, 1.0], 32)
-                        .draw("GAME OVER", &mut glyphs, &c.draw_state, transform, gl)
-                        .unwrap();
+    let scores: Vec<u64> = game.players.iter().map(|x| x.score).collect();
+    println!(
+        "{} ticks, scores {:?}, checksum {:016x}",
+        game.ticks,
+        scores,
+        game.checksum()
+    );
+}
 
-                    //Relocate where text is to be rendered
-                    let transform = c.transform
-                        .trans((WIDTH / 4 - 5) as f64, (HEIGHT / 2 + 10) as f64);
+//Same as net_soak but with rollback, only the end is printed since the
+//confirmed game can move on more than one tick at a time.
+fn rollback_soak(mut rollback: Rollback<TcpLink>, ticks: u64) {
+    let mut pilot = Pilot::flying(rollback.local());
 
-                    text::Text::new_color([1.0, 1.0, 1.0, 1.0], 24)
-                        .draw(
-                            "Press 'R' To Restart",
-                            &mut glyphs,
-                            &c.draw_state,
-                            transform,
-                            gl,
-                        )
-                        .unwrap();
-                });
-            }
+    let result = loop {
+        let (frame, confirmed) = rollback.frames();
+        if confirmed >= ticks || rollback.over() {
+            break Ok(());
         }
-
-        //Update the game data and render everything
-        //if the game over conditions haven't occured.
-        if !game_over {
-            if let Some(_u) = e.update_args() {
-                game_over = game.update();
-            }
+        let played = if frame < ticks && !rollback.game().over() {
+            let action = pilot.decide(rollback.game());
+            rollback.push(action);
+            rollback.step()
+        } else {
+            rollback.wait().map(|_| false)
+        };
+        match played {
+            Ok(true) => {}
+            Ok(false) => thread::sleep(Duration::from_millis(1)),
+            Err(e) => break Err(e),
         }
+    };
+    if let Err(e) = result {
+        eprintln!("{}", e);
+        process::exit(1);
+    }
 
-        //Listen for some key presses
-        if let Some(key) = e.button_args() {
-            if key.state == ButtonState::Press {
-                let result = game.pressed(&key.button, game_over);
-                //If a user pushes r, reset the game
-                if result.0 {
-                    reset = true;
-                }
-                //If a user collides with a block, game over
-                if result.1 {
-                    game_over = true;
-                }
-            }
-        }
+    let game = rollback.confirmed();
+    let scores: Vec<u64> = game.players.iter().map(|x| x.score).collect();
+    println!(
+        "{} ticks, scores {:?}, checksum {:016x}, {} rollbacks",
+        game.ticks,
+        scores,
+        game.checksum(),
+        rollback.rollbacks()
+    );
+}
 
-        //If a user decided to restart the game.
-        //Call restart to reset everything about the agme.
-        if reset {
-            game.restart();
-            reset = false;
-            game_over = false;
+//Have the pilot play game after game without a window, to shake out
+//panics and see how it does. Prints a summary at the end.
+fn soak(ticks: u64) {
+    let mut world = Env::new(0);
+    let mut pilot = Pilot::new();
+    let mut games = 0;
+    let mut score = 0;
+    let mut best = 0;
+    let mut played = 0;
+
+    for _ in 0..ticks {
+        let action = pilot.decide(world.game());
+        let step = world.step(action);
+        score += step.reward;
+        played += 1;
+        if step.done {
+            games += 1;
+            best = best.max(played);
+            played = 0;
+            world.reset(games);
+            pilot = Pilot::new();
         }
     }
+
+    println!(
+        "{} ticks, {} games, {} points, longest game {} ticks",
+        ticks, games, score, best
+    );
 }
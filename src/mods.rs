@@ -0,0 +1,212 @@
+// Copyright © 2018 William Haugen - Piemyth
+// [This work is licensed under the "BSD 2-Clause License"]
+// Please see the file LICENSE in the source
+// distribution of this software for license terms.
+
+//Asset packs dropped into the mods folder, each a folder of its own with a
+//manifest.txt describing it. Any file in a pack is used in place of the one
+//with the same name in assets, so a pack only needs what it changes. The
+//manifest is name = value lines with # starting a comment:
+//  name          shown on the options screen, default is the folder's name
+//  author        who made it
+//  description   a line about it
+//  shot          colour of the players' shots as RRGGBB
+//  bolt          colour of the enemies' shots
+//  stars         colour of the background stars
+
+use graphics;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use render::Palette;
+
+//Describes a pack, it isn't one without it.
+pub static MANIFEST: &str = "manifest.txt";
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Pack {
+    pub folder: PathBuf,
+    pub name: String,
+    pub author: String,
+    pub description: String,
+    pub palette: Palette,
+}
+
+impl Pack {
+    //Read the manifest of the pack in folder.
+    pub fn load(folder: &Path) -> Result<Pack, String> {
+        let path = folder.join(MANIFEST);
+        let text = fs::read_to_string(&path).map_err(|e| format!("{}: {}", path.display(), e))?;
+
+        let mut pack = Pack {
+            folder: folder.to_path_buf(),
+            name: folder
+                .file_name()
+                .map(|x| x.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+            author: String::new(),
+            description: String::new(),
+            palette: Palette::new(),
+        };
+        for (number, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+            let error = |x: String| format!("{}:{}: {}", path.display(), number + 1, x);
+
+            let mut parts = line.splitn(2, '=');
+            let key = parts.next().unwrap_or("").trim();
+            let value = match parts.next() {
+                Some(x) => x.trim().to_string(),
+                None => return Err(error("expected name = value".to_string())),
+            };
+            let color = || match parse_color(&value) {
+                Some(x) => Ok(x),
+                None => Err(error(format!("'{}' needs a colour as RRGGBB", key))),
+            };
+            match key {
+                "name" => pack.name = value.clone(),
+                "author" => pack.author = value.clone(),
+                "description" => pack.description = value.clone(),
+                "shot" => pack.palette.shot = color()?,
+                "bolt" => pack.palette.bolt = color()?,
+                "stars" => pack.palette.stars = color()?,
+                x => return Err(error(format!("unknown setting '{}'", x))),
+            }
+        }
+        Ok(pack)
+    }
+
+    //Every pack in the mods folder in name order, along with what went wrong
+    //with any that couldn't be read. No folder just means no packs.
+    pub fn list(mods: &Path) -> (Vec<Pack>, Vec<String>) {
+        let mut folders: Vec<PathBuf> = match fs::read_dir(mods) {
+            Ok(x) => x.filter_map(|x| x.ok()).map(|x| x.path()).collect(),
+            Err(_) => return (Vec::new(), Vec::new()),
+        };
+        folders.sort();
+
+        let mut packs = Vec::new();
+        let mut problems = Vec::new();
+        for x in folders.iter().filter(|x| x.join(MANIFEST).is_file()) {
+            match Pack::load(x) {
+                Ok(y) => packs.push(y),
+                Err(e) => problems.push(e),
+            }
+        }
+        (packs, problems)
+    }
+
+    //Folder name of the pack picked last time, from the options file.
+    pub fn chosen(options: &Path) -> Option<String> {
+        let text = fs::read_to_string(options).ok()?;
+        text.lines()
+            .filter_map(|x| {
+                let mut parts = x.splitn(2, '=');
+                match (parts.next(), parts.next()) {
+                    (Some(key), Some(value)) if key.trim() == "skin" => {
+                        Some(value.trim().to_string())
+                    }
+                    _ => None,
+                }
+            })
+            .next()
+    }
+
+    //Keep which pack is being used for next time, none for the usual assets.
+    pub fn remember(options: &Path, pack: Option<&Pack>) -> io::Result<()> {
+        match pack.and_then(|x| x.folder.file_name()) {
+            Some(x) => fs::write(options, format!("skin = {}\n", x.to_string_lossy())),
+            None => fs::write(options, ""),
+        }
+    }
+}
+
+//Where mods go, next to the assets folder.
+pub fn mods_folder(assets: &Path) -> PathBuf {
+    assets.with_file_name("mods")
+}
+
+fn parse_color(word: &str) -> Option<[f32; 4]> {
+    if word.len() == 6 && word.chars().all(|x| x.is_ascii_hexdigit()) {
+        Some(graphics::color::hex(word))
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //A mods folder of its own for each test, with packs of the manifests given.
+    fn mods(name: &str, packs: &[(&str, &str)]) -> PathBuf {
+        let folder = ::std::env::temp_dir().join(format!("galaga-mods-{}", name));
+        let _ = fs::remove_dir_all(&folder);
+        for (pack, manifest) in packs.iter() {
+            fs::create_dir_all(folder.join(pack)).unwrap();
+            fs::write(folder.join(pack).join(MANIFEST), manifest).unwrap();
+        }
+        folder
+    }
+
+    #[test]
+    fn reads_manifests() {
+        let neon = "name = Neon Nights # glowing\nauthor = Someone\nshot = 00FF88\n";
+        let plain = "\n# Nothing changed\n";
+        let folder = mods("reads", &[("neon", neon), ("plain", plain)]);
+        let (packs, problems) = Pack::list(&folder);
+        fs::remove_dir_all(&folder).unwrap();
+        assert!(problems.is_empty());
+        assert_eq!(packs.len(), 2);
+        assert_eq!(packs[0].name, "Neon Nights");
+        assert_eq!(packs[0].author, "Someone");
+        assert_eq!(packs[0].palette.shot, graphics::color::hex("00FF88"));
+        assert_eq!(packs[0].palette.bolt, Palette::new().bolt);
+        assert_eq!(packs[1].name, "plain");
+        assert_eq!(packs[1].palette, Palette::new());
+    }
+
+    #[test]
+    fn errors_say_where() {
+        let folder = mods(
+            "errors",
+            &[
+                ("a", "name = A\nshot = yellow\n"),
+                ("b", "stars = FFFFFF\n\nsound = loud\n"),
+                ("c", "# c\nauthor\n"),
+                ("d", "bolt = FF44\n"),
+            ],
+        );
+        let (packs, problems) = Pack::list(&folder);
+        let problems: Vec<String> = problems
+            .iter()
+            .map(|x| x.replace(&folder.display().to_string(), "mods"))
+            .collect();
+        fs::remove_dir_all(&folder).unwrap();
+        assert!(packs.is_empty());
+        assert_eq!(
+            problems,
+            vec![
+                "mods/a/manifest.txt:2: 'shot' needs a colour as RRGGBB",
+                "mods/b/manifest.txt:3: unknown setting 'sound'",
+                "mods/c/manifest.txt:2: expected name = value",
+                "mods/d/manifest.txt:1: 'bolt' needs a colour as RRGGBB",
+            ]
+        );
+    }
+
+    #[test]
+    fn remembers_the_pick() {
+        let folder = mods("remembers", &[("neon", "")]);
+        let options = folder.join("galaga.options");
+        let pack = Pack::load(&folder.join("neon")).unwrap();
+        Pack::remember(&options, Some(&pack)).unwrap();
+        assert_eq!(Pack::chosen(&options), Some("neon".to_string()));
+        Pack::remember(&options, None).unwrap();
+        assert_eq!(Pack::chosen(&options), None);
+        fs::remove_dir_all(&folder).unwrap();
+    }
+}
@@ -0,0 +1,359 @@
+// Copyright © 2018 William Haugen - Piemyth
+// [This work is licensed under the "BSD 2-Clause License"]
+// Please see the file LICENSE in the source
+// distribution of this software for license terms.
+
+//Two player co-op over TCP, kept in sync with lockstep. Both sides run the
+//whole game, and the only thing that goes over the connection is what each
+//player did on each tick. A tick only gets simulated once both players'
+//actions for it are in, and since the game is deterministic both sides end
+//up in the same state. Each tick also carries a checksum of the state it
+//started from, so if the two ever do drift apart it gets noticed.
+//
+//Everything is sent as lines of text:
+//  galaga-lockstep <version> <seed>        from the host when someone joins
+//  <tick> <checksum> <action> <action>...  from both, once per tick
+
+use std::collections::VecDeque;
+use std::io::{self, ErrorKind, Read, Write};
+use std::net::{TcpListener, TcpStream};
+
+use game::{Action, Game};
+
+//Version sent in the greeting, both sides have to match.
+static NETVERSION: u32 = 1;
+
+//Something that carries lines of text to the other player and back.
+pub trait Link {
+    fn send(&mut self, line: &str) -> Result<(), String>;
+
+    //Every whole line that has come in since the last call.
+    //Errors once the other end is gone and there's nothing left to read.
+    fn receive(&mut self) -> Result<Vec<String>, String>;
+}
+
+//Lines over a TCP connection, never blocks.
+pub struct TcpLink {
+    stream: TcpStream,
+    buffer: Vec<u8>,
+    //Sent but not taken by the connection yet, because it was full.
+    outgoing: Vec<u8>,
+    //The other end closed the connection.
+    closed: bool,
+}
+
+//What a player did on one tick and the checksum of the state before it.
+struct Input {
+    tick: u64,
+    checksum: u64,
+    actions: Vec<Action>,
+}
+
+pub struct Lockstep {
+    link: TcpLink,
+    //Which player this side is flying, the host is player 0.
+    local: usize,
+    //Local actions waiting to be sent with the next tick.
+    pending: Vec<Action>,
+    //What got sent for the tick being waited on.
+    sent: Option<Input>,
+    //Ticks the peer has sent that haven't been simulated yet.
+    received: VecDeque<Input>,
+}
+
+//Wait for somebody to join on the address, then tell them the seed.
+//The host flies player 1's ship.
+pub fn host(address: &str, seed: u64) -> io::Result<TcpStream> {
+    let listener = TcpListener::bind(address)?;
+    let (mut stream, _) = listener.accept()?;
+    writeln!(stream, "galaga-lockstep {} {}", NETVERSION, seed)?;
+    Ok(stream)
+}
+
+//Connect to a host, returning the connection and the seed to start
+//a Game::coop with. Whoever joins flies player 2's ship.
+pub fn join(address: &str) -> io::Result<(TcpStream, u64)> {
+    let mut stream = TcpStream::connect(address)?;
+
+    //Read the greeting a byte at a time so nothing after it gets lost.
+    let mut greeting = Vec::new();
+    let mut byte = [0u8; 1];
+    while greeting.len() < 256 {
+        if stream.read(&mut byte)? == 0 || byte[0] == b'\n' {
+            break;
+        }
+        greeting.push(byte[0]);
+    }
+    let line = String::from_utf8_lossy(&greeting);
+    let bad = || io::Error::new(ErrorKind::InvalidData, "not a galaga host");
+    let mut words = line.split_whitespace();
+    if words.next() != Some("galaga-lockstep") {
+        return Err(bad());
+    }
+    match words.next().and_then(|x| x.parse::<u32>().ok()) {
+        Some(x) if x == NETVERSION => {}
+        _ => {
+            return Err(io::Error::new(
+                ErrorKind::InvalidData,
+                "host is running a different version",
+            ))
+        }
+    }
+    let seed = words.next().and_then(|x| x.parse().ok()).ok_or_else(bad)?;
+
+    Ok((stream, seed))
+}
+
+impl TcpLink {
+    pub fn new(stream: TcpStream) -> io::Result<TcpLink> {
+        stream.set_nodelay(true)?;
+        stream.set_nonblocking(true)?;
+        Ok(TcpLink {
+            stream,
+            buffer: Vec::new(),
+            outgoing: Vec::new(),
+            closed: false,
+        })
+    }
+
+    //Write as much of what's waiting to go out as the connection takes
+    //right now, the rest is tried again next time.
+    fn flush(&mut self) -> Result<(), String> {
+        while !self.outgoing.is_empty() {
+            match self.stream.write(&self.outgoing) {
+                Ok(0) => return Err("lost connection: nothing could be written".to_string()),
+                Ok(written) => {
+                    self.outgoing.drain(..written);
+                }
+                Err(ref e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(ref e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => return Err(format!("lost connection: {}", e)),
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Link for TcpLink {
+    fn send(&mut self, line: &str) -> Result<(), String> {
+        self.outgoing.extend_from_slice(line.as_bytes());
+        self.outgoing.push(b'\n');
+        self.flush()
+    }
+
+    //Read whatever the peer has sent so far, keeping any partial line for later.
+    //Anything they sent before leaving still gets handed out first.
+    fn receive(&mut self) -> Result<Vec<String>, String> {
+        self.flush()?;
+        let mut bytes = [0u8; 1024];
+        while !self.closed {
+            match self.stream.read(&mut bytes) {
+                Ok(0) => self.closed = true,
+                Ok(read) => self.buffer.extend_from_slice(&bytes[..read]),
+                Err(ref e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(e) => return Err(format!("lost connection: {}", e)),
+            }
+        }
+
+        let mut lines = Vec::new();
+        while let Some(end) = self.buffer.iter().position(|&x| x == b'\n') {
+            let line: Vec<u8> = self.buffer.drain(..end + 1).collect();
+            lines.push(String::from_utf8_lossy(&line).trim().to_string());
+        }
+        if lines.is_empty() && self.closed {
+            return Err("the other player disconnected".to_string());
+        }
+        Ok(lines)
+    }
+}
+
+impl Lockstep {
+    //Play over a connection made with host or join, as the given player.
+    pub fn new(stream: TcpStream, local: usize) -> io::Result<Lockstep> {
+        Ok(Lockstep {
+            link: TcpLink::new(stream)?,
+            local,
+            pending: Vec::new(),
+            sent: None,
+            received: VecDeque::new(),
+        })
+    }
+
+    //Which player this side is flying.
+    pub fn local(&self) -> usize {
+        self.local
+    }
+
+    //Something the local player did, it gets sent with the next tick.
+    pub fn push(&mut self, action: Action) {
+        self.pending.push(action);
+    }
+
+    //Send the local actions for the game's current tick if they haven't been
+    //already, then simulate the tick if the peer's actions for it are in.
+    //Returns true if the game moved on a tick, or an error if the connection
+    //went away or the two games are no longer the same.
+    pub fn step(&mut self, game: &mut Game) -> Result<bool, String> {
+        if self.sent.is_none() {
+            let input = Input {
+                tick: game.ticks,
+                checksum: game.checksum(),
+                actions: self.pending.drain(..).collect(),
+            };
+            let mut line = format!("{} {:x}", input.tick, input.checksum);
+            for x in input.actions.iter() {
+                line.push(' ');
+                line.push_str(x.name());
+            }
+            self.link.send(&line)?;
+            self.sent = Some(input);
+        }
+
+        //Only worry about the connection being gone if there's nothing left to do.
+        match self.link.receive() {
+            Ok(lines) => {
+                for line in lines {
+                    match parse(&line) {
+                        Some(x) => self.received.push_back(x),
+                        None => return Err(format!("bad input from peer '{}'", line)),
+                    }
+                }
+            }
+            Err(e) => {
+                if self.received.is_empty() {
+                    return Err(e);
+                }
+            }
+        }
+
+        let ready = match (&self.sent, self.received.front()) {
+            (Some(sent), Some(remote)) => {
+                if remote.tick != sent.tick {
+                    return Err(format!(
+                        "out of step: waiting on tick {} but got tick {}",
+                        sent.tick, remote.tick
+                    ));
+                }
+                if remote.checksum != sent.checksum {
+                    return Err(format!("desync on tick {}", sent.tick));
+                }
+                true
+            }
+            _ => false,
+        };
+        if !ready {
+            return Ok(false);
+        }
+
+        //Player 1 always goes first so both sides do things in the same order.
+        let local = self.sent.take().unwrap().actions;
+        let remote = self.received.pop_front().unwrap().actions;
+        if self.local == 0 {
+            game.advance(&[local, remote]);
+        } else {
+            game.advance(&[remote, local]);
+        }
+        Ok(true)
+    }
+}
+
+//Read a '<tick> <checksum> <action>...' line.
+fn parse(line: &str) -> Option<Input> {
+    let mut words = line.split_whitespace();
+    let tick = words.next()?.parse().ok()?;
+    let checksum = u64::from_str_radix(words.next()?, 16).ok()?;
+    let mut actions = Vec::new();
+    for x in words {
+        actions.push(Action::from_name(x)?);
+    }
+    Some(Input {
+        tick,
+        checksum,
+        actions,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ai::Pilot;
+    use std::thread;
+    use std::time::Duration;
+
+    //Both ends of a connection through a listener on a free local port.
+    fn connect() -> (TcpStream, TcpStream) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (host, _) = listener.accept().unwrap();
+        (host, client)
+    }
+
+    //Pilots fly both ships in games from the seeds for ticks ticks, checking
+    //both sides agree after each one. Gives back the first error if any.
+    fn play(seeds: [u64; 2], ticks: u64) -> Result<(), String> {
+        let (host, client) = connect();
+        let mut peers = [
+            Lockstep::new(host, 0).unwrap(),
+            Lockstep::new(client, 1).unwrap(),
+        ];
+        let mut games = [Game::coop(seeds[0]), Game::coop(seeds[1])];
+        let mut pilots = [Pilot::flying(0), Pilot::flying(1)];
+
+        for _ in 0..ticks {
+            for i in 0..2 {
+                let action = pilots[i].decide(&games[i]);
+                peers[i].push(action);
+            }
+            let mut done = [false, false];
+            let mut tries = 0;
+            while !(done[0] && done[1]) {
+                for i in 0..2 {
+                    if !done[i] {
+                        done[i] = peers[i].step(&mut games[i])?;
+                    }
+                }
+                tries += 1;
+                assert!(tries < 1000, "the other side never answered");
+                thread::sleep(Duration::from_millis(1));
+            }
+            assert_eq!(games[0].checksum(), games[1].checksum());
+        }
+        assert_eq!(games[0], games[1]);
+        assert_eq!(games[0].ticks, ticks);
+        Ok(())
+    }
+
+    #[test]
+    fn stays_in_step() {
+        play([5, 5], 200).unwrap();
+    }
+
+    #[test]
+    fn catches_desync() {
+        assert_eq!(play([5, 6], 10), Err("desync on tick 0".to_string()));
+    }
+
+    #[test]
+    fn sends_everything_when_the_connection_is_full() {
+        let (host, client) = connect();
+        let (mut host, mut client) = (TcpLink::new(host).unwrap(), TcpLink::new(client).unwrap());
+        let line = "x".repeat(1000);
+        //Far more than the connection holds without the other end reading.
+        let count = 20000;
+        for _ in 0..count {
+            host.send(&line).unwrap();
+        }
+        assert!(!host.outgoing.is_empty());
+
+        let mut lines = Vec::new();
+        let mut tries = 0;
+        while lines.len() < count {
+            host.receive().unwrap();
+            lines.extend(client.receive().unwrap());
+            tries += 1;
+            assert!(tries < 100000, "only {} lines came through", lines.len());
+        }
+        assert!(lines.iter().all(|x| *x == line));
+        assert!(host.outgoing.is_empty());
+    }
+}
@@ -0,0 +1,210 @@
+// Copyright © 2018 William Haugen - Piemyth
+// [This work is licensed under the "BSD 2-Clause License"]
+// Please see the file LICENSE in the source
+// distribution of this software for license terms.
+
+use graphics;
+use opengl_graphics::GlGraphics;
+use piston::input::RenderArgs;
+use rand::{self, Rng};
+
+use screen;
+use GRIDSIZE;
+
+//Max number of particles alive at once. Once the pool is full
+//the oldest particle gets recycled for the new one.
+static POOLSIZE: usize = 512;
+
+#[derive(Clone, Copy)]
+struct Particle {
+    pos: (f64, f64),
+    vel: (f64, f64),
+    life: f64,
+    max_life: f64,
+    size: f64,
+    color: [f32; 4],
+    alive: bool,
+}
+
+//Pool of particles used for explosions, hits on rocks,
+//the ship's engine and the player dying.
+pub struct Particles {
+    pool: Vec<Particle>,
+    next: usize,
+    exhaust: f64,
+    //Bursts have this many times their usual particles, from the config.
+    pub amount: f64,
+    //Exhaust particles a second.
+    pub exhaust_rate: f64,
+}
+
+impl Default for Particles {
+    fn default() -> Particles {
+        Particles::new()
+    }
+}
+
+impl Particles {
+    pub fn new() -> Particles {
+        let dead = Particle {
+            pos: (0.0, 0.0),
+            vel: (0.0, 0.0),
+            life: 0.0,
+            max_life: 0.0,
+            size: 0.0,
+            color: [0.0; 4],
+            alive: false,
+        };
+
+        Particles {
+            pool: vec![dead; POOLSIZE],
+            next: 0,
+            exhaust: 0.0,
+            amount: 1.0,
+            exhaust_rate: 40.0,
+        }
+    }
+
+    //Draw all living particles, fading them out as their life runs out.
+    pub fn render(&self, gl: &mut GlGraphics, args: &RenderArgs) {
+        gl.draw(args.viewport(), |c, gl| {
+            let c = screen::view(c, args);
+            for x in self.pool.iter().filter(|x| x.alive) {
+                let fade = (x.life / x.max_life) as f32;
+                let mut color = x.color;
+                color[3] *= fade;
+
+                let size = x.size * (0.5 + 0.5 * fade as f64);
+                let square =
+                    graphics::rectangle::square(x.pos.0 - size / 2.0, x.pos.1 - size / 2.0, size);
+                graphics::rectangle(color, square, c.transform, gl);
+            }
+        });
+    }
+
+    //Move the particles along and age them by dt seconds.
+    pub fn update(&mut self, dt: f64) {
+        for x in self.pool.iter_mut().filter(|x| x.alive) {
+            x.life -= dt;
+            if x.life <= 0.0 {
+                x.alive = false;
+                continue;
+            }
+            x.pos.0 += x.vel.0 * dt;
+            x.pos.1 += x.vel.1 * dt;
+            //Slow them down a little so bursts settle instead of flying off.
+            x.vel.0 *= 1.0 - 1.5 * dt;
+            x.vel.1 *= 1.0 - 1.5 * dt;
+        }
+    }
+
+    //Burst of orange and yellow when an enemy ship gets destroyed.
+    pub fn explosion(&mut self, pos: (i64, i64)) {
+        let colors = [
+            graphics::color::hex("FFA500"),
+            graphics::color::hex("FFFF00"),
+            graphics::color::hex("FF4500"),
+        ];
+        self.burst(pos, 24, 120.0, 0.6, 4.0, &colors);
+    }
+
+    //Small grey sparks when a shot bounces off a rock.
+    pub fn impact(&mut self, pos: (i64, i64)) {
+        let colors = [
+            graphics::color::hex("C0C0C0"),
+            graphics::color::hex("FFFF00"),
+        ];
+        self.burst(pos, 8, 60.0, 0.3, 3.0, &colors);
+    }
+
+    //Big white and red burst for when the player ship is hit.
+    pub fn death(&mut self, pos: (i64, i64)) {
+        let colors = [
+            graphics::color::hex("FFFFFF"),
+            graphics::color::hex("FF0000"),
+            graphics::color::hex("FFA500"),
+        ];
+        self.burst(pos, 80, 180.0, 1.5, 5.0, &colors);
+    }
+
+    //Trail of flames coming out the bottom of the ship.
+    //dt is used so the amount of exhaust doesn't depend on frame rate.
+    pub fn exhaust(&mut self, pos: (i64, i64), dt: f64) {
+        let mut rng = rand::thread_rng();
+        self.exhaust += dt * self.exhaust_rate;
+        while self.exhaust >= 1.0 {
+            self.exhaust -= 1.0;
+            let color = if rng.gen() {
+                graphics::color::hex("FFA500")
+            } else {
+                graphics::color::hex("00BFFF")
+            };
+            let x = (pos.0 * GRIDSIZE + GRIDSIZE / 2) as f64 + rng.gen_range(-3.0, 3.0);
+            let y = ((pos.1 + 1) * GRIDSIZE) as f64;
+            self.spawn(
+                (x, y),
+                (rng.gen_range(-10.0, 10.0), rng.gen_range(40.0, 90.0)),
+                rng.gen_range(0.15, 0.3),
+                3.0,
+                color,
+            );
+        }
+    }
+
+    //Kill every particle, used when restarting the game.
+    pub fn clear(&mut self) {
+        for x in self.pool.iter_mut() {
+            x.alive = false;
+        }
+    }
+
+    //Spread count particles out from the center of a grid square.
+    fn burst(
+        &mut self,
+        pos: (i64, i64),
+        count: usize,
+        speed: f64,
+        life: f64,
+        size: f64,
+        colors: &[[f32; 4]],
+    ) {
+        let mut rng = rand::thread_rng();
+        let center = (
+            (pos.0 * GRIDSIZE + GRIDSIZE / 2) as f64,
+            (pos.1 * GRIDSIZE + GRIDSIZE / 2) as f64,
+        );
+        let count = (count as f64 * self.amount).round() as usize;
+        for _ in 0..count {
+            let angle: f64 = rng.gen_range(0.0, 2.0 * ::std::f64::consts::PI);
+            let speed = rng.gen_range(speed * 0.2, speed);
+            let color = colors[rng.gen_range(0, colors.len())];
+            self.spawn(
+                center,
+                (angle.cos() * speed, angle.sin() * speed),
+                rng.gen_range(life * 0.5, life),
+                size,
+                color,
+            );
+        }
+    }
+
+    //Grab a dead particle from the pool, or recycle the oldest one.
+    fn spawn(&mut self, pos: (f64, f64), vel: (f64, f64), life: f64, size: f64, color: [f32; 4]) {
+        let len = self.pool.len();
+        let index = (0..len)
+            .map(|x| (self.next + x) % len)
+            .find(|&x| !self.pool[x].alive)
+            .unwrap_or(self.next);
+        self.next = (index + 1) % len;
+
+        self.pool[index] = Particle {
+            pos,
+            vel,
+            life,
+            max_life: life,
+            size,
+            color,
+            alive: true,
+        };
+    }
+}
@@ -0,0 +1,334 @@
+// Copyright © 2018 William Haugen - Piemyth
+// [This work is licensed under the "BSD 2-Clause License"]
+// Please see the file LICENSE in the source
+// distribution of this software for license terms.
+
+use graphics::{self, Transformed};
+use opengl_graphics::{GlGraphics, GlyphCache};
+use piston::input::RenderArgs;
+
+use config::Config;
+use events::Event;
+use game::{Bullet, Enemy, Game, Ship};
+use particles::Particles;
+use screen;
+use sprites::Atlas;
+use starfield::Starfield;
+use GRIDSIZE;
+
+//Sprite for each player's ship, in player order.
+static SHIPS: [&str; 2] = ["ship", "ship2"];
+//Seconds a score popup floats up for, and how far it goes in that time.
+static POPUPTIME: f64 = 0.8;
+static POPUPRISE: f64 = 30.0;
+//Every sprite that gets drawn, for standing in placeholders when the
+//definitions can't be loaded.
+pub static SPRITES: [&str; 10] = [
+    "ship",
+    "ship2",
+    "enemy",
+    "bee",
+    "butterfly",
+    "boss",
+    "kamikaze",
+    "zigzagger",
+    "rock",
+    "explosion",
+];
+
+//Colours of the things drawn without sprites, an asset pack can change them.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Palette {
+    pub shot: [f32; 4],
+    pub bolt: [f32; 4],
+    pub stars: [f32; 4],
+}
+
+impl Default for Palette {
+    fn default() -> Palette {
+        Palette::new()
+    }
+}
+
+impl Palette {
+    pub fn new() -> Palette {
+        Palette {
+            shot: graphics::color::hex("FFFF00"),
+            bolt: graphics::color::hex("FF4422"),
+            stars: graphics::color::hex("FFFFFF"),
+        }
+    }
+}
+
+//Draws the game with OpenGL, along with all the effects
+//that only matter for how it looks.
+pub struct Renderer {
+    pub gl: GlGraphics,
+    pub particles: Particles,
+    pub background: Starfield,
+    //Explosions playing and how long they've been playing for.
+    explosions: Vec<((i64, i64), f64)>,
+    //Points scored floating up from where they were scored, and for how long.
+    popups: Vec<((i64, i64), u64, f64)>,
+    //Seconds of animation time, drives the looping sprites.
+    clock: f64,
+    //Seconds of warp between stages.
+    warp: f64,
+    palette: Palette,
+}
+
+impl Renderer {
+    pub fn new(gl: GlGraphics) -> Renderer {
+        Renderer {
+            gl,
+            particles: Particles::new(),
+            background: Starfield::new(),
+            explosions: Vec::new(),
+            popups: Vec::new(),
+            clock: 0.0,
+            warp: 1.5,
+            palette: Palette::new(),
+        }
+    }
+
+    //Use the tuning values from the config and the colours from the palette.
+    pub fn tune(&mut self, config: &Config, palette: &Palette) {
+        self.background.scroll = config.scroll;
+        self.particles.amount = config.particles;
+        self.particles.exhaust_rate = config.exhaust;
+        self.warp = config.warp;
+        self.background.tint = palette.stars;
+        self.palette = palette.clone();
+    }
+
+    //Gets screen and renders ship all the sprites on the screen.
+    pub fn render(
+        &mut self,
+        game: &mut Game,
+        arg: &RenderArgs,
+        atlas: &Atlas,
+        glyphs: &mut GlyphCache,
+    ) {
+        for (i, x) in game.players.iter_mut().enumerate() {
+            if x.alive() {
+                let sprite = SHIPS[i % SHIPS.len()];
+                x.ship
+                    .render(&mut self.gl, arg, atlas, sprite, self.clock, &self.palette);
+            }
+        }
+        game.enemies
+            .render(&mut self.gl, arg, atlas, self.clock, &self.palette);
+
+        //Explosions and particles go on top of everything else.
+        self.explosions
+            .retain(|x| !atlas.finished("explosion", x.1));
+        self.popups.retain(|x| x.2 < POPUPTIME);
+        let explosions = &self.explosions;
+        let popups = &self.popups;
+        self.gl.draw(arg.viewport(), |c, gl| {
+            let c = screen::view(c, arg);
+            for x in explosions.iter() {
+                let rect = graphics::rectangle::square(
+                    ((x.0).0 * GRIDSIZE - GRIDSIZE / 2) as f64,
+                    ((x.0).1 * GRIDSIZE - GRIDSIZE / 2) as f64,
+                    (GRIDSIZE * 2) as f64,
+                );
+                atlas.draw("explosion", x.1, rect, c.transform, gl);
+            }
+
+            //Scores rise and fade out from where they were made.
+            for x in popups.iter() {
+                let fade = (1.0 - x.2 / POPUPTIME) as f32;
+                let transform = c.transform.trans(
+                    ((x.0).0 * GRIDSIZE) as f64,
+                    ((x.0).1 * GRIDSIZE) as f64 - POPUPRISE * x.2 / POPUPTIME,
+                );
+                graphics::text::Text::new_color([1.0, 0.85, 0.2, fade], 14)
+                    .draw(&x.1.to_string(), glyphs, &c.draw_state, transform, gl)
+                    .unwrap();
+            }
+        });
+        self.particles.render(&mut self.gl, arg);
+    }
+
+    //Move the particles along between game ticks so they animate smoothly.
+    //Engine exhaust stops once a player is out.
+    pub fn animate(&mut self, game: &mut Game, dt: f64, game_over: bool) {
+        self.clock += dt;
+        self.background.update(dt);
+        for x in self.explosions.iter_mut() {
+            x.1 += dt;
+        }
+        for x in self.popups.iter_mut() {
+            x.2 += dt;
+        }
+        if !game_over {
+            for x in game.players.iter_mut().filter(|x| x.alive()) {
+                let pos = x.ship.current_pos();
+                self.particles.exhaust(pos, dt);
+            }
+        }
+        self.particles.update(dt);
+    }
+
+    //Set off explosions and sparks for anything hit in the events given.
+    //Blow up any player ships that were hit, float up the points from
+    //anything scored, warp the stars when the game picks up and slow them
+    //down once the game is over.
+    pub fn effects(&mut self, events: &[Event], game_over: bool) {
+        for x in events.iter() {
+            match *x {
+                Event::EnemyDestroyed { pos, points, .. } => {
+                    self.particles.explosion(pos);
+                    self.explosions.push((pos, 0.0));
+                    self.popup(pos, points);
+                }
+                Event::RockHit { pos, points, .. } => {
+                    self.particles.impact(pos);
+                    self.popup(pos, points);
+                }
+                Event::EnemyCrashed { pos, .. } => {
+                    self.particles.explosion(pos);
+                    self.explosions.push((pos, 0.0));
+                }
+                Event::EnemyDamaged { pos, .. } | Event::Impact { pos, .. } => {
+                    self.particles.impact(pos)
+                }
+                Event::PlayerHit { pos, .. } => {
+                    self.particles.death(pos);
+                    self.explosions.push((pos, 0.0));
+                }
+                Event::Warp => self.background.warp(self.warp),
+                _ => {}
+            }
+        }
+        if game_over {
+            //Let the stars drift to a crawl once everyone is gone.
+            self.background.set_speed(0.25);
+        }
+    }
+
+    //Float up the points scored at pos, if it was worth any.
+    fn popup(&mut self, pos: (i64, i64), points: u64) {
+        if points > 0 {
+            self.popups.push((pos, points, 0.0));
+        }
+    }
+
+    //Clear out any effects left over from the last game.
+    pub fn restart(&mut self) {
+        self.particles.clear();
+        self.explosions.clear();
+        self.popups.clear();
+    }
+}
+
+impl Ship {
+    //renders the ship, also will render the shots when created.
+    fn render(
+        &mut self,
+        gl: &mut GlGraphics,
+        args: &RenderArgs,
+        atlas: &Atlas,
+        sprite: &str,
+        time: f64,
+        palette: &Palette,
+    ) {
+        use graphics;
+
+        let ship = graphics::rectangle::square(
+            (self.pos_x * GRIDSIZE) as f64,
+            (self.pos_y * GRIDSIZE) as f64,
+            GRIDSIZE as f64,
+        );
+
+        gl.draw(args.viewport(), |c, gl| {
+            let c = screen::view(c, args);
+            //Draw the current frame of the ship's animation
+            atlas.draw(sprite, time, ship, c.transform, gl);
+        });
+
+        for x in self.shots.iter_mut() {
+            x.render(gl, args, palette.shot);
+        }
+    }
+}
+
+impl Bullet {
+    //Draw the bullet on the screen
+    fn render(&self, gl: &mut GlGraphics, args: &RenderArgs, color: [f32; 4]) {
+        use graphics;
+
+        let square = graphics::rectangle::square(
+            (self.pos_x * GRIDSIZE + GRIDSIZE / 4) as f64,
+            (self.pos_y * GRIDSIZE) as f64,
+            (GRIDSIZE / 2) as f64,
+        );
+
+        gl.draw(args.viewport(), |c, gl| {
+            let transform = screen::view(c, args).transform;
+
+            graphics::rectangle(color, square, transform, gl);
+        });
+    }
+}
+
+impl Enemy {
+    //renders the ship, also will render the shots when created.
+    fn render(
+        &mut self,
+        gl: &mut GlGraphics,
+        args: &RenderArgs,
+        atlas: &Atlas,
+        time: f64,
+        palette: &Palette,
+    ) {
+        use graphics;
+
+        let list = &self.list;
+        let rocks = &self.rocks;
+        let shots = &self.shots;
+        gl.draw(args.viewport(), |c, gl| {
+            let c = screen::view(c, args);
+            //Render all enemy ships in positions.
+            //Offset each one's animation by its column so they don't flap in sync.
+            for ships in list.iter() {
+                let new_ship = graphics::rectangle::square(
+                    (ships.pos_x * GRIDSIZE) as f64,
+                    (ships.pos_y * GRIDSIZE) as f64,
+                    GRIDSIZE as f64,
+                );
+                let offset = ships.pos_x as f64 * 0.07;
+                let sprite = ships.flight.as_ref().map(|x| x.kind.sprite());
+                atlas.draw(
+                    sprite.unwrap_or("enemy"),
+                    time + offset,
+                    new_ship,
+                    c.transform,
+                    gl,
+                );
+            }
+
+            //Render all rocks in their positions.
+            for rock in rocks.iter() {
+                let new_rock = graphics::rectangle::square(
+                    (rock.pos_x * GRIDSIZE) as f64,
+                    (rock.pos_y * GRIDSIZE) as f64,
+                    GRIDSIZE as f64,
+                );
+                let offset = rock.pos_x as f64 * 0.13;
+                atlas.draw("rock", time + offset, new_rock, c.transform, gl);
+            }
+
+            //Shots the enemies fired, coloured to stand out from the players'.
+            for x in shots.iter() {
+                let square = graphics::rectangle::square(
+                    (x.pos_x * GRIDSIZE + GRIDSIZE / 4) as f64,
+                    (x.pos_y * GRIDSIZE) as f64,
+                    (GRIDSIZE / 2) as f64,
+                );
+                graphics::rectangle(palette.bolt, square, c.transform, gl);
+            }
+        });
+    }
+}
@@ -0,0 +1,194 @@
+// Copyright © 2018 William Haugen - Piemyth
+// [This work is licensed under the "BSD 2-Clause License"]
+// Please see the file LICENSE in the source
+// distribution of this software for license terms.
+
+use std::collections::VecDeque;
+
+use game::Game;
+
+//How many ticks of the past are kept around to go back to.
+static HISTORY: usize = 6 * 5;
+//Most ticks that can be rewound on a full meter.
+static METER: u64 = 6 * 3;
+//Ticks of play it takes to earn back one tick of rewind.
+static REFILL: u64 = 4;
+
+//Rolling history of the last few seconds of the game, so time can be run
+//backwards after a mistake. The meter keeps it from being used all the time.
+pub struct Rewind {
+    history: VecDeque<Game>,
+    //Ticks of rewind left.
+    meter: u64,
+    //Ticks played towards the next bit of meter.
+    refill: u64,
+    //Debugging, rewinding never uses up the meter.
+    unlimited: bool,
+}
+
+impl Default for Rewind {
+    fn default() -> Rewind {
+        Rewind::new()
+    }
+}
+
+impl Rewind {
+    pub fn new() -> Rewind {
+        Rewind {
+            history: VecDeque::new(),
+            meter: METER,
+            refill: 0,
+            unlimited: false,
+        }
+    }
+
+    //Rewind as much as the history goes back, for scrubbing through
+    //what happened right before something went wrong.
+    pub fn unlimited() -> Rewind {
+        Rewind {
+            unlimited: true,
+            ..Rewind::new()
+        }
+    }
+
+    //Remember the game as it is before a tick gets played.
+    pub fn record(&mut self, game: &Game) {
+        let mut state = game.clone();
+        state.clear_events();
+        self.history.push_back(state);
+        if self.history.len() > HISTORY {
+            self.history.pop_front();
+        }
+
+        self.refill += 1;
+        if self.refill >= REFILL {
+            self.refill = 0;
+            self.meter = (self.meter + 1).min(METER);
+        }
+    }
+
+    //Put the game back one tick, as long as there's history and meter left.
+    //Returns true if it went back.
+    pub fn rewind(&mut self, game: &mut Game) -> bool {
+        if self.meter == 0 && !self.unlimited {
+            return false;
+        }
+        match self.history.pop_back() {
+            Some(x) => {
+                *game = x;
+                if !self.unlimited {
+                    self.meter -= 1;
+                }
+                self.refill = 0;
+                true
+            }
+            None => false,
+        }
+    }
+
+    //How full the meter is, from 0.0 to 1.0.
+    pub fn meter(&self) -> f64 {
+        if self.unlimited {
+            1.0
+        } else {
+            self.meter as f64 / METER as f64
+        }
+    }
+
+    //Forget the history and fill the meter back up, for a new game.
+    pub fn restart(&mut self) {
+        self.history.clear();
+        self.meter = METER;
+        self.refill = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ai::Pilot;
+    use game::Action;
+
+    //Play ticks on, remembering each one first, and give the actions played.
+    fn play(rewind: &mut Rewind, game: &mut Game, ticks: u64) -> Vec<Vec<Action>> {
+        let mut pilot = Pilot::flying(0);
+        let mut played = Vec::new();
+        for _ in 0..ticks {
+            rewind.record(game);
+            let actions = vec![vec![pilot.decide(game)], vec![Action::Idle]];
+            game.advance(&actions);
+            played.push(actions[0].clone());
+        }
+        played
+    }
+
+    //Go back as far as it lets, giving how many ticks that was.
+    fn rewind_all(rewind: &mut Rewind, game: &mut Game) -> u64 {
+        let mut count = 0;
+        while rewind.rewind(game) {
+            count += 1;
+        }
+        count
+    }
+
+    #[test]
+    fn oldest_ticks_are_forgotten() {
+        let mut rewind = Rewind::unlimited();
+        let mut game = Game::coop(1);
+        play(&mut rewind, &mut game, HISTORY as u64 + 10);
+        assert_eq!(rewind_all(&mut rewind, &mut game), HISTORY as u64);
+        assert_eq!(game.ticks, 10);
+    }
+
+    #[test]
+    fn meter_runs_out_and_refills() {
+        let mut rewind = Rewind::new();
+        let mut game = Game::coop(2);
+        play(&mut rewind, &mut game, HISTORY as u64);
+        assert_eq!(rewind.meter(), 1.0);
+        assert_eq!(rewind_all(&mut rewind, &mut game), METER);
+        assert_eq!(rewind.meter(), 0.0);
+
+        //A little play earns back a tick, and no more.
+        play(&mut rewind, &mut game, REFILL - 1);
+        assert!(!rewind.rewind(&mut game));
+        play(&mut rewind, &mut game, 1);
+        assert_eq!(rewind.meter(), 1.0 / METER as f64);
+        assert_eq!(rewind_all(&mut rewind, &mut game), 1);
+
+        //It tops out at full.
+        play(&mut rewind, &mut game, REFILL * (METER + 5));
+        assert_eq!(rewind.meter(), 1.0);
+        assert_eq!(rewind_all(&mut rewind, &mut game), METER);
+    }
+
+    #[test]
+    fn restart_forgets_everything() {
+        let mut rewind = Rewind::new();
+        let mut game = Game::coop(3);
+        play(&mut rewind, &mut game, 10);
+        rewind_all(&mut rewind, &mut game);
+        rewind.restart();
+        assert_eq!(rewind.meter(), 1.0);
+        assert!(!rewind.rewind(&mut game));
+    }
+
+    #[test]
+    fn going_back_plays_out_the_same() {
+        let mut rewind = Rewind::new();
+        let mut game = Game::coop(4);
+        play(&mut rewind, &mut game, 20);
+        let start = game.clone();
+        let played = play(&mut rewind, &mut game, 10);
+        let checksum = game.checksum();
+
+        for _ in 0..10 {
+            assert!(rewind.rewind(&mut game));
+        }
+        assert_eq!(game.checksum(), start.checksum());
+        for x in played.iter() {
+            game.advance(&[x.clone(), vec![Action::Idle]]);
+        }
+        assert_eq!(game.checksum(), checksum);
+    }
+}
@@ -0,0 +1,492 @@
+// Copyright © 2018 William Haugen - Piemyth
+// [This work is licensed under the "BSD 2-Clause License"]
+// Please see the file LICENSE in the source
+// distribution of this software for license terms.
+
+//Online co-op that never sits waiting on the other player. The local game
+//runs ahead guessing the other player did nothing, since most ticks nobody
+//presses anything. Alongside it a confirmed copy only moves on once both
+//players' actions for a tick are in. When the other player's real actions
+//turn out not to match the guess, the shown game is thrown away, restored
+//from the confirmed copy and played forward again with what really happened.
+//
+//Lines can get lost, so each one carries every action the other side hasn't
+//said it has yet:
+//  <ack> <first> <confirmed> <checksum> <actions> <actions>...
+//ack is the first tick still needed from the other side, then the actions
+//start at tick first, each tick's separated by commas or '-' for nothing.
+//The checksum is of the confirmed game at tick confirmed, so the two sides
+//can tell if they ever stopped agreeing.
+//
+//Ticks of the shown game get played again whenever a guess was wrong, so
+//its events are thrown away. Events are handed out from the confirmed game
+//instead, a little late but only once each and only for what really happened.
+
+use rand::Rng;
+use std::cell::RefCell;
+use std::collections::{BTreeMap, VecDeque};
+use std::rc::Rc;
+
+use game::{Action, Game};
+use net::Link;
+use rng::GameRng;
+
+//Furthest the shown game runs ahead of the confirmed one, past this
+//it waits for the other player to catch up.
+static MAXROLLBACK: u64 = 30;
+//How many ticks of our own checksums to keep for checking the other side's.
+static CHECKSUMS: usize = 64;
+
+pub struct Rollback<L: Link> {
+    link: L,
+    //Which player this side is flying.
+    local: usize,
+    //Local actions waiting for the next tick.
+    pending: Vec<Action>,
+    //Game as of the first tick we don't know both players' actions for.
+    confirmed: Game,
+    confirmed_frame: u64,
+    //Game being shown, ahead of confirmed on guesses.
+    predicted: Game,
+    frame: u64,
+    //Our actions for every tick not yet confirmed or not yet received.
+    inputs: BTreeMap<u64, Vec<Action>>,
+    //The other player's actions for ticks that aren't confirmed yet.
+    remote: BTreeMap<u64, Vec<Action>>,
+    //First tick we don't have the other player's actions for.
+    remote_next: u64,
+    //First tick the other player doesn't have our actions for.
+    acked: u64,
+    //Our recent confirmed checksums, and theirs waiting to be checked.
+    checksums: BTreeMap<u64, u64>,
+    remote_checksums: BTreeMap<u64, u64>,
+    //Times the shown game had to be restored and played again.
+    rollbacks: u64,
+}
+
+//In-process stand-in for a network connection, to try rollback without one.
+//Lines take a number of ticks to arrive and some never do.
+pub struct SimLink {
+    outgoing: Rc<RefCell<VecDeque<(u64, String)>>>,
+    incoming: Rc<RefCell<VecDeque<(u64, String)>>>,
+    //Calls to receive so far, this end's idea of what tick it is.
+    clock: u64,
+    latency: u64,
+    //Chance of a line getting lost, from 0.0 to 1.0.
+    loss: f64,
+    rng: GameRng,
+}
+
+impl<L: Link> Rollback<L> {
+    //Start a Game::coop from the seed, flying the given player's ship.
+    pub fn new(link: L, local: usize, seed: u64) -> Rollback<L> {
+        Rollback::with_game(link, local, Game::coop(seed))
+    }
+
+    //Start from a game both sides set up the same way.
+    pub fn with_game(link: L, local: usize, game: Game) -> Rollback<L> {
+        let mut checksums = BTreeMap::new();
+        checksums.insert(0, game.checksum());
+
+        Rollback {
+            link,
+            local,
+            pending: Vec::new(),
+            confirmed: game.clone(),
+            confirmed_frame: 0,
+            predicted: game,
+            frame: 0,
+            inputs: BTreeMap::new(),
+            remote: BTreeMap::new(),
+            remote_next: 0,
+            acked: 0,
+            checksums,
+            remote_checksums: BTreeMap::new(),
+            rollbacks: 0,
+        }
+    }
+
+    //Which player this side is flying.
+    pub fn local(&self) -> usize {
+        self.local
+    }
+
+    //The game as it's being shown, guesses and all.
+    pub fn game(&self) -> &Game {
+        &self.predicted
+    }
+
+    //The game as far as both players agree on it.
+    pub fn confirmed(&self) -> &Game {
+        &self.confirmed
+    }
+
+    //Ticks played in the shown game and in the confirmed one.
+    pub fn frames(&self) -> (u64, u64) {
+        (self.frame, self.confirmed_frame)
+    }
+
+    pub fn rollbacks(&self) -> u64 {
+        self.rollbacks
+    }
+
+    //Game over is only for real once it's confirmed.
+    pub fn over(&self) -> bool {
+        self.confirmed.over()
+    }
+
+    //A copy of the shown game for drawing, along with the events of every
+    //tick confirmed since the last copy.
+    pub fn snapshot(&mut self) -> Game {
+        let mut game = self.predicted.clone();
+        game.events = self.confirmed.take_events();
+        game
+    }
+
+    //Something the local player did, it happens on the next tick.
+    pub fn push(&mut self, action: Action) {
+        self.pending.push(action);
+    }
+
+    //Play the next tick, unless the shown game is too far ahead of the other
+    //player, and swap actions with them. Returns true if a tick was played,
+    //or an error if the connection went away or the games stopped matching.
+    pub fn step(&mut self) -> Result<bool, String> {
+        self.exchange(true)
+    }
+
+    //Swap actions with the other player without playing a tick,
+    //so they can catch up and everything gets confirmed.
+    pub fn wait(&mut self) -> Result<(), String> {
+        self.exchange(false).map(|_| ())
+    }
+
+    fn exchange(&mut self, play: bool) -> Result<bool, String> {
+        let mut wrong = false;
+        for line in self.link.receive()? {
+            if self.read(&line)? {
+                wrong = true;
+            }
+        }
+        self.confirm()?;
+
+        //Something was guessed wrong, go back to what's known and play it all again.
+        if wrong {
+            self.rollbacks += 1;
+            self.predicted = self.confirmed.clone();
+            for frame in self.confirmed_frame..self.frame {
+                self.simulate(frame);
+            }
+        }
+
+        let play = play && self.frame - self.confirmed_frame < MAXROLLBACK;
+        if play {
+            let actions = self.pending.drain(..).collect();
+            self.inputs.insert(self.frame, actions);
+            let frame = self.frame;
+            self.simulate(frame);
+            self.frame += 1;
+            self.confirm()?;
+        }
+
+        self.send()?;
+        Ok(play)
+    }
+
+    //Take in a line from the other player.
+    //Returns true if it shows a guess for a tick already played was wrong.
+    fn read(&mut self, line: &str) -> Result<bool, String> {
+        let bad = || format!("bad input from peer '{}'", line);
+        let mut words = line.split_whitespace();
+        let mut number = |hex: bool| -> Result<u64, String> {
+            let word = words.next().ok_or_else(bad)?;
+            let radix = if hex { 16 } else { 10 };
+            u64::from_str_radix(word, radix).map_err(|_| bad())
+        };
+        let ack = number(false)?;
+        let first = number(false)?;
+        let checkframe = number(false)?;
+        let checksum = number(true)?;
+
+        self.acked = self.acked.max(ack);
+        self.remote_checksums.insert(checkframe, checksum);
+
+        let mut wrong = false;
+        for (i, word) in words.enumerate() {
+            let frame = first + i as u64;
+            let actions = parse_actions(word).ok_or_else(bad)?;
+            if frame < self.remote_next || self.remote.contains_key(&frame) {
+                continue;
+            }
+            if frame < self.frame && actions != guess() {
+                wrong = true;
+            }
+            self.remote.insert(frame, actions);
+        }
+        while self.remote.contains_key(&self.remote_next) {
+            self.remote_next += 1;
+        }
+        Ok(wrong)
+    }
+
+    //Move the confirmed game on as far as both players' actions are known,
+    //then check it against the other player's checksums.
+    fn confirm(&mut self) -> Result<(), String> {
+        while self.confirmed_frame < self.remote_next && self.confirmed_frame < self.frame {
+            let frame = self.confirmed_frame;
+            let remote = self.remote.remove(&frame).unwrap_or_default();
+            let local = self.inputs[&frame].clone();
+            let actions = self.order(local, remote);
+            self.confirmed.advance(&actions);
+            self.confirmed_frame += 1;
+            self.checksums
+                .insert(self.confirmed_frame, self.confirmed.checksum());
+        }
+        while self.checksums.len() > CHECKSUMS {
+            let oldest = *self.checksums.keys().next().unwrap();
+            self.checksums.remove(&oldest);
+        }
+
+        for (frame, theirs) in self.remote_checksums.iter() {
+            match self.checksums.get(frame) {
+                Some(ours) if ours != theirs => return Err(format!("desync on tick {}", frame)),
+                _ => {}
+            }
+        }
+        let confirmed = self.confirmed_frame;
+        self.remote_checksums.retain(|&x, _| x > confirmed);
+
+        //Our actions are done with once they're confirmed and the other side has them.
+        let keep = self.acked.min(self.confirmed_frame);
+        self.inputs = self.inputs.split_off(&keep);
+        Ok(())
+    }
+
+    //Play a tick of the shown game, guessing for the other player if needed.
+    fn simulate(&mut self, frame: u64) {
+        let local = self.inputs[&frame].clone();
+        let remote = self.remote.get(&frame).cloned().unwrap_or_else(guess);
+        let actions = self.order(local, remote);
+        self.predicted.advance(&actions);
+        //Ticks get played again after a wrong guess, so only the confirmed
+        //game's events are handed out.
+        self.predicted.clear_events();
+    }
+
+    //Everything the other player doesn't have yet.
+    fn send(&mut self) -> Result<(), String> {
+        let mut line = format!(
+            "{} {} {} {:x}",
+            self.remote_next,
+            self.acked,
+            self.confirmed_frame,
+            self.checksums[&self.confirmed_frame]
+        );
+        for (_, actions) in self.inputs.range(self.acked..) {
+            line.push(' ');
+            if actions.is_empty() {
+                line.push('-');
+            } else {
+                let names: Vec<&str> = actions.iter().map(|x| x.name()).collect();
+                line.push_str(&names.join(","));
+            }
+        }
+        self.link.send(&line)
+    }
+
+    //Player 1's actions always go first so both sides play ticks the same way.
+    fn order(&self, local: Vec<Action>, remote: Vec<Action>) -> [Vec<Action>; 2] {
+        if self.local == 0 {
+            [local, remote]
+        } else {
+            [remote, local]
+        }
+    }
+}
+
+impl SimLink {
+    //Both ends of a connection where lines take latency ticks to arrive
+    //and each one has a chance of loss to go missing.
+    pub fn pair(latency: u64, loss: f64, seed: u64) -> (SimLink, SimLink) {
+        let one = Rc::new(RefCell::new(VecDeque::new()));
+        let two = Rc::new(RefCell::new(VecDeque::new()));
+        let end = |outgoing: &Rc<_>, incoming: &Rc<_>, seed: u64| SimLink {
+            outgoing: Rc::clone(outgoing),
+            incoming: Rc::clone(incoming),
+            clock: 0,
+            latency,
+            loss,
+            rng: GameRng::new(seed),
+        };
+        (end(&one, &two, seed), end(&two, &one, seed.wrapping_add(1)))
+    }
+}
+
+impl Link for SimLink {
+    fn send(&mut self, line: &str) -> Result<(), String> {
+        if self.rng.gen::<f64>() >= self.loss {
+            self.outgoing
+                .borrow_mut()
+                .push_back((self.clock + self.latency, line.to_string()));
+        }
+        Ok(())
+    }
+
+    fn receive(&mut self) -> Result<Vec<String>, String> {
+        self.clock += 1;
+        let mut incoming = self.incoming.borrow_mut();
+        let mut lines = Vec::new();
+        while incoming.front().map(|x| x.0 <= self.clock) == Some(true) {
+            lines.push(incoming.pop_front().unwrap().1);
+        }
+        Ok(lines)
+    }
+}
+
+//What the other player is guessed to have done, most ticks it's nothing.
+fn guess() -> Vec<Action> {
+    Vec::new()
+}
+
+//Read one tick's actions, like 'left,shoot' or '-' for none.
+fn parse_actions(word: &str) -> Option<Vec<Action>> {
+    if word == "-" {
+        return Some(Vec::new());
+    }
+    word.split(',').map(Action::from_name).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ai::Pilot;
+    use events::Event;
+
+    //Both sides, the actions each one played on every tick and the events
+    //each one handed out.
+    type Played = (
+        Vec<Rollback<SimLink>>,
+        Vec<Vec<Vec<Action>>>,
+        Vec<Vec<Event>>,
+    );
+
+    //Pilots fly both ships over a simulated connection until both sides
+    //have confirmed frames ticks.
+    fn play(latency: u64, loss: f64, frames: u64) -> Played {
+        let (one, two) = SimLink::pair(latency, loss, 7);
+        let mut peers = vec![Rollback::new(one, 0, 42), Rollback::new(two, 1, 42)];
+        let mut pilots = [Pilot::flying(0), Pilot::flying(1)];
+        let mut history: Vec<Vec<Vec<Action>>> = vec![Vec::new(), Vec::new()];
+        let mut carry: Vec<Vec<Action>> = vec![Vec::new(), Vec::new()];
+        let mut events: Vec<Vec<Event>> = vec![Vec::new(), Vec::new()];
+
+        for _ in 0..frames * 20 {
+            if peers.iter().all(|x| x.frames().1 >= frames) {
+                break;
+            }
+            for (i, peer) in peers.iter_mut().enumerate() {
+                if peer.frames().0 >= frames {
+                    peer.wait().unwrap();
+                } else {
+                    let action = pilots[i].decide(peer.game());
+                    peer.push(action);
+                    carry[i].push(action);
+                    if peer.step().unwrap() {
+                        history[i].push(carry[i].drain(..).collect());
+                    }
+                }
+                events[i].extend(peer.snapshot().events);
+            }
+        }
+        assert!(peers.iter().all(|x| x.frames() == (frames, frames)));
+        (peers, history, events)
+    }
+
+    //Both sides end up where playing every tick's real actions in order does.
+    fn check(latency: u64, loss: f64) -> u64 {
+        let frames = 400;
+        let (peers, history, _) = play(latency, loss, frames);
+
+        let mut reference = Game::coop(42);
+        for (one, two) in history[0].iter().zip(history[1].iter()) {
+            reference.advance(&[one.clone(), two.clone()]);
+        }
+        assert_eq!(history[0].len(), frames as usize);
+        assert_eq!(peers[0].confirmed(), peers[1].confirmed());
+        assert_eq!(peers[0].confirmed().checksum(), reference.checksum());
+        assert_eq!(peers[0].game().checksum(), reference.checksum());
+
+        peers.iter().map(|x| x.rollbacks()).sum()
+    }
+
+    #[test]
+    fn matches_without_latency() {
+        check(0, 0.0);
+    }
+
+    #[test]
+    fn matches_with_latency() {
+        //Pilots shoot all the time, so guessing nothing is wrong a lot.
+        assert!(check(4, 0.0) > 0);
+    }
+
+    #[test]
+    fn matches_with_loss() {
+        assert!(check(3, 0.3) > 0);
+        check(10, 0.6);
+    }
+
+    #[test]
+    fn events_once_with_loss() {
+        let (peers, _, events) = play(3, 0.3, 400);
+        assert!(peers.iter().all(|x| x.rollbacks() > 0));
+        for (peer, events) in peers.iter().zip(events) {
+            let destroyed = events
+                .iter()
+                .filter(|x| matches!(x, Event::EnemyDestroyed { .. }))
+                .count() as u64;
+            let kills: u64 = peer
+                .confirmed()
+                .players
+                .iter()
+                .map(|x| x.stats.kills())
+                .sum();
+            assert!(kills > 0);
+            assert_eq!(destroyed, kills);
+        }
+    }
+
+    #[test]
+    fn waits_when_too_far_ahead() {
+        let (one, _two) = SimLink::pair(0, 1.0, 1);
+        let mut peer = Rollback::new(one, 0, 1);
+        for _ in 0..MAXROLLBACK {
+            assert!(peer.step().unwrap());
+        }
+        assert!(!peer.step().unwrap());
+        assert_eq!(peer.frames(), (MAXROLLBACK, 0));
+    }
+
+    #[test]
+    fn catches_desync() {
+        let (one, two) = SimLink::pair(1, 0.0, 1);
+        let mut peers = [Rollback::new(one, 0, 1), Rollback::new(two, 1, 2)];
+        let mut result = Ok(true);
+        for _ in 0..10 {
+            for x in peers.iter_mut() {
+                result = result.and_then(|_| x.step());
+            }
+        }
+        assert_eq!(result, Err("desync on tick 0".to_string()));
+    }
+
+    #[test]
+    fn reads_actions() {
+        assert_eq!(parse_actions("-"), Some(Vec::new()));
+        assert_eq!(
+            parse_actions("left,shoot"),
+            Some(vec![Action::Left, Action::Shoot])
+        );
+        assert_eq!(parse_actions("left,jump"), None);
+    }
+}
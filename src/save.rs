@@ -0,0 +1,401 @@
+// Copyright © 2018 William Haugen - Piemyth
+// [This work is licensed under the "BSD 2-Clause License"]
+// Please see the file LICENSE in the source
+// distribution of this software for license terms.
+
+//Saving a game in progress to pick it back up later. Everything that decides
+//how the game plays out goes in the file, random number state included, so a
+//loaded game carries on exactly the way the saved one would have.
+//
+//Save files are text, one thing per line:
+//  galaga-save 1
+//  ticks 130
+//  spawnrate 10
+//  rng 1234567890
+//  lives 3
+//  stage <stage> <ticks into it>
+//  player <score> <lives> <start column> <x> <y> <multiplier> <combo ticks>
+//  stats <shots> <hits> <rocks> <ticks> <best multiplier> <destroyed of each kind>
+//                        belongs to the player above it, kinds in the order wave
+//                        files list them
+//  shot <x> <y>          belongs to the player above it
+//  enemy <x> <y>
+//  flight <kind> <path> <script or -> <column> <row> <hold> <age> <diving> <hp>
+//                        belongs to the enemy above it, if it's from a wave
+//  curve <x> <y> <x> <y> <x> <y>
+//                        start and control points, if the enemy above flies a curve
+//  rock <x> <y>
+//  bolt <x> <y>          a shot fired by an enemy
+//
+//The stages themselves aren't saved, the game picks back up at the same
+//stage of whatever wave file is loaded.
+
+use std::fs::File;
+use std::io::{self, Read, Write};
+
+use game::{Bullet, Flight, Game, Player, Ship};
+use rng::GameRng;
+use stats::Stats;
+use waves::{Entry, Kind};
+
+//Version written at the top of save files.
+static SAVEVERSION: u32 = 1;
+
+//The whole game as the text of a save file.
+pub fn to_string(game: &Game) -> String {
+    let mut text = format!("galaga-save {}\n", SAVEVERSION);
+    text.push_str(&format!("ticks {}\n", game.ticks));
+    text.push_str(&format!("spawnrate {}\n", game.spawnrate));
+    text.push_str(&format!("rng {}\n", game.rng.state()));
+    text.push_str(&format!("lives {}\n", game.lives));
+    text.push_str(&format!("stage {} {}\n", game.stage, game.stage_ticks));
+    for x in game.players.iter() {
+        text.push_str(&format!(
+            "player {} {} {} {} {} {} {}\n",
+            x.score, x.lives, x.start, x.ship.pos_x, x.ship.pos_y, x.multiplier, x.combo_ticks
+        ));
+        let destroyed: Vec<String> = x.stats.destroyed.iter().map(|y| y.to_string()).collect();
+        text.push_str(&format!(
+            "stats {} {} {} {} {} {}\n",
+            x.stats.shots,
+            x.stats.hits,
+            x.stats.rocks,
+            x.stats.ticks,
+            x.stats.best_multiplier,
+            destroyed.join(" ")
+        ));
+        for y in x.ship.shots.iter() {
+            text.push_str(&format!("shot {} {}\n", y.pos_x, y.pos_y));
+        }
+    }
+    for x in game.enemies.list.iter() {
+        text.push_str(&format!("enemy {} {}\n", x.pos_x, x.pos_y));
+        if let Some(ref y) = x.flight {
+            text.push_str(&format!(
+                "flight {} {} {} {} {} {} {} {} {}\n",
+                y.kind.name(),
+                y.entry.name(),
+                y.script.as_deref().unwrap_or("-"),
+                y.slot.0,
+                y.slot.1,
+                y.hold,
+                y.age,
+                y.diving as u8,
+                y.hp
+            ));
+            if let Some(ref z) = y.curve {
+                text.push_str(&format!(
+                    "curve {} {} {} {} {} {}\n",
+                    z[0].0, z[0].1, z[1].0, z[1].1, z[2].0, z[2].1
+                ));
+            }
+        }
+    }
+    for x in game.enemies.rocks.iter() {
+        text.push_str(&format!("rock {} {}\n", x.pos_x, x.pos_y));
+    }
+    for x in game.enemies.shots.iter() {
+        text.push_str(&format!("bolt {} {}\n", x.pos_x, x.pos_y));
+    }
+    text
+}
+
+//Read the text of a save file back into a game.
+//Errors say which line was wrong, name is put in front of them.
+pub fn parse(text: &str, name: &str) -> Result<Game, String> {
+    let mut lines = text.lines().enumerate();
+    let header = format!("galaga-save {}", SAVEVERSION);
+    match lines.next() {
+        Some((_, x)) if x.trim() == header => {}
+        Some((_, x)) if x.starts_with("galaga-save ") => {
+            return Err(format!(
+                "{}:1: saved by a different version, expected '{}'",
+                name, header
+            ))
+        }
+        _ => return Err(format!("{}:1: expected '{}'", name, header)),
+    }
+
+    let mut game = Game::seeded(0);
+    game.players.clear();
+    let mut seen = (false, false);
+
+    for (number, line) in lines {
+        let error = |message: &str| format!("{}:{}: {}", name, number + 1, message);
+        let mut words = line.split_whitespace();
+        let key = match words.next() {
+            Some(x) => x,
+            None => continue,
+        };
+
+        //The state is a full u64, too big to go through i64 with the rest.
+        if key == "rng" {
+            match (words.next().and_then(|x| x.parse().ok()), words.next()) {
+                (Some(x), None) => game.rng = GameRng::new(x),
+                _ => return Err(error("'rng' takes 1 number")),
+            }
+            seen.1 = true;
+            continue;
+        }
+        //Names first, then numbers.
+        let flight = if key == "flight" {
+            let kind = words.next().unwrap_or("");
+            let kind =
+                Kind::from_name(kind).ok_or_else(|| error(&format!("no enemy kind '{}'", kind)))?;
+            let entry = words.next().unwrap_or("");
+            let entry =
+                Entry::from_name(entry).ok_or_else(|| error(&format!("no path '{}'", entry)))?;
+            let script = match words.next() {
+                Some("-") => None,
+                Some(x) => Some(x.to_string()),
+                None => return Err(error("'flight' is missing its script")),
+            };
+            Some((kind, entry, script))
+        } else {
+            None
+        };
+        let values: Vec<i64> = match words.map(|x| x.parse()).collect() {
+            Ok(x) => x,
+            Err(_) => return Err(error("expected whole numbers")),
+        };
+        let count = |n: usize| {
+            if values.len() == n {
+                Ok(())
+            } else {
+                Err(error(&format!("'{}' takes {} numbers", key, n)))
+            }
+        };
+        let unsigned = |x: i64| {
+            if x >= 0 {
+                Ok(x as u64)
+            } else {
+                Err(error("can't be negative"))
+            }
+        };
+
+        match key {
+            "ticks" => {
+                count(1)?;
+                game.ticks = unsigned(values[0])?;
+                seen.0 = true;
+            }
+            "spawnrate" => {
+                count(1)?;
+                game.spawnrate = unsigned(values[0])?;
+                if game.spawnrate == 0 {
+                    return Err(error("spawnrate can't be 0"));
+                }
+            }
+            "lives" => {
+                count(1)?;
+                game.lives = unsigned(values[0])? as u32;
+            }
+            "stage" => {
+                count(2)?;
+                game.stage = unsigned(values[0])? as usize;
+                game.stage_ticks = unsigned(values[1])?;
+            }
+            "player" => {
+                count(7)?;
+                game.players.push(Player {
+                    ship: Ship {
+                        pos_x: values[3],
+                        pos_y: values[4],
+                        shots: Vec::new(),
+                        flight: None,
+                    },
+                    score: unsigned(values[0])?,
+                    lives: unsigned(values[1])? as u32,
+                    start: values[2],
+                    multiplier: unsigned(values[5])?.max(1),
+                    combo_ticks: unsigned(values[6])?,
+                    stats: Stats::new(),
+                });
+            }
+            "stats" => {
+                let player = game
+                    .players
+                    .last_mut()
+                    .ok_or_else(|| error("stats before any player"))?;
+                let stats = &mut player.stats;
+                count(5 + stats.destroyed.len())?;
+                stats.shots = unsigned(values[0])?;
+                stats.hits = unsigned(values[1])?;
+                stats.rocks = unsigned(values[2])?;
+                stats.ticks = unsigned(values[3])?;
+                stats.best_multiplier = unsigned(values[4])?.max(1);
+                for (i, x) in values[5..].iter().enumerate() {
+                    stats.destroyed[i] = unsigned(*x)?;
+                }
+            }
+            "shot" => {
+                count(2)?;
+                let player = game
+                    .players
+                    .last_mut()
+                    .ok_or_else(|| error("shot before any player"))?;
+                player.ship.shots.push(Bullet {
+                    pos_x: values[0],
+                    pos_y: values[1],
+                });
+            }
+            "enemy" | "rock" => {
+                count(2)?;
+                let ship = Ship {
+                    pos_x: values[0],
+                    pos_y: values[1],
+                    shots: Vec::new(),
+                    flight: None,
+                };
+                if key == "enemy" {
+                    game.enemies.list.push(ship);
+                } else {
+                    game.enemies.rocks.push(ship);
+                }
+            }
+            "flight" => {
+                count(6)?;
+                let (kind, entry, script) = flight.unwrap();
+                let enemy = game
+                    .enemies
+                    .list
+                    .last_mut()
+                    .ok_or_else(|| error("flight before any enemy"))?;
+                enemy.flight = Some(Flight {
+                    kind,
+                    entry,
+                    slot: (values[0], values[1]),
+                    hold: unsigned(values[2])?,
+                    age: unsigned(values[3])?,
+                    diving: values[4] != 0,
+                    script,
+                    step: (0, 0),
+                    curve: None,
+                    hp: unsigned(values[5])? as u32,
+                });
+            }
+            "curve" => {
+                count(6)?;
+                let flight = game
+                    .enemies
+                    .list
+                    .last_mut()
+                    .and_then(|x| x.flight.as_mut())
+                    .ok_or_else(|| error("curve before any flight"))?;
+                flight.curve = Some([
+                    (values[0], values[1]),
+                    (values[2], values[3]),
+                    (values[4], values[5]),
+                ]);
+            }
+            "bolt" => {
+                count(2)?;
+                game.enemies.shots.push(Bullet {
+                    pos_x: values[0],
+                    pos_y: values[1],
+                });
+            }
+            _ => return Err(error(&format!("unknown '{}'", key))),
+        }
+    }
+
+    if game.players.is_empty() {
+        return Err(format!("{}: no players", name));
+    }
+    if !seen.0 || !seen.1 {
+        return Err(format!("{}: missing ticks or rng", name));
+    }
+    Ok(game)
+}
+
+pub fn save(game: &Game, path: &str) -> io::Result<()> {
+    let mut file = File::create(path)?;
+    file.write_all(to_string(game).as_bytes())
+}
+
+pub fn load(path: &str) -> Result<Game, String> {
+    let mut text = String::new();
+    File::open(path)
+        .and_then(|mut x| x.read_to_string(&mut text))
+        .map_err(|e| format!("{}: {}", path, e))?;
+    parse(&text, path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ai::Pilot;
+    use game::Action;
+
+    //Play a game with the pilot for a while, as something worth saving.
+    fn played(mut game: Game, ticks: u64) -> Game {
+        let mut pilots = [Pilot::flying(0), Pilot::flying(1)];
+        for _ in 0..ticks {
+            let actions: Vec<Vec<Action>> = (0..game.players.len())
+                .map(|x| vec![pilots[x].decide(&game)])
+                .collect();
+            game.advance(&actions);
+        }
+        game.clear_events();
+        game
+    }
+
+    #[test]
+    fn round_trip_plays_the_same() {
+        for game in [played(Game::seeded(3), 150), played(Game::coop(4), 300)].iter() {
+            let game = game.clone();
+            assert!(!game.over());
+            let loaded = parse(&to_string(&game), "test").unwrap();
+            assert_eq!(loaded, game);
+
+            //Both carry on the same way, random spawns and all.
+            let game = played(game, 200);
+            let loaded = played(loaded, 200);
+            assert_eq!(loaded, game);
+            assert_eq!(to_string(&loaded), to_string(&game));
+        }
+    }
+
+    #[test]
+    fn round_trip_through_a_file() {
+        let path = ::std::env::temp_dir().join("galaga-save-test.save");
+        let path = path.to_str().unwrap();
+        let game = played(Game::coop(9), 100);
+        save(&game, path).unwrap();
+        assert_eq!(load(path).unwrap(), game);
+        ::std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn errors_say_where() {
+        assert_eq!(
+            parse("galaga-save 99\n", "x").unwrap_err(),
+            "x:1: saved by a different version, expected 'galaga-save 1'"
+        );
+        assert_eq!(
+            parse("galaga-save 1\nticks 3\nrng 1\nplayer 0 1 10 10\n", "x").unwrap_err(),
+            "x:4: 'player' takes 7 numbers"
+        );
+        assert_eq!(
+            parse("galaga-save 1\nticks 3\nshot 1 2\n", "x").unwrap_err(),
+            "x:3: shot before any player"
+        );
+        //One count for each kind of enemy.
+        let stats = format!(
+            "galaga-save 1\nplayer 0 1 10 26 10 1 0\nstats 1 1 0 5 1{}\n",
+            " 0".repeat(Kind::ALL.len() - 1)
+        );
+        assert_eq!(
+            parse(&stats, "x").unwrap_err(),
+            format!("x:3: 'stats' takes {} numbers", 5 + Kind::ALL.len())
+        );
+        assert_eq!(
+            parse("galaga-save 1\nticks -3\n", "x").unwrap_err(),
+            "x:2: can't be negative"
+        );
+        assert_eq!(
+            parse("galaga-save 1\nticks 3\nrng 1\n", "x").unwrap_err(),
+            "x: no players"
+        );
+    }
+}
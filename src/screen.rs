@@ -0,0 +1,66 @@
+// Copyright © 2018 William Haugen - Piemyth
+// [This work is licensed under the "BSD 2-Clause License"]
+// Please see the file LICENSE in the source
+// distribution of this software for license terms.
+
+use glutin_window::GlutinWindow;
+use graphics::{self, Context, Transformed};
+use opengl_graphics::GlGraphics;
+use piston::input::RenderArgs;
+
+use {HEIGHT, WIDTH};
+
+//The playfield is always laid out in WIDTH x HEIGHT logical units.
+//These work out how to fit that into whatever size the window is,
+//keeping the aspect ratio and filling the rest with black bars.
+
+//Scale and offset of the playfield inside the window.
+fn fit(args: &RenderArgs) -> (f64, f64, f64) {
+    let scale = (args.width as f64 / WIDTH as f64).min(args.height as f64 / HEIGHT as f64);
+    let x = (args.width as f64 - WIDTH as f64 * scale) / 2.0;
+    let y = (args.height as f64 - HEIGHT as f64 * scale) / 2.0;
+    (scale, x, y)
+}
+
+//Turn a context for the whole window into one for the playfield,
+//so everything can keep drawing in logical coordinates.
+pub fn view(c: Context, args: &RenderArgs) -> Context {
+    let (scale, x, y) = fit(args);
+    c.trans(x, y).scale(scale, scale)
+}
+
+//Where a point in the window lands on the playfield, the other way from view.
+pub fn unview(position: [f64; 2], args: &RenderArgs) -> [f64; 2] {
+    let (scale, x, y) = fit(args);
+    [(position[0] - x) / scale, (position[1] - y) / scale]
+}
+
+//Cover anything drawn outside of the playfield.
+//Needs to be called last, with the untransformed context.
+pub fn letterbox(c: &Context, gl: &mut GlGraphics, args: &RenderArgs) {
+    let (_, x, y) = fit(args);
+    let black = [0.0, 0.0, 0.0, 1.0];
+    let (w, h) = (args.width as f64, args.height as f64);
+    let bars = [
+        [0.0, 0.0, w, y],
+        [0.0, h - y, w, y],
+        [0.0, 0.0, x, h],
+        [w - x, 0.0, x, h],
+    ];
+    for bar in bars.iter() {
+        if bar[2] > 0.0 && bar[3] > 0.0 {
+            graphics::rectangle(black, *bar, c.transform, gl);
+        }
+    }
+}
+
+//Switch between fullscreen on the current monitor and a window.
+pub fn toggle_fullscreen(window: &GlutinWindow, fullscreen: &mut bool) {
+    *fullscreen = !*fullscreen;
+    if *fullscreen {
+        let monitor = window.window.get_current_monitor();
+        window.window.set_fullscreen(Some(monitor));
+    } else {
+        window.window.set_fullscreen(None);
+    }
+}
@@ -0,0 +1,809 @@
+// Copyright © 2018 William Haugen - Piemyth
+// [This work is licensed under the "BSD 2-Clause License"]
+// Please see the file LICENSE in the source
+// distribution of this software for license terms.
+
+//Enemy behaviour written in little scripts instead of in the code, so
+//patterns can be tried out without recompiling. Each file in the scripts
+//folder named <name>.script is one script, and a wave picks one by name.
+//
+//A script runs once a tick for every enemy flying by it. It can read:
+//  x y          where the enemy is
+//  age          ticks since it came in
+//  tick         ticks since the game started
+//  px py        where the closest player is
+//  sx sy        the formation slot it was given
+//and give orders:
+//  move dx, dy              move up to a column and a row, more is cut down to 1
+//  fire                     drop a shot straight down
+//  spawn name               bring in another enemy flying by script name,
+//  spawn name x, y          where this one is or somewhere else
+//along with:
+//  let name = value         set a name for the rest of the run
+//  if value / else / end    anything but 0 is true
+//Values are whole numbers with + - * / % == != < > <= >= and or not,
+//brackets, and abs(a) sign(a) min(a, b) max(a, b) rand(n).
+//Anything after a # is a comment.
+//
+//There are no loops and nothing outside the enemy can be touched, so a
+//script always finishes quickly and the game plays out the same every time.
+//An enemy only follows its script for so long before it falls away, and
+//moving up from above the top of the screen takes it out of the game.
+
+use rand::Rng;
+use std::fs::{self, File};
+use std::io::{ErrorKind, Read};
+use std::path::Path;
+use std::time::SystemTime;
+
+use rng::GameRng;
+
+//Names every script can read, in the order they're stored.
+static BUILTINS: [&str; 8] = ["x", "y", "age", "tick", "px", "py", "sx", "sy"];
+//Most enemies one run can spawn, so they can't flood the screen in a tick.
+static MAXSPAWNS: usize = 4;
+
+//What an enemy knows about the game when its script runs.
+pub struct Context {
+    pub position: (i64, i64),
+    pub age: u64,
+    pub tick: u64,
+    pub player: (i64, i64),
+    pub slot: (i64, i64),
+}
+
+//What the script wants done this tick.
+#[derive(Debug, Default, PartialEq)]
+pub struct Orders {
+    pub step: (i64, i64),
+    pub fire: bool,
+    //Script and where to spawn it.
+    pub spawns: Vec<(String, i64, i64)>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Op {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+    Eq,
+    Ne,
+    Lt,
+    Gt,
+    Le,
+    Ge,
+    And,
+    Or,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Function {
+    Abs,
+    Sign,
+    Min,
+    Max,
+    Rand,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Expr {
+    Number(i64),
+    //Index into the values, builtins first then lets.
+    Variable(usize),
+    Negate(Box<Expr>),
+    Not(Box<Expr>),
+    Binary(Op, Box<Expr>, Box<Expr>),
+    Call(Function, Vec<Expr>),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Statement {
+    Let(usize, Expr),
+    If(Expr, Vec<Statement>, Vec<Statement>),
+    Move(Expr, Expr),
+    Fire,
+    //Script to spawn, where, and the line it's on for errors.
+    Spawn(String, Option<(Expr, Expr)>, usize),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Script {
+    pub name: String,
+    body: Vec<Statement>,
+    //How many names the script sets with let.
+    locals: usize,
+}
+
+//Every script that was in the folder.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Scripts {
+    list: Vec<Script>,
+}
+
+impl Scripts {
+    //Load every script in the folder. A missing folder just means no scripts.
+    //Errors say which file and line was wrong.
+    pub fn load(folder: &Path) -> Result<Scripts, String> {
+        let mut paths = Vec::new();
+        match fs::read_dir(folder) {
+            Ok(entries) => {
+                for x in entries {
+                    let path = x
+                        .map_err(|e| format!("{}: {}", folder.display(), e))?
+                        .path();
+                    if path.extension().and_then(|x| x.to_str()) == Some("script") {
+                        paths.push(path);
+                    }
+                }
+            }
+            Err(ref e) if e.kind() == ErrorKind::NotFound => {}
+            Err(e) => return Err(format!("{}: {}", folder.display(), e)),
+        }
+        //Always the same order, whatever order the folder lists them in.
+        paths.sort();
+
+        let mut scripts = Scripts::default();
+        let mut files = Vec::new();
+        for path in paths {
+            let mut text = String::new();
+            File::open(&path)
+                .and_then(|mut x| x.read_to_string(&mut text))
+                .map_err(|e| format!("{}: {}", path.display(), e))?;
+            let name = path
+                .file_stem()
+                .and_then(|x| x.to_str())
+                .unwrap_or("")
+                .to_string();
+            let file = path.display().to_string();
+            scripts.list.push(parse(&text, &name, &file)?);
+            files.push(file);
+        }
+
+        //Scripts can only spawn ones that are there.
+        for (script, file) in scripts.list.iter().zip(files.iter()) {
+            let mut spawns = Vec::new();
+            spawned(&script.body, &mut spawns);
+            for (name, line) in spawns {
+                if scripts.get(name).is_none() {
+                    return Err(format!("{}:{}: no script '{}' to spawn", file, line, name));
+                }
+            }
+        }
+        Ok(scripts)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Script> {
+        self.list.iter().find(|x| x.name == name)
+    }
+
+    //When anything in the folder last changed, to notice edits.
+    pub fn modified(folder: &Path) -> Option<SystemTime> {
+        let mut newest = fs::metadata(folder).and_then(|x| x.modified()).ok()?;
+        for x in fs::read_dir(folder).ok()? {
+            if let Some(time) = x
+                .ok()
+                .and_then(|x| x.metadata().ok())
+                .and_then(|x| x.modified().ok())
+            {
+                newest = newest.max(time);
+            }
+        }
+        Some(newest)
+    }
+}
+
+impl Script {
+    //Run the script for one enemy for one tick.
+    pub fn run(&self, context: &Context, rng: &mut GameRng) -> Orders {
+        let mut values = vec![0; BUILTINS.len() + self.locals];
+        values[..BUILTINS.len()].copy_from_slice(&[
+            context.position.0,
+            context.position.1,
+            context.age as i64,
+            context.tick as i64,
+            context.player.0,
+            context.player.1,
+            context.slot.0,
+            context.slot.1,
+        ]);
+
+        let mut orders = Orders::default();
+        execute(&self.body, &mut values, rng, &mut orders);
+        orders.step = (orders.step.0.signum(), orders.step.1.signum());
+        orders
+    }
+}
+
+fn execute(body: &[Statement], values: &mut [i64], rng: &mut GameRng, orders: &mut Orders) {
+    for x in body.iter() {
+        match x {
+            Statement::Let(index, value) => values[*index] = evaluate(value, values, rng),
+            Statement::If(test, then, otherwise) => {
+                if evaluate(test, values, rng) != 0 {
+                    execute(then, values, rng, orders);
+                } else {
+                    execute(otherwise, values, rng, orders);
+                }
+            }
+            Statement::Move(x, y) => {
+                let step = (evaluate(x, values, rng), evaluate(y, values, rng));
+                orders.step.0 = orders.step.0.wrapping_add(step.0);
+                orders.step.1 = orders.step.1.wrapping_add(step.1);
+            }
+            Statement::Fire => orders.fire = true,
+            Statement::Spawn(name, place, _) => {
+                let place = match place {
+                    Some((x, y)) => (evaluate(x, values, rng), evaluate(y, values, rng)),
+                    None => (values[0], values[1]),
+                };
+                if orders.spawns.len() < MAXSPAWNS {
+                    orders.spawns.push((name.clone(), place.0, place.1));
+                }
+            }
+        }
+    }
+}
+
+//Work out a value. Nothing here can fail, dividing by 0 gives 0
+//and numbers wrap instead of overflowing.
+fn evaluate(expr: &Expr, values: &[i64], rng: &mut GameRng) -> i64 {
+    match expr {
+        Expr::Number(x) => *x,
+        Expr::Variable(x) => values[*x],
+        Expr::Negate(x) => evaluate(x, values, rng).wrapping_neg(),
+        Expr::Not(x) => (evaluate(x, values, rng) == 0) as i64,
+        Expr::Binary(Op::And, a, b) => {
+            (evaluate(a, values, rng) != 0 && evaluate(b, values, rng) != 0) as i64
+        }
+        Expr::Binary(Op::Or, a, b) => {
+            (evaluate(a, values, rng) != 0 || evaluate(b, values, rng) != 0) as i64
+        }
+        Expr::Binary(op, a, b) => {
+            let a = evaluate(a, values, rng);
+            let b = evaluate(b, values, rng);
+            match op {
+                Op::Add => a.wrapping_add(b),
+                Op::Sub => a.wrapping_sub(b),
+                Op::Mul => a.wrapping_mul(b),
+                Op::Div => a.checked_div(b).unwrap_or(0),
+                Op::Rem => a.checked_rem(b).unwrap_or(0),
+                Op::Eq => (a == b) as i64,
+                Op::Ne => (a != b) as i64,
+                Op::Lt => (a < b) as i64,
+                Op::Gt => (a > b) as i64,
+                Op::Le => (a <= b) as i64,
+                Op::Ge => (a >= b) as i64,
+                Op::And | Op::Or => unreachable!(),
+            }
+        }
+        Expr::Call(function, args) => {
+            let args: Vec<i64> = args.iter().map(|x| evaluate(x, values, rng)).collect();
+            match function {
+                Function::Abs => args[0].wrapping_abs(),
+                Function::Sign => args[0].signum(),
+                Function::Min => args[0].min(args[1]),
+                Function::Max => args[0].max(args[1]),
+                Function::Rand if args[0] > 0 => rng.gen_range(0, args[0]),
+                Function::Rand => 0,
+            }
+        }
+    }
+}
+
+//Every script spawned anywhere in the body, with its line.
+fn spawned<'a>(body: &'a [Statement], list: &mut Vec<(&'a str, usize)>) {
+    for x in body.iter() {
+        match x {
+            Statement::If(_, then, otherwise) => {
+                spawned(then, list);
+                spawned(otherwise, list);
+            }
+            Statement::Spawn(name, _, line) => list.push((name, *line)),
+            _ => {}
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Number(i64),
+    Word(String),
+    Symbol(&'static str),
+}
+
+//Symbols longest first so <= isn't read as < then =.
+static SYMBOLS: [&str; 15] = [
+    "==", "!=", "<=", ">=", "<", ">", "+", "-", "*", "/", "%", "(", ")", ",", "=",
+];
+
+//Read the text of a script. Errors start with file and the line they're on.
+pub fn parse(text: &str, name: &str, file: &str) -> Result<Script, String> {
+    let mut lines = Vec::new();
+    for (number, line) in text.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or("");
+        let tokens = tokenize(line).map_err(|e| format!("{}:{}: {}", file, number + 1, e))?;
+        if !tokens.is_empty() {
+            lines.push((number + 1, tokens));
+        }
+    }
+
+    let mut parser = Parser {
+        lines,
+        line: 0,
+        tokens: Vec::new(),
+        at: 0,
+        locals: Vec::new(),
+    };
+    let body = match parser.block() {
+        Ok((body, None)) => body,
+        Ok((_, Some(x))) => {
+            return Err(format!(
+                "{}:{}: '{}' without an 'if'",
+                file,
+                parser.number(),
+                x
+            ))
+        }
+        Err(e) => return Err(format!("{}:{}: {}", file, parser.number(), e)),
+    };
+
+    Ok(Script {
+        name: name.to_string(),
+        body,
+        locals: parser.locals.len(),
+    })
+}
+
+fn tokenize(line: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut rest = line.trim_start();
+    while let Some(first) = rest.chars().next() {
+        let end = if first.is_ascii_digit() {
+            let end = rest
+                .find(|x: char| !x.is_ascii_digit())
+                .unwrap_or(rest.len());
+            let number = rest[..end]
+                .parse()
+                .map_err(|_| format!("'{}' is too big", &rest[..end]))?;
+            tokens.push(Token::Number(number));
+            end
+        } else if first.is_alphabetic() || first == '_' {
+            let end = rest
+                .find(|x: char| !(x.is_alphanumeric() || x == '_'))
+                .unwrap_or(rest.len());
+            tokens.push(Token::Word(rest[..end].to_string()));
+            end
+        } else {
+            match SYMBOLS.iter().find(|x| rest.starts_with(*x)) {
+                Some(x) => {
+                    tokens.push(Token::Symbol(x));
+                    x.len()
+                }
+                None => return Err(format!("unexpected '{}'", first)),
+            }
+        };
+        rest = rest[end..].trim_start();
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    lines: Vec<(usize, Vec<Token>)>,
+    //Which of the lines is being read.
+    line: usize,
+    tokens: Vec<Token>,
+    //Which of the line's tokens is next.
+    at: usize,
+    //Names set with let, stored after the builtins.
+    locals: Vec<String>,
+}
+
+impl Parser {
+    //Line number in the file of the line being read.
+    fn number(&self) -> usize {
+        match self.lines.get(self.line) {
+            Some(x) => x.0,
+            None => self.lines.last().map(|x| x.0).unwrap_or(1),
+        }
+    }
+
+    //Statements up to an else, end or the end of the file.
+    //Gives back which one stopped it.
+    fn block(&mut self) -> Result<(Vec<Statement>, Option<&'static str>), String> {
+        let mut body = Vec::new();
+        while self.line < self.lines.len() {
+            self.tokens = self.lines[self.line].1.clone();
+            self.at = 0;
+            let word = match self.next() {
+                Some(Token::Word(x)) => x,
+                _ => return Err("expected a command at the start of the line".to_string()),
+            };
+
+            let statement = match word.as_str() {
+                "else" | "end" => {
+                    self.finish()?;
+                    return Ok((body, Some(if word == "else" { "else" } else { "end" })));
+                }
+                "let" => {
+                    let name = match self.next() {
+                        Some(Token::Word(x)) => x,
+                        _ => return Err("expected a name after 'let'".to_string()),
+                    };
+                    if BUILTINS.contains(&name.as_str()) {
+                        return Err(format!("'{}' can't be changed", name));
+                    }
+                    self.expect("=")?;
+                    let value = self.expression()?;
+                    let index = match self.locals.iter().position(|x| *x == name) {
+                        Some(x) => x,
+                        None => {
+                            self.locals.push(name);
+                            self.locals.len() - 1
+                        }
+                    };
+                    Statement::Let(BUILTINS.len() + index, value)
+                }
+                "if" => {
+                    let test = self.expression()?;
+                    self.finish()?;
+                    let start = self.line;
+                    self.line += 1;
+                    let (then, stop) = self.block()?;
+                    let otherwise = if stop == Some("else") {
+                        self.line += 1;
+                        let (otherwise, stop) = self.block()?;
+                        if stop != Some("end") {
+                            self.line = start;
+                            return Err("'if' is missing its 'end'".to_string());
+                        }
+                        otherwise
+                    } else if stop == Some("end") {
+                        Vec::new()
+                    } else {
+                        self.line = start;
+                        return Err("'if' is missing its 'end'".to_string());
+                    };
+                    Statement::If(test, then, otherwise)
+                }
+                "move" => {
+                    let x = self.expression()?;
+                    self.expect(",")?;
+                    Statement::Move(x, self.expression()?)
+                }
+                "fire" => Statement::Fire,
+                "spawn" => {
+                    let name = match self.next() {
+                        Some(Token::Word(x)) => x,
+                        _ => return Err("expected a script name after 'spawn'".to_string()),
+                    };
+                    let place = if self.at < self.tokens.len() {
+                        let x = self.expression()?;
+                        self.expect(",")?;
+                        Some((x, self.expression()?))
+                    } else {
+                        None
+                    };
+                    Statement::Spawn(name, place, self.number())
+                }
+                x => return Err(format!("unknown command '{}'", x)),
+            };
+            self.finish()?;
+            body.push(statement);
+            self.line += 1;
+        }
+        Ok((body, None))
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.at).cloned();
+        self.at += 1;
+        token
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.at)
+    }
+
+    //Take the next token if it's the symbol or word.
+    fn take(&mut self, what: &str) -> bool {
+        let found = match self.peek() {
+            Some(Token::Symbol(x)) => *x == what,
+            Some(Token::Word(x)) => x == what,
+            _ => false,
+        };
+        if found {
+            self.at += 1;
+        }
+        found
+    }
+
+    fn expect(&mut self, what: &str) -> Result<(), String> {
+        if self.take(what) {
+            Ok(())
+        } else {
+            Err(format!("expected '{}'", what))
+        }
+    }
+
+    //Nothing should be left on the line.
+    fn finish(&self) -> Result<(), String> {
+        match self.peek() {
+            None => Ok(()),
+            Some(x) => Err(format!("unexpected {} at the end of the line", describe(x))),
+        }
+    }
+
+    fn expression(&mut self) -> Result<Expr, String> {
+        let mut left = self.and()?;
+        while self.take("or") {
+            left = Expr::Binary(Op::Or, Box::new(left), Box::new(self.and()?));
+        }
+        Ok(left)
+    }
+
+    fn and(&mut self) -> Result<Expr, String> {
+        let mut left = self.not()?;
+        while self.take("and") {
+            left = Expr::Binary(Op::And, Box::new(left), Box::new(self.not()?));
+        }
+        Ok(left)
+    }
+
+    fn not(&mut self) -> Result<Expr, String> {
+        if self.take("not") {
+            return Ok(Expr::Not(Box::new(self.not()?)));
+        }
+        self.compare()
+    }
+
+    fn compare(&mut self) -> Result<Expr, String> {
+        let left = self.sum()?;
+        let ops = [
+            ("==", Op::Eq),
+            ("!=", Op::Ne),
+            ("<=", Op::Le),
+            (">=", Op::Ge),
+            ("<", Op::Lt),
+            (">", Op::Gt),
+        ];
+        for (symbol, op) in ops.iter() {
+            if self.take(symbol) {
+                return Ok(Expr::Binary(*op, Box::new(left), Box::new(self.sum()?)));
+            }
+        }
+        Ok(left)
+    }
+
+    fn sum(&mut self) -> Result<Expr, String> {
+        let mut left = self.product()?;
+        loop {
+            let op = if self.take("+") {
+                Op::Add
+            } else if self.take("-") {
+                Op::Sub
+            } else {
+                return Ok(left);
+            };
+            left = Expr::Binary(op, Box::new(left), Box::new(self.product()?));
+        }
+    }
+
+    fn product(&mut self) -> Result<Expr, String> {
+        let mut left = self.unary()?;
+        loop {
+            let op = if self.take("*") {
+                Op::Mul
+            } else if self.take("/") {
+                Op::Div
+            } else if self.take("%") {
+                Op::Rem
+            } else {
+                return Ok(left);
+            };
+            left = Expr::Binary(op, Box::new(left), Box::new(self.unary()?));
+        }
+    }
+
+    fn unary(&mut self) -> Result<Expr, String> {
+        if self.take("-") {
+            return Ok(Expr::Negate(Box::new(self.unary()?)));
+        }
+        match self.next() {
+            Some(Token::Number(x)) => Ok(Expr::Number(x)),
+            Some(Token::Symbol("(")) => {
+                let inside = self.expression()?;
+                self.expect(")")?;
+                Ok(inside)
+            }
+            Some(Token::Word(ref x)) if self.peek() == Some(&Token::Symbol("(")) => self.call(x),
+            Some(Token::Word(x)) => match BUILTINS.iter().position(|y| *y == x) {
+                Some(index) => Ok(Expr::Variable(index)),
+                None => match self.locals.iter().position(|y| *y == x) {
+                    Some(index) => Ok(Expr::Variable(BUILTINS.len() + index)),
+                    None => Err(format!("'{}' hasn't been set", x)),
+                },
+            },
+            Some(x) => Err(format!("expected a value but found {}", describe(&x))),
+            None => Err("expected a value at the end of the line".to_string()),
+        }
+    }
+
+    fn call(&mut self, name: &str) -> Result<Expr, String> {
+        let (function, count) = match name {
+            "abs" => (Function::Abs, 1),
+            "sign" => (Function::Sign, 1),
+            "min" => (Function::Min, 2),
+            "max" => (Function::Max, 2),
+            "rand" => (Function::Rand, 1),
+            x => return Err(format!("no function '{}'", x)),
+        };
+        self.expect("(")?;
+        let mut args = vec![self.expression()?];
+        while self.take(",") {
+            args.push(self.expression()?);
+        }
+        self.expect(")")?;
+        if args.len() != count {
+            return Err(format!("'{}' takes {} values", name, count));
+        }
+        Ok(Expr::Call(function, args))
+    }
+}
+
+fn describe(token: &Token) -> String {
+    match token {
+        Token::Number(x) => format!("'{}'", x),
+        Token::Word(x) => format!("'{}'", x),
+        Token::Symbol(x) => format!("'{}'", x),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use game::Game;
+    use std::rc::Rc;
+    use waves;
+
+    //Load the scripts given as name and text from a folder of their own.
+    fn load(folder: &str, files: &[(&str, &str)]) -> Result<Scripts, String> {
+        let folder = ::std::env::temp_dir().join(folder);
+        fs::create_dir_all(&folder).unwrap();
+        for (name, text) in files.iter() {
+            fs::write(folder.join(format!("{}.script", name)), text).unwrap();
+        }
+        let scripts = Scripts::load(&folder);
+        fs::remove_dir_all(&folder).unwrap();
+        scripts
+    }
+
+    //Run a script once for an enemy at position.
+    fn run(text: &str, position: (i64, i64)) -> Orders {
+        let context = Context {
+            position,
+            age: 0,
+            tick: 0,
+            player: (10, 26),
+            slot: (5, 3),
+        };
+        parse(text, "test", "f")
+            .unwrap()
+            .run(&context, &mut GameRng::new(1))
+    }
+
+    //Work out each value, using where a spawn goes to see it.
+    fn values(list: &[&str]) -> Vec<i64> {
+        list.iter()
+            .map(|x| run(&format!("spawn a {}, 0\n", x), (0, 0)).spawns[0].1)
+            .collect()
+    }
+
+    #[test]
+    fn operators_take_precedence() {
+        assert_eq!(
+            values(&[
+                "1 + 2 * 3",
+                "10 - 4 - 3",
+                "(1 + 2) * 3",
+                "-2 * 3 + 1",
+                "7 % 4 * 2",
+                "12 / 2 / 3",
+                "1 + 1 == 2",
+                "not 1 == 2",
+                "0 and 0 or 1",
+                "1 or 0 and 0",
+                "2 > 1 and 3 <= 2",
+            ]),
+            vec![7, 3, 9, -5, 6, 2, 1, 1, 1, 1, 0]
+        );
+    }
+
+    #[test]
+    fn dividing_by_zero_gives_zero() {
+        assert_eq!(values(&["7 / 0", "7 % 0", "x / (y - y)"]), vec![0, 0, 0]);
+    }
+
+    #[test]
+    fn nested_ifs() {
+        let text = "\
+if x > 5
+  if y > 5
+    spawn a 1, 0
+  else
+    spawn a 2, 0
+  end
+else
+  spawn a 3, 0
+end
+";
+        let picked = |position| run(text, position).spawns[0].1;
+        assert_eq!(picked((9, 9)), 1);
+        assert_eq!(picked((9, 1)), 2);
+        assert_eq!(picked((1, 9)), 3);
+
+        assert_eq!(
+            parse("fire\nelse\n", "test", "f").unwrap_err(),
+            "f:2: 'else' without an 'if'"
+        );
+        assert_eq!(
+            parse("fire\nend\n", "test", "f").unwrap_err(),
+            "f:2: 'end' without an 'if'"
+        );
+        assert_eq!(
+            parse("fire\nif x\n  if y\n    fire\n  end\n", "test", "f").unwrap_err(),
+            "f:2: 'if' is missing its 'end'"
+        );
+        assert_eq!(
+            parse("if x\nelse\n  fire\n", "test", "f").unwrap_err(),
+            "f:1: 'if' is missing its 'end'"
+        );
+    }
+
+    #[test]
+    fn spawns_are_capped() {
+        let text = "spawn a\n".repeat(MAXSPAWNS + 3);
+        assert_eq!(run(&text, (4, 2)).spawns.len(), MAXSPAWNS);
+    }
+
+    #[test]
+    fn spawns_need_a_script() {
+        let scripts = load(
+            "galaga-script-test-spawn",
+            &[("a", "fire\nspawn b\n"), ("c", "spawn a\n")],
+        );
+        let error = scripts.unwrap_err();
+        assert!(
+            error.ends_with("a.script:2: no script 'b' to spawn"),
+            "{}",
+            error
+        );
+
+        let scripts = load("galaga-script-test-spawned", &[("a", "spawn a 1, 2\n")]).unwrap();
+        assert!(scripts.get("a").is_some());
+    }
+
+    #[test]
+    fn stubborn_enemies_dont_hold_up_the_stage() {
+        let scripts = load(
+            "galaga-script-test-stubborn",
+            &[
+                ("stay", "move sx - x, sy - y\n"),
+                ("up", "move 0, 1 - 2 * (age / 8)\n"),
+            ],
+        )
+        .unwrap();
+        let text = "[[stage]]\n\n\
+                    [[stage.wave]]\nat = 0\nscript = \"stay\"\nslots = [[3, 3], [16, 3]]\n\n\
+                    [[stage.wave]]\nat = 0\nscript = \"up\"\nslots = [[5, 3]]\n";
+        let waves = Rc::new(waves::parse(text, "x", scripts).unwrap());
+        let mut game = Game::seeded(1).with_waves(&waves);
+        let mut ticks = 0;
+        while game.stage == 0 {
+            assert!(!game.update());
+            ticks += 1;
+            assert!(ticks < 6 * 30, "the stage never ended");
+        }
+    }
+}
@@ -0,0 +1,542 @@
+// Copyright © 2018 William Haugen - Piemyth
+// [This work is licensed under the "BSD 2-Clause License"]
+// Please see the file LICENSE in the source
+// distribution of this software for license terms.
+
+use graphics::math::Matrix2d;
+use graphics::{self, Image};
+use image::{self, imageops, GenericImage, RgbaImage};
+use opengl_graphics::{GlGraphics, Texture, TextureSettings};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Read;
+use std::path::PathBuf;
+
+use assets::locate;
+
+//Width of the atlas texture, frames are packed into rows of this width.
+static ATLASWIDTH: u32 = 512;
+//Empty pixels between frames so filtering doesn't bleed neighbours in.
+static PADDING: u32 = 1;
+//Size of the squares drawn in place of images that can't be loaded.
+static PLACEHOLDERSIZE: u32 = 16;
+//Colours placeholders are picked from, so different sprites tell apart.
+static PLACEHOLDERS: [[u8; 3]; 6] = [
+    [255, 0, 255],
+    [0, 255, 255],
+    [255, 255, 0],
+    [255, 128, 0],
+    [128, 255, 0],
+    [160, 160, 255],
+];
+
+struct Frame {
+    rect: [f64; 4],
+    duration: f64,
+}
+
+//Named sequence of frames in the atlas.
+pub struct Animation {
+    frames: Vec<Frame>,
+    looping: bool,
+    length: f64,
+}
+
+//All the sprites in one texture, along with the animations cut out of it.
+pub struct Atlas {
+    texture: Texture,
+    animations: HashMap<String, Animation>,
+    //Images that couldn't be loaded and are drawn as placeholders instead.
+    pub problems: Vec<String>,
+}
+
+//One frame as written in the definitions file, before packing.
+#[derive(Debug)]
+struct FrameDef {
+    file: String,
+    column: (u32, u32),
+    transforms: Vec<String>,
+    duration: f64,
+}
+
+#[derive(Debug)]
+struct AnimationDef {
+    name: String,
+    looping: bool,
+    frames: Vec<FrameDef>,
+}
+
+impl Animation {
+    //Find which frame to show after time seconds of playing.
+    //Non looping animations hold on their last frame.
+    fn frame(&self, time: f64) -> &Frame {
+        let mut time = if self.looping && self.length > 0.0 {
+            time % self.length
+        } else {
+            time
+        };
+
+        for x in self.frames.iter() {
+            if time < x.duration {
+                return x;
+            }
+            time -= x.duration;
+        }
+        &self.frames[self.frames.len() - 1]
+    }
+}
+
+impl Atlas {
+    //Read the animation definitions, load every image they use
+    //and pack them all into a single texture. Images that are missing or
+    //broken get a coloured square instead, listed in problems, only
+    //mistakes in the definitions themselves are an error. Every file is
+    //taken from the first of the folders that has it.
+    pub fn load(folders: &[PathBuf], definitions: &str) -> Result<Atlas, String> {
+        let path = locate(folders, definitions);
+        let mut text = String::new();
+        File::open(&path)
+            .and_then(|mut x| x.read_to_string(&mut text))
+            .map_err(|e| format!("{}: {}", path.display(), e))?;
+        let defs = parse(&text).map_err(|e| format!("{}:{}", path.display(), e))?;
+
+        //Cut out and transform every frame before packing.
+        let mut sources: HashMap<String, RgbaImage> = HashMap::new();
+        let mut frames: Vec<RgbaImage> = Vec::new();
+        let mut problems = Vec::new();
+        for def in defs.iter() {
+            for frame in def.frames.iter() {
+                if !sources.contains_key(&frame.file) {
+                    let file = locate(folders, &frame.file);
+                    let img = match image::open(&file) {
+                        Ok(x) => x.to_rgba(),
+                        Err(e) => {
+                            problems.push(format!("{}: {}", file.display(), e));
+                            placeholder(&def.name, frame.column.1)
+                        }
+                    };
+                    sources.insert(frame.file.clone(), img);
+                }
+                let sheet = sources.get_mut(&frame.file).unwrap();
+                frames.push(cut(sheet, frame)?);
+            }
+        }
+
+        let mut atlas = Atlas::pack(defs, &frames);
+        atlas.problems = problems;
+        Ok(atlas)
+    }
+
+    //Only placeholders, for when the definitions can't be loaded at all.
+    //Each name gets a square that plays once, so explosions still finish.
+    pub fn placeholders(names: &[&str]) -> Atlas {
+        let defs: Vec<AnimationDef> = names
+            .iter()
+            .map(|x| AnimationDef {
+                name: x.to_string(),
+                looping: false,
+                frames: vec![FrameDef {
+                    file: x.to_string(),
+                    column: (0, 1),
+                    transforms: Vec::new(),
+                    duration: 0.5,
+                }],
+            })
+            .collect();
+        let frames: Vec<RgbaImage> = names.iter().map(|x| placeholder(x, 1)).collect();
+        Atlas::pack(defs, &frames)
+    }
+
+    //Pack the frames of every animation into one texture.
+    fn pack(defs: Vec<AnimationDef>, frames: &[RgbaImage]) -> Atlas {
+        let (atlas, rects) = pack(frames);
+        let texture = Texture::from_image(&atlas, &TextureSettings::new());
+
+        let mut animations = HashMap::new();
+        let mut rects = rects.into_iter();
+        for def in defs {
+            let frames: Vec<Frame> = def
+                .frames
+                .iter()
+                .map(|x| Frame {
+                    rect: rects.next().unwrap(),
+                    duration: x.duration,
+                })
+                .collect();
+            let length = frames.iter().map(|x| x.duration).sum();
+            animations.insert(
+                def.name,
+                Animation {
+                    frames,
+                    looping: def.looping,
+                    length,
+                },
+            );
+        }
+
+        Atlas {
+            texture,
+            animations,
+            problems: Vec::new(),
+        }
+    }
+
+    //Draw the frame of an animation that is showing after time seconds,
+    //stretched over rect. Unknown names draw nothing.
+    pub fn draw(
+        &self,
+        name: &str,
+        time: f64,
+        rect: [f64; 4],
+        transform: Matrix2d,
+        gl: &mut GlGraphics,
+    ) {
+        if let Some(animation) = self.animations.get(name) {
+            let frame = animation.frame(time);
+            let draw_state = graphics::DrawState::new_alpha();
+            Image::new().src_rect(frame.rect).rect(rect).draw(
+                &self.texture,
+                &draw_state,
+                transform,
+                gl,
+            );
+        }
+    }
+
+    //Check if a non looping animation has played all the way through.
+    pub fn finished(&self, name: &str, time: f64) -> bool {
+        match self.animations.get(name) {
+            Some(x) => !x.looping && time >= x.length,
+            None => true,
+        }
+    }
+}
+
+//Parse the definitions file into animations.
+//Errors start with the line number they were found on.
+fn parse(text: &str) -> Result<Vec<AnimationDef>, String> {
+    let mut defs: Vec<AnimationDef> = Vec::new();
+
+    for (number, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let error = |x: String| format!("{}: {}", number + 1, x);
+
+        let words: Vec<&str> = line.split_whitespace().collect();
+        if words.len() < 4 {
+            return Err(error(
+                "expected a name, loop or once, frame time and at least one frame".to_string(),
+            ));
+        }
+
+        let looping = match words[1] {
+            "loop" => true,
+            "once" => false,
+            x => return Err(error(format!("unknown mode '{}'", x))),
+        };
+        let duration = parse_ms(words[2]).map_err(&error)?;
+
+        let mut frames = Vec::new();
+        for word in words[3..].iter() {
+            frames.push(parse_frame(word, duration).map_err(&error)?);
+        }
+
+        if defs.iter().any(|x| x.name == words[0]) {
+            return Err(error(format!("'{}' is defined twice", words[0])));
+        }
+        defs.push(AnimationDef {
+            name: words[0].to_string(),
+            looping,
+            frames,
+        });
+    }
+
+    Ok(defs)
+}
+
+//Parse a frame in the form file#i/n:transform@ms.
+fn parse_frame(word: &str, duration: f64) -> Result<FrameDef, String> {
+    let mut parts = word.splitn(2, '@');
+    let word = parts.next().unwrap_or("");
+    let duration = match parts.next() {
+        Some(x) => parse_ms(x)?,
+        None => duration,
+    };
+
+    let mut parts = word.split(':');
+    let source = parts.next().unwrap_or("");
+    let transforms: Vec<String> = parts.map(|x| x.to_string()).collect();
+    for x in transforms.iter() {
+        match x.as_str() {
+            "rot90" | "rot180" | "rot270" | "flip" | "squash" => {}
+            x if x.starts_with("tint=") && parse_tint(x).is_some() => {}
+            _ => return Err(format!("unknown transform '{}' in '{}'", x, word)),
+        }
+    }
+
+    let mut parts = source.splitn(2, '#');
+    let file = parts.next().unwrap_or("").to_string();
+    let column = match parts.next() {
+        Some(x) => {
+            let bad = || format!("expected #index/count in '{}'", word);
+            let mut nums = x.splitn(2, '/');
+            let index: u32 = nums.next().and_then(|x| x.parse().ok()).ok_or_else(bad)?;
+            let count: u32 = nums.next().and_then(|x| x.parse().ok()).ok_or_else(bad)?;
+            if count == 0 || index >= count {
+                return Err(format!("frame {} is outside a sheet of {}", index, count));
+            }
+            (index, count)
+        }
+        None => (0, 1),
+    };
+    if file.is_empty() {
+        return Err(format!("missing file name in '{}'", word));
+    }
+
+    Ok(FrameDef {
+        file,
+        column,
+        transforms,
+        duration,
+    })
+}
+
+//Milliseconds in the file, seconds in the game.
+fn parse_ms(word: &str) -> Result<f64, String> {
+    match word.parse::<u32>() {
+        Ok(x) if x > 0 => Ok(x as f64 / 1000.0),
+        _ => Err(format!("'{}' is not a frame time in ms", word)),
+    }
+}
+
+//Solid square standing in for a sheet of columns frames, coloured by the
+//name of the animation that needed it.
+fn placeholder(name: &str, columns: u32) -> RgbaImage {
+    let sum = name.bytes().fold(0usize, |x, y| x + y as usize);
+    let color = PLACEHOLDERS[sum % PLACEHOLDERS.len()];
+    RgbaImage::from_pixel(
+        PLACEHOLDERSIZE * columns,
+        PLACEHOLDERSIZE,
+        image::Rgba([color[0], color[1], color[2], 255]),
+    )
+}
+
+//Cut a frame out of its sheet and apply its transforms. The sheet is only
+//borrowed mutably because that's what cropping takes, it isn't changed.
+fn cut(sheet: &mut RgbaImage, def: &FrameDef) -> Result<RgbaImage, String> {
+    let width = sheet.width() / def.column.1;
+    if width == 0 {
+        return Err(format!(
+            "{} is too small for {} frames",
+            def.file, def.column.1
+        ));
+    }
+    let height = sheet.height();
+    let mut frame = imageops::crop(sheet, width * def.column.0, 0, width, height).to_image();
+
+    for x in def.transforms.iter() {
+        frame = match x.as_str() {
+            "rot90" => imageops::rotate90(&frame),
+            "rot180" => imageops::rotate180(&frame),
+            "rot270" => imageops::rotate270(&frame),
+            "flip" => imageops::flip_horizontal(&frame),
+            "squash" => squash(&frame),
+            x => tint(&frame, parse_tint(x).unwrap()),
+        };
+    }
+    Ok(frame)
+}
+
+//Colour out of a tint=RRGGBB transform.
+fn parse_tint(word: &str) -> Option<[u8; 3]> {
+    let hex = word.trim_start_matches("tint=");
+    if hex.len() != 6 {
+        return None;
+    }
+    let mut color = [0u8; 3];
+    for (i, x) in color.iter_mut().enumerate() {
+        *x = u8::from_str_radix(hex.get(i * 2..i * 2 + 2)?, 16).ok()?;
+    }
+    Some(color)
+}
+
+//Multiply every pixel by a colour, so white parts take on the tint.
+fn tint(frame: &RgbaImage, color: [u8; 3]) -> RgbaImage {
+    let mut out = frame.clone();
+    for pixel in out.pixels_mut() {
+        for (x, tint) in pixel.data.iter_mut().zip(color.iter()) {
+            *x = (*x as u32 * *tint as u32 / 255) as u8;
+        }
+    }
+    out
+}
+
+//Squeeze an image horizontally, keeping it centered and the same size.
+fn squash(frame: &RgbaImage) -> RgbaImage {
+    let width = (frame.width() * 7 / 10).max(1);
+    let thin = imageops::resize(frame, width, frame.height(), imageops::FilterType::Triangle);
+    let mut out = RgbaImage::new(frame.width(), frame.height());
+    out.copy_from(&thin, (frame.width() - width) / 2, 0);
+    out
+}
+
+//Place frames left to right in rows, starting a new row when one fills up.
+//Returns the atlas and the source rectangle of each frame in order.
+fn pack(frames: &[RgbaImage]) -> (RgbaImage, Vec<[f64; 4]>) {
+    let mut rects = Vec::new();
+    let mut places = Vec::new();
+    let (mut x, mut y, mut row) = (PADDING, PADDING, 0);
+    let mut width = ATLASWIDTH;
+
+    for frame in frames.iter() {
+        width = width.max(frame.width() + PADDING * 2);
+        if x + frame.width() + PADDING > width {
+            x = PADDING;
+            y += row + PADDING;
+            row = 0;
+        }
+        places.push((x, y));
+        rects.push([
+            x as f64,
+            y as f64,
+            frame.width() as f64,
+            frame.height() as f64,
+        ]);
+        x += frame.width() + PADDING;
+        row = row.max(frame.height());
+    }
+
+    let mut atlas = RgbaImage::new(width, y + row + PADDING);
+    for (frame, place) in frames.iter().zip(places) {
+        atlas.copy_from(frame, place.0, place.1);
+    }
+    (atlas, rects)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_frames() {
+        let frame = parse_frame("explosion.png#2/5:rot90:tint=FF8800@40", 0.1).unwrap();
+        assert_eq!(frame.file, "explosion.png");
+        assert_eq!(frame.column, (2, 5));
+        assert_eq!(frame.transforms, vec!["rot90", "tint=FF8800"]);
+        assert_eq!(frame.duration, 0.04);
+
+        let frame = parse_frame("ship.png", 0.1).unwrap();
+        assert_eq!(frame.column, (0, 1));
+        assert!(frame.transforms.is_empty());
+        assert_eq!(frame.duration, 0.1);
+
+        assert_eq!(
+            parse_frame("ship.png:spin", 0.1).unwrap_err(),
+            "unknown transform 'spin' in 'ship.png:spin'"
+        );
+        assert_eq!(
+            parse_frame("ship.png:tint=FF88", 0.1).unwrap_err(),
+            "unknown transform 'tint=FF88' in 'ship.png:tint=FF88'"
+        );
+        assert_eq!(
+            parse_frame("sheet.png#3/3", 0.1).unwrap_err(),
+            "frame 3 is outside a sheet of 3"
+        );
+        assert_eq!(
+            parse_frame("sheet.png#a/3", 0.1).unwrap_err(),
+            "expected #index/count in 'sheet.png#a/3'"
+        );
+        assert_eq!(
+            parse_frame("#0/1", 0.1).unwrap_err(),
+            "missing file name in '#0/1'"
+        );
+        assert_eq!(
+            parse_frame("ship.png@0", 0.1).unwrap_err(),
+            "'0' is not a frame time in ms"
+        );
+    }
+
+    #[test]
+    fn reads_definitions() {
+        let text = "\
+#A comment, then a blank line
+
+ship   loop 100 ship.png
+boom   once 50  boom.png#0/2 boom.png#1/2@200
+";
+        let defs = parse(text).unwrap();
+        assert_eq!(defs.len(), 2);
+        assert_eq!(defs[0].name, "ship");
+        assert!(defs[0].looping);
+        assert_eq!(defs[1].name, "boom");
+        assert!(!defs[1].looping);
+        let durations: Vec<f64> = defs[1].frames.iter().map(|x| x.duration).collect();
+        assert_eq!(durations, vec![0.05, 0.2]);
+
+        assert_eq!(
+            parse("ship loop 100\n").unwrap_err(),
+            "1: expected a name, loop or once, frame time and at least one frame"
+        );
+        assert_eq!(
+            parse("\nship forever 100 ship.png\n").unwrap_err(),
+            "2: unknown mode 'forever'"
+        );
+        assert_eq!(
+            parse("ship loop 100 ship.png\nship once 100 ship.png\n").unwrap_err(),
+            "2: 'ship' is defined twice"
+        );
+        assert_eq!(
+            parse("ship loop 100 ship.png:spin\n").unwrap_err(),
+            "1: unknown transform 'spin' in 'ship.png:spin'"
+        );
+    }
+
+    #[test]
+    fn cuts_columns() {
+        //Three columns of 2 pixels, each a different colour.
+        let mut sheet = RgbaImage::from_fn(6, 2, |x, _| image::Rgba([x as u8 / 2, 0, 0, 255]));
+        let def = parse_frame("sheet.png#1/3", 0.1).unwrap();
+        let frame = cut(&mut sheet, &def).unwrap();
+        assert_eq!(frame.dimensions(), (2, 2));
+        assert!(frame.pixels().all(|x| x.data[0] == 1));
+
+        let def = parse_frame("sheet.png#0/7", 0.1).unwrap();
+        assert_eq!(
+            cut(&mut sheet, &def).unwrap_err(),
+            "sheet.png is too small for 7 frames"
+        );
+    }
+
+    #[test]
+    fn packs_without_overlap() {
+        let frames: Vec<RgbaImage> = [(100, 20), (300, 40), (200, 10), (600, 5), (16, 16)]
+            .iter()
+            .map(|x| RgbaImage::new(x.0, x.1))
+            .collect();
+        let (atlas, rects) = pack(&frames);
+        assert_eq!(rects.len(), frames.len());
+        //Wide enough for the widest frame, which gets a row to itself.
+        assert_eq!(atlas.width(), 600 + PADDING * 2);
+
+        for (i, a) in rects.iter().enumerate() {
+            assert_eq!(
+                (a[2] as u32, a[3] as u32),
+                frames[i].dimensions(),
+                "frame {} changed size",
+                i
+            );
+            assert!(a[0] >= PADDING as f64 && a[1] >= PADDING as f64);
+            assert!(a[0] + a[2] + PADDING as f64 <= atlas.width() as f64);
+            assert!(a[1] + a[3] + PADDING as f64 <= atlas.height() as f64);
+            for b in rects[i + 1..].iter() {
+                let apart = a[0] + a[2] + PADDING as f64 <= b[0]
+                    || b[0] + b[2] + PADDING as f64 <= a[0]
+                    || a[1] + a[3] + PADDING as f64 <= b[1]
+                    || b[1] + b[3] + PADDING as f64 <= a[1];
+                assert!(apart, "{:?} and {:?} overlap", a, b);
+            }
+        }
+    }
+}
@@ -0,0 +1,147 @@
+// Copyright © 2018 William Haugen - Piemyth
+// [This work is licensed under the "BSD 2-Clause License"]
+// Please see the file LICENSE in the source
+// distribution of this software for license terms.
+
+use graphics::{self, Context, Image};
+use opengl_graphics::{GlGraphics, Texture};
+use rand::{self, Rng};
+
+use {HEIGHT, WIDTH};
+
+//How fast the background image scrolls in pixels per second.
+//Star layers scroll faster the closer they are.
+static IMAGESPEED: f64 = 10.0;
+//How many times faster than normal everything moves at full warp.
+static WARPSPEED: f64 = 12.0;
+
+struct Layer {
+    stars: Vec<(f64, f64)>,
+    speed: f64,
+    size: f64,
+    brightness: f32,
+}
+
+//Vertically scrolling background made of an optional image
+//and several layers of stars moving at different speeds.
+pub struct Starfield {
+    layers: Vec<Layer>,
+    //How far the image layer has scrolled.
+    offset: f64,
+    //Current speed multiplier and the one it's easing towards.
+    speed: f64,
+    target: f64,
+    //Seconds of warp left.
+    warp: f64,
+    //Everything's scroll speed is multiplied by this, from the config.
+    pub scroll: f64,
+    //Colour of the stars, their brightness is taken out of it.
+    pub tint: [f32; 4],
+}
+
+impl Layer {
+    fn new(count: usize, speed: f64, size: f64, brightness: f32) -> Layer {
+        let mut rng = rand::thread_rng();
+        let stars = (0..count)
+            .map(|_| {
+                (
+                    rng.gen_range(0.0, WIDTH as f64),
+                    rng.gen_range(0.0, HEIGHT as f64),
+                )
+            })
+            .collect();
+
+        Layer {
+            stars,
+            speed,
+            size,
+            brightness,
+        }
+    }
+}
+
+impl Default for Starfield {
+    fn default() -> Starfield {
+        Starfield::new()
+    }
+}
+
+impl Starfield {
+    pub fn new() -> Starfield {
+        Starfield {
+            layers: vec![
+                Layer::new(60, 20.0, 1.0, 0.4),
+                Layer::new(35, 45.0, 2.0, 0.7),
+                Layer::new(15, 90.0, 3.0, 1.0),
+            ],
+            offset: 0.0,
+            speed: 1.0,
+            target: 1.0,
+            warp: 0.0,
+            scroll: 1.0,
+            tint: [1.0; 4],
+        }
+    }
+
+    //Jump to warp speed for a few seconds, used between stages.
+    pub fn warp(&mut self, seconds: f64) {
+        self.warp = seconds;
+        self.target = 1.0;
+    }
+
+    //Ease the scroll speed towards speed, where 1.0 is normal.
+    pub fn set_speed(&mut self, speed: f64) {
+        self.target = speed;
+    }
+
+    //Scroll everything down by dt seconds worth of movement.
+    pub fn update(&mut self, dt: f64) {
+        let target = if self.warp > 0.0 {
+            self.warp -= dt;
+            WARPSPEED
+        } else {
+            self.target
+        };
+        //Ease in and out of warp instead of snapping.
+        self.speed += (target - self.speed) * (dt * 3.0).min(1.0);
+
+        let speed = self.speed * self.scroll;
+        self.offset = (self.offset + IMAGESPEED * speed * dt) % HEIGHT as f64;
+        for layer in self.layers.iter_mut() {
+            let step = layer.speed * speed * dt;
+            for x in layer.stars.iter_mut() {
+                x.1 += step;
+                //Wrap stars back to the top when they leave the bottom.
+                if x.1 > HEIGHT as f64 {
+                    x.1 -= HEIGHT as f64;
+                    x.0 = rand::thread_rng().gen_range(0.0, WIDTH as f64);
+                }
+            }
+        }
+    }
+
+    //Draw the image layer if there is one, then the stars on top.
+    //Stars stretch into streaks the faster they go.
+    pub fn draw(&self, c: &Context, gl: &mut GlGraphics, image: Option<&Texture>) {
+        if let Some(texture) = image {
+            //Two copies stacked so the seam is never on screen.
+            for x in 0..2 {
+                let y = self.offset - (x * HEIGHT) as f64;
+                let rect = [0.0, y, WIDTH as f64, HEIGHT as f64];
+                let draw_state = graphics::DrawState::new_alpha();
+                Image::new()
+                    .rect(rect)
+                    .draw(texture, &draw_state, c.transform, gl);
+            }
+        }
+
+        for layer in self.layers.iter() {
+            let color = [self.tint[0], self.tint[1], self.tint[2], layer.brightness];
+            let length = layer.size.max(layer.speed * (self.speed - 1.0) * 0.05);
+            for x in layer.stars.iter() {
+                let rect = [x.0, x.1 - length, layer.size, length];
+                graphics::rectangle(color, rect, c.transform, gl);
+            }
+        }
+    }
+}
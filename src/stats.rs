@@ -0,0 +1,305 @@
+// Copyright © 2018 William Haugen - Piemyth
+// [This work is licensed under the "BSD 2-Clause License"]
+// Please see the file LICENSE in the source
+// distribution of this software for license terms.
+
+//How each player did over a game, shown on the results screen once it's
+//over and written out as JSON so sessions can be looked at afterwards.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use events::Event;
+use game::Game;
+use waves::Kind;
+
+//Game ticks in a second.
+static TICKSPERSECOND: u64 = 6;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Stats {
+    //Shots fired and how many of them hit something.
+    pub shots: u64,
+    pub hits: u64,
+    //Enemies brought down of each kind, in the order of Kind::ALL.
+    pub destroyed: [u64; Kind::ALL.len()],
+    pub rocks: u64,
+    //Ticks the player was still in the game for.
+    pub ticks: u64,
+    //Highest combo multiplier reached.
+    pub best_multiplier: u64,
+}
+
+impl Default for Stats {
+    fn default() -> Stats {
+        Stats::new()
+    }
+}
+
+impl Stats {
+    pub fn new() -> Stats {
+        Stats {
+            shots: 0,
+            hits: 0,
+            destroyed: [0; Kind::ALL.len()],
+            rocks: 0,
+            ticks: 0,
+            best_multiplier: 1,
+        }
+    }
+
+    //Count an enemy of the kind being brought down.
+    pub fn destroy(&mut self, kind: Kind) {
+        self.destroyed[index(kind)] += 1;
+    }
+
+    //Count whatever one of the player's events says they did.
+    pub fn count(&mut self, event: &Event) {
+        match *event {
+            Event::ShotFired { .. } => self.shots += 1,
+            Event::EnemyDestroyed {
+                kind, multiplier, ..
+            } => {
+                self.hits += 1;
+                self.destroy(kind);
+                self.best_multiplier = self.best_multiplier.max(multiplier);
+            }
+            Event::EnemyDamaged { .. } => self.hits += 1,
+            Event::RockHit { .. } => {
+                self.hits += 1;
+                self.rocks += 1;
+            }
+            _ => {}
+        }
+    }
+
+    //Enemies of the kind brought down.
+    pub fn destroyed_of(&self, kind: Kind) -> u64 {
+        self.destroyed[index(kind)]
+    }
+
+    //Enemies brought down of every kind.
+    pub fn kills(&self) -> u64 {
+        self.destroyed.iter().sum()
+    }
+
+    //Percentage of shots that hit something, 0 without any shots.
+    pub fn accuracy(&self) -> f64 {
+        if self.shots == 0 {
+            0.0
+        } else {
+            self.hits as f64 * 100.0 / self.shots as f64
+        }
+    }
+
+    //Time survived as minutes and seconds.
+    pub fn time(&self) -> String {
+        let seconds = self.ticks / TICKSPERSECOND;
+        format!("{}:{:02}", seconds / 60, seconds % 60)
+    }
+
+    //Lines for the results screen.
+    pub fn lines(&self) -> Vec<String> {
+        let mut lines = vec![
+            format!(
+                "Accuracy {:.1}%  ({} of {} shots)",
+                self.accuracy(),
+                self.hits,
+                self.shots
+            ),
+            format!(
+                "Survived {}  Best combo x{}",
+                self.time(),
+                self.best_multiplier
+            ),
+            format!("Enemies {}  Rocks hit {}", self.kills(), self.rocks),
+        ];
+        let kinds: Vec<String> = Kind::ALL
+            .iter()
+            .zip(self.destroyed.iter())
+            .filter(|x| *x.1 > 0)
+            .map(|(kind, count)| format!("{} {}", kind.name(), count))
+            .collect();
+        if !kinds.is_empty() {
+            lines.push(kinds.join("  "));
+        }
+        lines
+    }
+
+    fn to_json(&self, score: u64) -> String {
+        let destroyed: Vec<String> = Kind::ALL
+            .iter()
+            .zip(self.destroyed.iter())
+            .map(|(kind, count)| format!("\"{}\": {}", kind.name(), count))
+            .collect();
+        format!(
+            "{{\"score\": {}, \"shots\": {}, \"hits\": {}, \"accuracy\": {:.2}, \
+             \"destroyed\": {{{}}}, \"rocks\": {}, \"ticks\": {}, \"seconds\": {}, \
+             \"best_multiplier\": {}}}",
+            score,
+            self.shots,
+            self.hits,
+            self.accuracy(),
+            destroyed.join(", "),
+            self.rocks,
+            self.ticks,
+            self.ticks / TICKSPERSECOND,
+            self.best_multiplier
+        )
+    }
+}
+
+fn index(kind: Kind) -> usize {
+    Kind::ALL.iter().position(|x| *x == kind).unwrap_or(0)
+}
+
+//A finished game as JSON, each of games holding one or more players.
+//Taking turns, each player has a game of their own.
+pub fn to_json(games: &[&Game]) -> String {
+    let time = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|x| x.as_secs())
+        .unwrap_or(0);
+    json(games, time)
+}
+
+//The JSON for a game finished at time, in seconds since the epoch.
+fn json(games: &[&Game], time: u64) -> String {
+    let players: Vec<String> = games
+        .iter()
+        .flat_map(|x| x.players.iter())
+        .map(|x| format!("    {}", x.stats.to_json(x.score)))
+        .collect();
+    let ticks = games.iter().map(|x| x.ticks).max().unwrap_or(0);
+    let stage = games.iter().map(|x| x.stage).max().unwrap_or(0);
+    format!(
+        "{{\n  \"time\": {},\n  \"ticks\": {},\n  \"stage\": {},\n  \"players\": [\n{}\n  ]\n}}\n",
+        time,
+        ticks,
+        stage + 1,
+        players.join(",\n")
+    )
+}
+
+//Write a finished game out to a file of its own in folder, named by when
+//it was written. Gives the file written.
+pub fn write(folder: &Path, games: &[&Game]) -> io::Result<PathBuf> {
+    fs::create_dir_all(folder)?;
+    let time = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|x| x.as_secs())
+        .unwrap_or(0);
+    let mut path = folder.join(format!("{}.json", time));
+    let mut count = 1;
+    while path.exists() {
+        count += 1;
+        path = folder.join(format!("{}-{}.json", time, count));
+    }
+    fs::write(&path, json(games, time))?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accuracy_and_time() {
+        let mut stats = Stats::new();
+        assert_eq!(stats.accuracy(), 0.0);
+        assert_eq!(stats.time(), "0:00");
+        stats.shots = 8;
+        stats.hits = 6;
+        stats.ticks = TICKSPERSECOND * 75 + 1;
+        assert_eq!(stats.accuracy(), 75.0);
+        assert_eq!(stats.time(), "1:15");
+    }
+
+    #[test]
+    fn counts_come_from_events() {
+        let mut stats = Stats::new();
+        let kill = |kind, multiplier| Event::EnemyDestroyed {
+            player: 0,
+            kind,
+            pos: (0, 0),
+            points: 10,
+            multiplier,
+        };
+        let events = [
+            Event::ShotFired {
+                player: 0,
+                pos: (0, 0),
+            },
+            kill(Kind::Bee, 1),
+            kill(Kind::Bee, 3),
+            kill(Kind::Boss, 2),
+            Event::EnemyDamaged {
+                player: 0,
+                kind: Kind::Boss,
+                pos: (0, 0),
+            },
+            Event::RockHit {
+                player: 0,
+                pos: (0, 0),
+                points: 10,
+            },
+            Event::EnemyCrashed {
+                player: 0,
+                kind: Kind::Drone,
+                pos: (0, 0),
+            },
+        ];
+        for x in events.iter() {
+            stats.count(x);
+        }
+        assert_eq!(stats.shots, 1);
+        assert_eq!(stats.hits, 5);
+        assert_eq!(stats.rocks, 1);
+        assert_eq!(stats.destroyed_of(Kind::Bee), 2);
+        assert_eq!(stats.destroyed_of(Kind::Boss), 1);
+        //Crashing into the player doesn't count as brought down.
+        assert_eq!(stats.destroyed_of(Kind::Drone), 0);
+        assert_eq!(stats.destroyed, [0, 2, 0, 1, 0, 0]);
+        assert_eq!(stats.kills(), 3);
+        assert_eq!(stats.best_multiplier, 3);
+    }
+
+    #[test]
+    fn writes_json() {
+        let mut game = Game::coop(1);
+        game.ticks = 130;
+        game.stage = 2;
+        game.players[0].score = 500;
+        game.players[0].stats.shots = 3;
+        game.players[0].stats.hits = 2;
+        game.players[0].stats.destroy(Kind::Butterfly);
+        game.players[0].stats.ticks = 130;
+        game.players[0].stats.best_multiplier = 2;
+        game.players[1].stats.rocks = 1;
+        game.players[1].stats.ticks = 64;
+
+        let folder = ::std::env::temp_dir().join("galaga-stats-test");
+        let _ = fs::remove_dir_all(&folder);
+        let path = write(&folder, &[&game]).unwrap();
+        let time = path.file_stem().unwrap().to_str().unwrap();
+        let text = fs::read_to_string(&path).unwrap();
+        fs::remove_dir_all(&folder).unwrap();
+
+        let destroyed = "\"destroyed\": {\"drone\": 0, \"bee\": 0, \"butterfly\": 1, \
+                         \"boss\": 0, \"kamikaze\": 0, \"zigzagger\": 0}";
+        let nothing = "\"destroyed\": {\"drone\": 0, \"bee\": 0, \"butterfly\": 0, \
+                       \"boss\": 0, \"kamikaze\": 0, \"zigzagger\": 0}";
+        let expected = format!(
+            "{{\n  \"time\": {},\n  \"ticks\": 130,\n  \"stage\": 3,\n  \"players\": [\n    \
+             {{\"score\": 500, \"shots\": 3, \"hits\": 2, \"accuracy\": 66.67, {}, \
+             \"rocks\": 0, \"ticks\": 130, \"seconds\": 21, \"best_multiplier\": 2}},\n    \
+             {{\"score\": 0, \"shots\": 0, \"hits\": 0, \"accuracy\": 0.00, {}, \
+             \"rocks\": 1, \"ticks\": 64, \"seconds\": 10, \"best_multiplier\": 1}}\n  \
+             ]\n}}\n",
+            time, destroyed, nothing
+        );
+        assert_eq!(text, expected);
+    }
+}
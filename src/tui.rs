@@ -0,0 +1,275 @@
+// Copyright © 2018 William Haugen - Piemyth
+// [This work is licensed under the "BSD 2-Clause License"]
+// Please see the file LICENSE in the source
+// distribution of this software for license terms.
+
+//Plays the game in a terminal instead of a window, with each square of
+//the grid drawn as two characters. Handy over ssh or on machines without
+//a GPU, and keeps the game itself honest about not depending on OpenGL.
+
+use piston::input::{Button, ButtonState, Key};
+use std::io::{self, Read, Write};
+use std::rc::Rc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use controller::{Controller, Keyboard};
+use events::Event;
+use game::Game;
+use waves::{Kind, Waves};
+use {GRIDSIZE, HEIGHT, WIDTH};
+
+//Same speed as the window's ups of 6.
+static TICKMS: u64 = 1000 / 6;
+
+enum Input {
+    Press(Button),
+    Quit,
+}
+
+//Puts the terminal into raw mode and puts it back when dropped,
+//even if the game panics.
+#[cfg(unix)]
+struct Terminal {
+    original: ::libc::termios,
+}
+
+#[cfg(unix)]
+impl Terminal {
+    fn new() -> io::Result<Terminal> {
+        use libc;
+        use std::mem;
+
+        let original = unsafe {
+            let mut original: libc::termios = mem::zeroed();
+            if libc::tcgetattr(libc::STDIN_FILENO, &mut original) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+
+            //No line buffering or echo, and reads return right away
+            //whether or not a key has been pressed.
+            let mut raw = original;
+            raw.c_lflag &= !(libc::ICANON | libc::ECHO | libc::ISIG);
+            raw.c_cc[libc::VMIN] = 0;
+            raw.c_cc[libc::VTIME] = 0;
+            if libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &raw) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+            original
+        };
+
+        //Switch to the alternate screen and hide the cursor.
+        print!("\x1b[?1049h\x1b[?25l");
+        io::stdout().flush()?;
+        Ok(Terminal { original })
+    }
+}
+
+#[cfg(unix)]
+impl Drop for Terminal {
+    fn drop(&mut self) {
+        unsafe {
+            ::libc::tcsetattr(::libc::STDIN_FILENO, ::libc::TCSANOW, &self.original);
+        }
+        print!("\x1b[?25h\x1b[?1049l");
+        let _ = io::stdout().flush();
+    }
+}
+
+//Run the game in the terminal until the player quits with 'q' or Esc,
+//playing through the same stages as the window.
+#[cfg(unix)]
+pub fn run(waves: &Rc<Waves>) {
+    let _terminal = match Terminal::new() {
+        Ok(x) => x,
+        Err(e) => {
+            eprintln!("Could not set up the terminal: {}", e);
+            return;
+        }
+    };
+
+    let mut game = Game::new().with_waves(waves);
+    let mut keyboard = Keyboard::arrows();
+    let mut game_over = false;
+    let mut last_tick = Instant::now();
+    let tick = Duration::from_millis(TICKMS);
+    let mut stdin = io::stdin();
+    let mut buffer = [0u8; 64];
+    let mut dirty = true;
+
+    loop {
+        //Listen for some key presses
+        let read = stdin.read(&mut buffer).unwrap_or(0);
+        for input in parse(&buffer[..read]) {
+            match input {
+                Input::Quit => return,
+                //If a user pushes r, reset the game
+                Input::Press(Button::Keyboard(Key::R)) => {
+                    game.restart();
+                    game_over = false;
+                    dirty = true;
+                }
+                Input::Press(btn) => keyboard.button(&btn, ButtonState::Press),
+            }
+        }
+
+        for action in keyboard.poll(&game) {
+            //If a user collides with a block, game over
+            if game.act(0, action) {
+                game_over = true;
+            }
+            dirty = true;
+        }
+
+        //Update the game data if the game over conditions haven't occured.
+        if last_tick.elapsed() >= tick {
+            last_tick += tick;
+            if !game_over {
+                game_over = game.update();
+            }
+            dirty = true;
+        }
+
+        if dirty {
+            draw(&mut game, game_over);
+            dirty = false;
+        }
+        thread::sleep(Duration::from_millis(5));
+    }
+}
+
+#[cfg(not(unix))]
+pub fn run(_waves: &Rc<Waves>) {
+    eprintln!("The terminal renderer is only supported on unix systems.");
+}
+
+//Turn raw bytes from the terminal into key presses.
+//Arrow keys come in as escape sequences, a lone Esc quits.
+fn parse(bytes: &[u8]) -> Vec<Input> {
+    let mut inputs = Vec::new();
+    let mut index = 0;
+    while index < bytes.len() {
+        let key = match bytes[index] {
+            0x1b if bytes.len() > index + 2 && bytes[index + 1] == b'[' => {
+                index += 2;
+                match bytes[index] {
+                    b'A' => Some(Key::Up),
+                    b'B' => Some(Key::Down),
+                    b'C' => Some(Key::Right),
+                    b'D' => Some(Key::Left),
+                    _ => None,
+                }
+            }
+            //Esc, q or ctrl-c
+            0x1b | b'q' | b'Q' | 3 => {
+                inputs.push(Input::Quit);
+                None
+            }
+            b'z' | b'Z' | b' ' => Some(Key::Z),
+            b'r' | b'R' => Some(Key::R),
+            _ => None,
+        };
+        if let Some(x) = key {
+            inputs.push(Input::Press(Button::Keyboard(x)));
+        }
+        index += 1;
+    }
+    inputs
+}
+
+//Draw the whole playfield in one write so it doesn't flicker.
+fn draw(game: &mut Game, game_over: bool) {
+    let columns = (WIDTH / GRIDSIZE) as usize;
+    let rows = (HEIGHT / GRIDSIZE) as usize;
+    let mut grid: Vec<Vec<&str>> = vec![vec!["  "; columns]; rows];
+
+    {
+        let mut place = |pos: (i64, i64), cell| {
+            if pos.0 >= 0 && pos.1 >= 0 && (pos.0 as usize) < columns && (pos.1 as usize) < rows {
+                grid[pos.1 as usize][pos.0 as usize] = cell;
+            }
+        };
+
+        for x in game.enemies.current_rock_pos() {
+            place(x, "\x1b[33m()");
+        }
+        //Each kind of enemy in its own colour, the same as its sprite.
+        for x in game.enemies.list.iter() {
+            let cell = match x.flight.as_ref().map(|y| y.kind) {
+                Some(Kind::Bee) => "\x1b[93m\\/",
+                Some(Kind::Butterfly) => "\x1b[95m\\/",
+                Some(Kind::Boss) => "\x1b[92mVV",
+                Some(Kind::Kamikaze) => "\x1b[33m/\\",
+                Some(Kind::Zigzagger) => "\x1b[96m\\/",
+                _ => "\x1b[31m\\/",
+            };
+            place((x.pos_x, x.pos_y), cell);
+        }
+        for x in game.players.iter() {
+            for y in x.ship.shots.iter() {
+                place(y.get_pos(), "\x1b[93m||");
+            }
+        }
+        for x in game.enemies.shots.iter() {
+            place(x.get_pos(), "\x1b[91m!!");
+        }
+        //Ships destroyed since the last tick.
+        for x in game.events.iter() {
+            match *x {
+                Event::EnemyDestroyed { pos, .. } | Event::EnemyCrashed { pos, .. } => {
+                    place(pos, "\x1b[91m**")
+                }
+                _ => {}
+            }
+        }
+        for x in game.players.iter_mut() {
+            if x.alive() {
+                place(x.ship.current_pos(), "\x1b[96m/\\");
+            } else {
+                place(x.ship.current_pos(), "\x1b[91m##");
+            }
+        }
+    }
+    //The terminal has nothing to animate, so the events are done with here.
+    game.clear_events();
+
+    let mut frame = String::from("\x1b[H");
+    let border = format!("+{}+\n", "-".repeat(columns * 2));
+    frame.push_str(&border);
+    //How the game went, beside the grid under the restart prompt.
+    let results = if game_over {
+        game.players[0].stats.lines()
+    } else {
+        Vec::new()
+    };
+    for (index, row) in grid.iter().enumerate() {
+        frame.push('|');
+        for cell in row.iter() {
+            frame.push_str(cell);
+            if *cell != "  " {
+                frame.push_str("\x1b[0m");
+            }
+        }
+        frame.push('|');
+        if game_over && index == rows / 2 - 1 {
+            frame.push_str("  GAME OVER");
+        }
+        if game_over && index == rows / 2 {
+            frame.push_str("  Press 'R' To Restart");
+        }
+        if let Some(x) = index.checked_sub(rows / 2 + 2).and_then(|x| results.get(x)) {
+            frame.push_str("  ");
+            frame.push_str(x);
+        }
+        frame.push_str("\x1b[K\n");
+    }
+    frame.push_str(&border);
+    frame.push_str(&game.score());
+    frame.push_str("  ");
+    frame.push_str(&game.combo());
+    frame.push_str("\x1b[K\nArrows move, Z shoots, R restarts, Q quits\x1b[K");
+
+    let mut stdout = io::stdout();
+    let _ = stdout.write_all(frame.as_bytes());
+    let _ = stdout.flush();
+}
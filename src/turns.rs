@@ -0,0 +1,188 @@
+// Copyright © 2018 William Haugen - Piemyth
+// [This work is licensed under the "BSD 2-Clause License"]
+// Please see the file LICENSE in the source
+// distribution of this software for license terms.
+
+//Arcade style two players taking turns. Each player has a whole game of
+//their own, and whenever the one playing loses a ship their game gets put
+//away and the other player's game picks up right where it was left.
+
+use rand::RngCore;
+use std::mem;
+
+use game::Game;
+
+//How long the ready card shows before play starts, in game ticks.
+static READYTICKS: u64 = 6 * 2;
+
+pub struct Turns {
+    //Game of whoever isn't playing right now, exactly as they left it.
+    pub waiting: Game,
+    //Which player is playing, counting from 0.
+    pub player: usize,
+    //Ticks left showing the ready card, nothing moves until it's gone.
+    pub ready: u64,
+    //Lives the player had when their turn started, to notice one being lost.
+    lives: u32,
+}
+
+impl Turns {
+    //Set up turns with game as player 1's, player 2 gets a game of their own
+    //seeded from it so the same first game always gives the same second one.
+    //Both play through the same stages.
+    pub fn new(game: &mut Game) -> Turns {
+        let seed = game.rng.next_u64();
+        Turns {
+            waiting: Game::turns(seed).with_waves(&game.waves),
+            player: 0,
+            ready: READYTICKS,
+            lives: game.players[0].lives,
+        }
+    }
+
+    //Check if the player just lost a ship, and if so hand over to the other
+    //player as long as they still have ships left.
+    pub fn update(&mut self, game: &mut Game) {
+        let lives = game.players[0].lives;
+        if lives >= self.lives {
+            return;
+        }
+        self.lives = lives;
+        if self.waiting.over() {
+            //Nobody to hand over to, keep going until this game is over too.
+            if lives > 0 {
+                self.ready = READYTICKS;
+            }
+            return;
+        }
+
+        //What happened as the ship was lost still goes out this time round,
+        //not whenever this game comes back.
+        let events = game.take_events();
+        mem::swap(game, &mut self.waiting);
+        game.events = events;
+        self.player = 1 - self.player;
+        self.lives = game.players[0].lives;
+        self.ready = READYTICKS;
+    }
+
+    //Count down the ready card, returns true while it's still showing.
+    pub fn wait(&mut self) -> bool {
+        if self.ready > 0 {
+            self.ready -= 1;
+        }
+        self.ready > 0
+    }
+
+    //Both players are out of ships.
+    pub fn over(&self, game: &Game) -> bool {
+        game.over() && self.waiting.over()
+    }
+
+    //Text for the ready card, if it's showing.
+    pub fn card(&self) -> Option<String> {
+        if self.ready > 0 {
+            Some(format!("PLAYER {} READY", self.player + 1))
+        } else {
+            None
+        }
+    }
+
+    //Both players' scores and ships left, player 1 first.
+    pub fn score(&self, game: &Game) -> String {
+        let scores: Vec<String> = self
+            .games(game)
+            .iter()
+            .enumerate()
+            .map(|(i, x)| format!("P{}: {} x{}", i + 1, x.players[0].score, x.players[0].lives))
+            .collect();
+        scores.join("  ")
+    }
+
+    //Both players' games, player 1's first, given whoever's is being played.
+    pub fn games<'a>(&'a self, game: &'a Game) -> [&'a Game; 2] {
+        if self.player == 1 {
+            [&self.waiting, game]
+        } else {
+            [game, &self.waiting]
+        }
+    }
+
+    //Start both games over from the beginning with player 1 up.
+    pub fn restart(&mut self, game: &mut Game) {
+        if self.player == 1 {
+            mem::swap(game, &mut self.waiting);
+            self.player = 0;
+        }
+        game.restart();
+        self.waiting.restart();
+        self.lives = game.players[0].lives;
+        self.ready = READYTICKS;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use events::Event;
+
+    //Take a ship off whoever is playing, the way getting hit would.
+    fn lose(game: &mut Game) {
+        let pos = game.players[0].ship.current_pos();
+        game.players[0].lives -= 1;
+        game.events.push(Event::PlayerHit {
+            player: 0,
+            pos,
+            lives: game.players[0].lives,
+        });
+    }
+
+    #[test]
+    fn losing_a_ship_hands_over() {
+        let mut game = Game::turns(1);
+        let mut turns = Turns::new(&mut game);
+        while turns.wait() {}
+        for _ in 0..20 {
+            game.update();
+        }
+        game.clear_events();
+        let first = game.clone();
+        let second = turns.waiting.clone();
+
+        //Nothing happens until a ship is lost.
+        turns.update(&mut game);
+        assert_eq!(turns.player, 0);
+
+        lose(&mut game);
+        turns.update(&mut game);
+        assert_eq!(turns.player, 1);
+        assert_eq!(turns.card(), Some("PLAYER 2 READY".to_string()));
+        assert_eq!(game.checksum(), second.checksum());
+        assert_eq!(turns.waiting.players[0].lives, first.players[0].lives - 1);
+        assert_eq!(turns.games(&game)[0].ticks, first.ticks);
+
+        //The ship lost still gets shown now, not when player 1 comes back.
+        assert_eq!(game.events.len(), 1);
+        assert!(turns.waiting.events.is_empty());
+    }
+
+    #[test]
+    fn over_once_both_are_out() {
+        let mut game = Game::turns(2);
+        let mut turns = Turns::new(&mut game);
+
+        //Each lost ship hands over, until player 1 is out for good.
+        while !turns.waiting.over() {
+            lose(&mut game);
+            turns.update(&mut game);
+            assert!(!turns.over(&game));
+        }
+        assert_eq!(turns.player, 1);
+        assert_eq!(game.players[0].lives, 1);
+
+        //Then player 2 plays on alone.
+        lose(&mut game);
+        turns.update(&mut game);
+        assert!(turns.over(&game));
+    }
+}
@@ -0,0 +1,53 @@
+// Copyright © 2018 William Haugen - Piemyth
+// [This work is licensed under the "BSD 2-Clause License"]
+// Please see the file LICENSE in the source
+// distribution of this software for license terms.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+//Notices files in some folders being changed or added, by checking when
+//each was last modified. Folders inside them aren't looked in.
+pub struct Watch {
+    folders: Vec<PathBuf>,
+    times: HashMap<PathBuf, SystemTime>,
+}
+
+impl Watch {
+    //Start watching from how the folders are now.
+    pub fn new(folders: &[PathBuf]) -> Watch {
+        let mut watch = Watch {
+            folders: folders.to_vec(),
+            times: HashMap::new(),
+        };
+        watch.changed();
+        watch
+    }
+
+    //Files changed since the last call, in name order.
+    pub fn changed(&mut self) -> Vec<PathBuf> {
+        let mut changed = Vec::new();
+        let entries = self
+            .folders
+            .iter()
+            .filter_map(|x| fs::read_dir(x).ok())
+            .flat_map(|x| x.filter_map(|x| x.ok()));
+        for x in entries {
+            let time = match x.metadata() {
+                Ok(ref y) if y.is_file() => y.modified().ok(),
+                _ => None,
+            };
+            if let Some(time) = time {
+                let path = x.path();
+                if self.times.get(&path) != Some(&time) {
+                    self.times.insert(path.clone(), time);
+                    changed.push(path);
+                }
+            }
+        }
+        changed.sort();
+        changed
+    }
+}
@@ -0,0 +1,897 @@
+// Copyright © 2018 William Haugen - Piemyth
+// [This work is licensed under the "BSD 2-Clause License"]
+// Please see the file LICENSE in the source
+// distribution of this software for license terms.
+
+//Stages of enemies and rocks written out in a file instead of in the code.
+//Each stage lists waves of enemies, when they come in, how they get there
+//and which formation slot each one flies to, along with any rock showers.
+//Once every stage has been cleared the game carries on endlessly the way it
+//always has.
+//
+//The file is a small piece of TOML, one key per line:
+//  [[stage]]
+//  name = "Opening"
+//
+//  [[stage.wave]]
+//  at = 0                      tick into the stage the first one comes in
+//  every = 2                   ticks between each one after that, default 0
+//  kind = "bee"                drone, bee, butterfly, boss, kamikaze or
+//                              zigzagger, default drone
+//  path = "swoop"              straight, swoop, zigzag or curve, default straight
+//  slots = [[5, 3], [7, 3]]    column and row each one flies to
+//  hold = 20                   ticks they sit there before diving, default 0
+//  script = "dive"             fly by a script instead, see script.rs
+//  curve = [[1, -1], [18, 4], [2, 8]]
+//                              start and two control points for path = "curve"
+//
+//  [[stage.rocks]]
+//  at = 40
+//  every = 3                   default 0
+//  count = 4
+//  column = 9                  default is to aim at a player
+//
+//Anything after a # outside a string is a comment. A quote or backslash
+//inside a string needs a backslash in front of it.
+//
+//Scripts come from the scripts folder next to the wave file.
+
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use script::Scripts;
+use {GRIDSIZE, HEIGHT, POINTS, WIDTH};
+
+//Columns the ships can be in, the same as the player.
+static COLUMNS: (i64, i64) = (1, WIDTH / GRIDSIZE - 2);
+//Rows a slot can be in, anything lower and the enemy would be gone.
+static ROWS: (i64, i64) = (0, HEIGHT / GRIDSIZE - 5);
+//Where the points of a curve can be, the edges and just above the top.
+static CURVE: ((i64, i64), (i64, i64)) = ((0, WIDTH / GRIDSIZE - 1), (-1, HEIGHT / GRIDSIZE - 5));
+
+//What a wave's enemies are.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Kind {
+    //Follows its path and never fires.
+    Drone,
+    //Fires once as it starts to dive.
+    Bee,
+    //Takes two hits, drifts after the nearest player when it dives.
+    Butterfly,
+    //Takes three hits and fires the whole time, but dives slowly.
+    Boss,
+    //Never waits in formation, heads straight for the nearest player.
+    Kamikaze,
+    //Weaves side to side all the way down, firing as it goes.
+    Zigzagger,
+}
+
+//How a wave's enemies get to their slots.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Entry {
+    //Straight down the slot's column.
+    Straight,
+    //In from the far side of the screen and across.
+    Swoop,
+    //Down the slot's column, then weaving side to side once it dives.
+    Zigzag,
+    //Along a Bezier curve from its start, bent by two control points.
+    Curve,
+}
+
+impl Kind {
+    pub const ALL: [Kind; 6] = [
+        Kind::Drone,
+        Kind::Bee,
+        Kind::Butterfly,
+        Kind::Boss,
+        Kind::Kamikaze,
+        Kind::Zigzagger,
+    ];
+
+    //Name used for the kind in wave and save files.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Kind::Drone => "drone",
+            Kind::Bee => "bee",
+            Kind::Butterfly => "butterfly",
+            Kind::Boss => "boss",
+            Kind::Kamikaze => "kamikaze",
+            Kind::Zigzagger => "zigzagger",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Kind> {
+        Kind::ALL.iter().find(|x| x.name() == name).cloned()
+    }
+
+    //Animation the kind is drawn with.
+    pub fn sprite(&self) -> &'static str {
+        match self {
+            Kind::Drone => "enemy",
+            Kind::Bee => "bee",
+            Kind::Butterfly => "butterfly",
+            Kind::Boss => "boss",
+            Kind::Kamikaze => "kamikaze",
+            Kind::Zigzagger => "zigzagger",
+        }
+    }
+
+    //Shots it takes to bring down.
+    pub fn hp(&self) -> u32 {
+        match self {
+            Kind::Butterfly => 2,
+            Kind::Boss => 3,
+            _ => 1,
+        }
+    }
+
+    //Points for bringing one down, double while it's diving.
+    pub fn points(&self, diving: bool) -> u64 {
+        let points = match self {
+            Kind::Drone => POINTS,
+            Kind::Bee => 50,
+            Kind::Butterfly => 80,
+            Kind::Boss => 150,
+            Kind::Kamikaze => 60,
+            Kind::Zigzagger => 70,
+        };
+        if diving {
+            points * 2
+        } else {
+            points
+        }
+    }
+
+    //Whether it fires on a tick, given its age and if it's diving. Dove is
+    //true on the tick it leaves the formation.
+    pub fn fires(&self, age: u64, diving: bool, dove: bool) -> bool {
+        match self {
+            Kind::Drone | Kind::Kamikaze => false,
+            Kind::Bee => dove,
+            Kind::Butterfly => diving && age.is_multiple_of(6),
+            Kind::Boss => age.is_multiple_of(5),
+            Kind::Zigzagger => diving && age.is_multiple_of(4),
+        }
+    }
+}
+
+impl Entry {
+    pub const ALL: [Entry; 4] = [Entry::Straight, Entry::Swoop, Entry::Zigzag, Entry::Curve];
+
+    //Name used for the path in wave and save files.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Entry::Straight => "straight",
+            Entry::Swoop => "swoop",
+            Entry::Zigzag => "zigzag",
+            Entry::Curve => "curve",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Entry> {
+        Entry::ALL.iter().find(|x| x.name() == name).cloned()
+    }
+}
+
+//Enemies that come in together, one for each slot.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Wave {
+    pub at: u64,
+    pub every: u64,
+    pub kind: Kind,
+    pub entry: Entry,
+    pub slots: Vec<(i64, i64)>,
+    pub hold: u64,
+    //Script the enemies fly by, path then only decides where they come in.
+    pub script: Option<String>,
+    //Start and two control points of a curve path, it ends at each slot.
+    pub curve: Option<[(i64, i64); 3]>,
+}
+
+//Rocks dropped one after another.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Shower {
+    pub at: u64,
+    pub every: u64,
+    pub count: u64,
+    //Column they fall down, or None to go for a player.
+    pub column: Option<i64>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Stage {
+    pub name: String,
+    pub waves: Vec<Wave>,
+    pub rocks: Vec<Shower>,
+}
+
+//Every stage in the order they're played, and the scripts they use.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Waves {
+    pub stages: Vec<Stage>,
+    pub scripts: Scripts,
+}
+
+impl Wave {
+    //Tick into the stage the slot's enemy comes in.
+    pub fn time(&self, slot: usize) -> u64 {
+        self.at + slot as u64 * self.every
+    }
+}
+
+impl Shower {
+    //Tick into the stage the rock comes in.
+    pub fn time(&self, rock: u64) -> u64 {
+        self.at + rock * self.every
+    }
+}
+
+//Where something flying a curve should be after a number of ticks, the
+//curve ends at end. Ticks are spread over how far it is around the control
+//points so it never has to move more than a square a tick to keep up.
+pub fn curve_point(curve: &[(i64, i64); 3], end: (i64, i64), tick: u64) -> (i64, i64) {
+    let points = [curve[0], curve[1], curve[2], end];
+    let length: i64 = points
+        .windows(2)
+        .map(|x| (x[1].0 - x[0].0).abs().max((x[1].1 - x[0].1).abs()))
+        .sum();
+    if tick as i64 >= length {
+        return end;
+    }
+
+    //Cubic Bezier in whole numbers, t is tick out of length.
+    let (t, n) = (tick as i64, length);
+    let weights = [
+        (n - t).pow(3),
+        3 * (n - t).pow(2) * t,
+        3 * (n - t) * t * t,
+        t.pow(3),
+    ];
+    let total = n.pow(3);
+    let mut point = (0, 0);
+    for (p, w) in points.iter().zip(weights.iter()) {
+        point.0 += p.0 * w;
+        point.1 += p.1 * w;
+    }
+    //Round to the closest square.
+    let round = |x: i64| (2 * x + total).div_euclid(2 * total);
+    (round(point.0), round(point.1))
+}
+
+impl Stage {
+    //Tick the last enemy or rock of the stage comes in.
+    pub fn last(&self) -> u64 {
+        let waves = self.waves.iter().map(|x| x.time(x.slots.len() - 1));
+        let rocks = self.rocks.iter().map(|x| x.time(x.count - 1));
+        waves.chain(rocks).max().unwrap_or(0)
+    }
+}
+
+impl Waves {
+    //No stages at all, straight into endless play.
+    pub fn endless() -> Waves {
+        Waves {
+            stages: Vec::new(),
+            scripts: Scripts::default(),
+        }
+    }
+
+    //Load the wave file along with the scripts folder next to it.
+    pub fn load(path: &Path) -> Result<Waves, String> {
+        let scripts = Scripts::load(&Waves::scripts_folder(path))?;
+        let mut text = String::new();
+        File::open(path)
+            .and_then(|mut x| x.read_to_string(&mut text))
+            .map_err(|e| format!("{}: {}", path.display(), e))?;
+        parse(&text, &path.display().to_string(), scripts)
+    }
+
+    //Where the scripts for the wave file at path are.
+    pub fn scripts_folder(path: &Path) -> PathBuf {
+        path.with_file_name("scripts")
+    }
+
+    //The same stages with the scripts read in again, as long as every
+    //script the stages use is still there.
+    pub fn reload_scripts(&self, path: &Path) -> Result<Waves, String> {
+        let folder = Waves::scripts_folder(path);
+        let scripts = Scripts::load(&folder)?;
+        for stage in self.stages.iter() {
+            for name in stage.waves.iter().filter_map(|x| x.script.as_ref()) {
+                if scripts.get(name).is_none() {
+                    return Err(format!(
+                        "{}: '{}' still uses script '{}'",
+                        folder.display(),
+                        stage.name,
+                        name
+                    ));
+                }
+            }
+        }
+        Ok(Waves {
+            stages: self.stages.clone(),
+            scripts,
+        })
+    }
+}
+
+//The stages written out as a wave file, anything left at its default is
+//left out. Reading it back with parse gives the same stages.
+pub fn to_string(waves: &Waves) -> String {
+    let pairs = |list: &[(i64, i64)]| {
+        let list: Vec<String> = list.iter().map(|x| format!("[{}, {}]", x.0, x.1)).collect();
+        format!("[{}]", list.join(", "))
+    };
+
+    let mut text = String::new();
+    for stage in waves.stages.iter() {
+        text.push_str(&format!("[[stage]]\nname = {}\n", quote(&stage.name)));
+        for x in stage.waves.iter() {
+            text.push_str(&format!("\n[[stage.wave]]\nat = {}\n", x.at));
+            if x.every != 0 {
+                text.push_str(&format!("every = {}\n", x.every));
+            }
+            if x.kind != Kind::Drone {
+                text.push_str(&format!("kind = \"{}\"\n", x.kind.name()));
+            }
+            if x.entry != Entry::Straight {
+                text.push_str(&format!("path = \"{}\"\n", x.entry.name()));
+            }
+            if let Some(ref y) = x.curve {
+                text.push_str(&format!("curve = {}\n", pairs(&y[..])));
+            }
+            if let Some(ref y) = x.script {
+                text.push_str(&format!("script = {}\n", quote(y)));
+            }
+            text.push_str(&format!("slots = {}\n", pairs(&x.slots)));
+            if x.hold != 0 {
+                text.push_str(&format!("hold = {}\n", x.hold));
+            }
+        }
+        for x in stage.rocks.iter() {
+            text.push_str(&format!("\n[[stage.rocks]]\nat = {}\n", x.at));
+            if x.every != 0 {
+                text.push_str(&format!("every = {}\n", x.every));
+            }
+            text.push_str(&format!("count = {}\n", x.count));
+            if let Some(y) = x.column {
+                text.push_str(&format!("column = {}\n", y));
+            }
+        }
+        text.push('\n');
+    }
+    text
+}
+
+//Text as a string in the wave file, with quotes and backslashes escaped.
+fn quote(text: &str) -> String {
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+//A value on the right of an '='.
+enum Value {
+    Number(i64),
+    Text(String),
+    List(Vec<Value>),
+}
+
+//A [[header]] and the keys under it, with the lines they were on.
+struct Table {
+    header: String,
+    line: usize,
+    keys: Vec<(String, Value, usize)>,
+}
+
+//Read the text of a wave file, errors say which line was wrong
+//and have name put in front of them. Waves can use any of the scripts.
+pub fn parse(text: &str, name: &str, scripts: Scripts) -> Result<Waves, String> {
+    let mut waves = Waves {
+        stages: Vec::new(),
+        scripts,
+    };
+    for table in tables(text, name)? {
+        let fields = Fields {
+            table: &table,
+            name,
+        };
+        match table.header.as_str() {
+            "stage" => {
+                fields.allow(&["name"])?;
+                let number = waves.stages.len() + 1;
+                waves.stages.push(Stage {
+                    name: fields.text("name")?.unwrap_or(format!("Stage {}", number)),
+                    waves: Vec::new(),
+                    rocks: Vec::new(),
+                });
+            }
+            "stage.wave" => {
+                fields.allow(&[
+                    "at", "every", "kind", "path", "slots", "hold", "script", "curve",
+                ])?;
+                let wave = Wave {
+                    at: fields.required(fields.count("at")?, "at")?,
+                    every: fields.count("every")?.unwrap_or(0),
+                    kind: match fields.text("kind")? {
+                        Some(x) => Kind::from_name(&x).ok_or_else(|| {
+                            fields.error("kind", &format!("no enemy kind '{}'", x))
+                        })?,
+                        None => Kind::Drone,
+                    },
+                    entry: match fields.text("path")? {
+                        Some(x) => Entry::from_name(&x)
+                            .ok_or_else(|| fields.error("path", &format!("no path '{}'", x)))?,
+                        None => Entry::Straight,
+                    },
+                    slots: fields.required(fields.pairs("slots", (COLUMNS, ROWS))?, "slots")?,
+                    hold: fields.count("hold")?.unwrap_or(0),
+                    script: fields.text("script")?,
+                    curve: match fields.pairs("curve", CURVE)? {
+                        Some(ref x) if x.len() == 3 => Some([x[0], x[1], x[2]]),
+                        Some(_) => {
+                            return Err(fields.error("curve", "'curve' takes 3 [column, row] pairs"))
+                        }
+                        None => None,
+                    },
+                };
+                match (wave.entry, wave.curve.is_some()) {
+                    (Entry::Curve, false) => return Err(fields.missing("curve")),
+                    (Entry::Curve, true) | (_, false) => {}
+                    (_, true) => {
+                        return Err(fields.error("curve", "'curve' needs path = \"curve\""))
+                    }
+                }
+                if let Some(ref x) = wave.script {
+                    if waves.scripts.get(x).is_none() {
+                        return Err(fields.error("script", &format!("no script '{}'", x)));
+                    }
+                }
+                fields.stage(&mut waves)?.waves.push(wave);
+            }
+            "stage.rocks" => {
+                fields.allow(&["at", "every", "count", "column"])?;
+                let shower = Shower {
+                    at: fields.required(fields.count("at")?, "at")?,
+                    every: fields.count("every")?.unwrap_or(0),
+                    count: fields.required(fields.count("count")?, "count")?,
+                    column: fields.column()?,
+                };
+                if shower.count == 0 {
+                    return Err(fields.error("count", "needs at least 1 rock"));
+                }
+                fields.stage(&mut waves)?.rocks.push(shower);
+            }
+            x => {
+                return Err(format!(
+                    "{}:{}: unknown table [[{}]], expected stage, stage.wave or stage.rocks",
+                    name, table.line, x
+                ))
+            }
+        }
+    }
+
+    for x in waves.stages.iter() {
+        if x.waves.is_empty() && x.rocks.is_empty() {
+            return Err(format!("{}: '{}' has no waves or rocks", name, x.name));
+        }
+    }
+    Ok(waves)
+}
+
+//Split the text into tables, checking the syntax but not what's in them.
+fn tables(text: &str, name: &str) -> Result<Vec<Table>, String> {
+    let mut tables: Vec<Table> = Vec::new();
+    for (number, line) in text.lines().enumerate() {
+        let number = number + 1;
+        let error = |message: &str| format!("{}:{}: {}", name, number, message);
+        let line = strip_comment(line).trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        if line.starts_with("[[") {
+            if !line.ends_with("]]") {
+                return Err(error("expected ']]' at the end of the header"));
+            }
+            tables.push(Table {
+                header: line[2..line.len() - 2].trim().to_string(),
+                line: number,
+                keys: Vec::new(),
+            });
+            continue;
+        }
+        if line.starts_with('[') {
+            return Err(error("only [[arrays of tables]] are supported"));
+        }
+
+        let mut parts = line.splitn(2, '=');
+        let key = parts.next().unwrap_or("").trim();
+        let value = match parts.next() {
+            Some(x) => x,
+            None => return Err(error("expected 'key = value'")),
+        };
+        if key.is_empty() || !key.chars().all(|x| x.is_alphanumeric() || x == '_') {
+            return Err(error(&format!("bad key '{}'", key)));
+        }
+        let (value, rest) = parse_value(value.trim_start()).map_err(|e| error(&e))?;
+        if !rest.trim().is_empty() {
+            return Err(error(&format!(
+                "unexpected '{}' after the value",
+                rest.trim()
+            )));
+        }
+
+        let table = match tables.last_mut() {
+            Some(x) => x,
+            None => return Err(error(&format!("'{}' needs to go under a [[stage]]", key))),
+        };
+        if table.keys.iter().any(|x| x.0 == key) {
+            return Err(error(&format!("'{}' is set twice", key)));
+        }
+        table.keys.push((key.to_string(), value, number));
+    }
+    Ok(tables)
+}
+
+//Everything before a # that isn't inside a string.
+fn strip_comment(line: &str) -> &str {
+    let mut quoted = false;
+    let mut escaped = false;
+    for (i, x) in line.char_indices() {
+        match x {
+            _ if escaped => escaped = false,
+            '\\' if quoted => escaped = true,
+            '"' => quoted = !quoted,
+            '#' if !quoted => return &line[..i],
+            _ => {}
+        }
+    }
+    line
+}
+
+//Read a value from the start of the text, giving it and whatever's left.
+fn parse_value(text: &str) -> Result<(Value, &str), String> {
+    //Strings can have quotes and backslashes in them escaped with a backslash.
+    if let Some(text) = text.strip_prefix('"') {
+        let mut string = String::new();
+        let mut chars = text.char_indices();
+        while let Some((i, x)) = chars.next() {
+            match x {
+                '"' => return Ok((Value::Text(string), &text[i + 1..])),
+                '\\' => match chars.next() {
+                    Some((_, x)) if x == '"' || x == '\\' => string.push(x),
+                    Some((_, x)) => return Err(format!("unknown escape '\\{}' in the string", x)),
+                    None => break,
+                },
+                _ => string.push(x),
+            }
+        }
+        return Err("string is missing its closing '\"'".to_string());
+    }
+
+    if let Some(text) = text.strip_prefix('[') {
+        let mut list = Vec::new();
+        let mut rest = text.trim_start();
+        loop {
+            if let Some(after) = rest.strip_prefix(']') {
+                return Ok((Value::List(list), after));
+            }
+            let (value, after) = parse_value(rest)?;
+            list.push(value);
+            rest = after.trim_start();
+            if let Some(after) = rest.strip_prefix(',') {
+                rest = after.trim_start();
+            } else if !rest.starts_with(']') {
+                return Err("expected ',' or ']' in the list".to_string());
+            }
+        }
+    }
+
+    let end = text
+        .find(|x: char| !(x.is_ascii_digit() || x == '-' || x == '+' || x == '_'))
+        .unwrap_or(text.len());
+    if end == 0 {
+        return Err(match text.chars().next() {
+            Some(x) => format!("expected a number, string or list but found '{}'", x),
+            None => "missing a value".to_string(),
+        });
+    }
+    match text[..end].replace('_', "").parse() {
+        Ok(x) => Ok((Value::Number(x), &text[end..])),
+        Err(_) => Err(format!("'{}' is not a whole number", &text[..end])),
+    }
+}
+
+//Reads the keys of a table, knowing what they should be.
+struct Fields<'a> {
+    table: &'a Table,
+    name: &'a str,
+}
+
+impl<'a> Fields<'a> {
+    fn get(&self, key: &str) -> Option<&'a (String, Value, usize)> {
+        self.table.keys.iter().find(|x| x.0 == key)
+    }
+
+    //An error on the line the key is on, or the header if it isn't set.
+    fn error(&self, key: &str, message: &str) -> String {
+        let line = self.get(key).map(|x| x.2).unwrap_or(self.table.line);
+        format!("{}:{}: {}", self.name, line, message)
+    }
+
+    //Anything not in the list is a mistake.
+    fn allow(&self, keys: &[&str]) -> Result<(), String> {
+        for x in self.table.keys.iter() {
+            if !keys.contains(&x.0.as_str()) {
+                return Err(self.error(
+                    &x.0,
+                    &format!(
+                        "unknown key '{}' in [[{}]], expected one of {}",
+                        x.0,
+                        self.table.header,
+                        keys.join(", ")
+                    ),
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    fn required<T>(&self, value: Option<T>, key: &str) -> Result<T, String> {
+        value.ok_or_else(|| self.missing(key))
+    }
+
+    fn missing(&self, key: &str) -> String {
+        format!(
+            "{}:{}: [[{}]] is missing '{}'",
+            self.name, self.table.line, self.table.header, key
+        )
+    }
+
+    //The stage the table belongs to.
+    fn stage<'b>(&self, waves: &'b mut Waves) -> Result<&'b mut Stage, String> {
+        let message = format!("[[{}]] comes before any [[stage]]", self.table.header);
+        waves
+            .stages
+            .last_mut()
+            .ok_or_else(|| format!("{}:{}: {}", self.name, self.table.line, message))
+    }
+
+    fn text(&self, key: &str) -> Result<Option<String>, String> {
+        match self.get(key) {
+            Some(&(_, Value::Text(ref x), _)) => Ok(Some(x.clone())),
+            Some(_) => Err(self.error(key, &format!("'{}' should be a string", key))),
+            None => Ok(None),
+        }
+    }
+
+    //A number of ticks or things, which can't be negative.
+    fn count(&self, key: &str) -> Result<Option<u64>, String> {
+        match self.get(key) {
+            Some(&(_, Value::Number(x), _)) if x >= 0 => Ok(Some(x as u64)),
+            Some(&(_, Value::Number(_), _)) => {
+                Err(self.error(key, &format!("'{}' can't be negative", key)))
+            }
+            Some(_) => Err(self.error(key, &format!("'{}' should be a number", key))),
+            None => Ok(None),
+        }
+    }
+
+    fn column(&self) -> Result<Option<i64>, String> {
+        match self.get("column") {
+            Some(&(_, Value::Number(x), _)) => self.check("column", "column", x, COLUMNS).map(Some),
+            Some(_) => Err(self.error("column", "'column' should be a number")),
+            None => Ok(None),
+        }
+    }
+
+    //A list of [column, row] pairs, at least one, each inside the area.
+    fn pairs(
+        &self,
+        key: &str,
+        area: ((i64, i64), (i64, i64)),
+    ) -> Result<Option<Vec<(i64, i64)>>, String> {
+        let bad = || {
+            self.error(
+                key,
+                &format!("'{}' should be a list of [column, row] pairs", key),
+            )
+        };
+        let list = match self.get(key) {
+            Some(&(_, Value::List(ref x), _)) => x,
+            Some(_) => return Err(bad()),
+            None => return Ok(None),
+        };
+        if list.is_empty() {
+            return Err(self.error(key, &format!("'{}' needs at least 1", key)));
+        }
+
+        let mut pairs = Vec::new();
+        for x in list.iter() {
+            match x {
+                Value::List(pair) => match pair.as_slice() {
+                    [Value::Number(column), Value::Number(row)] => pairs.push((
+                        self.check(key, "column", *column, area.0)?,
+                        self.check(key, "row", *row, area.1)?,
+                    )),
+                    _ => return Err(bad()),
+                },
+                _ => return Err(bad()),
+            }
+        }
+        Ok(Some(pairs))
+    }
+
+    //Make sure a column or row is somewhere an enemy can be.
+    fn check(&self, key: &str, what: &str, x: i64, range: (i64, i64)) -> Result<i64, String> {
+        if x < range.0 || x > range.1 {
+            return Err(self.error(
+                key,
+                &format!(
+                    "{} {} is off the screen, expected {} to {}",
+                    what, x, range.0, range.1
+                ),
+            ));
+        }
+        Ok(x)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    static WAVES: &str = "\
+[[stage]]
+name = \"Opening\"
+
+[[stage.wave]]
+at = 0
+every = 2
+kind = \"bee\"
+path = \"swoop\"
+slots = [[5, 3], [7, 3]]   # two bees
+hold = 20
+
+[[stage.wave]]
+at = 10
+kind = \"boss\"
+path = \"curve\"
+curve = [[1, -1], [18, 4], [2, 8]]
+slots = [[9, 2]]
+
+[[stage.rocks]]
+at = 40
+every = 3
+count = 4
+column = 9
+
+[[stage]]
+
+[[stage.wave]]
+at = 5
+script = \"dive\"
+slots = [[1, 0], [18, 20]]
+
+[[stage.rocks]]
+at = 0
+count = 1
+";
+
+    //Scripts with just the one called dive, loaded from a folder of its own.
+    fn scripts() -> Scripts {
+        let folder = ::std::env::temp_dir().join("galaga-waves-test");
+        fs::create_dir_all(&folder).unwrap();
+        fs::write(folder.join("dive.script"), "move 0, 1\n").unwrap();
+        let scripts = Scripts::load(&folder).unwrap();
+        fs::remove_dir_all(&folder).unwrap();
+        scripts
+    }
+
+    //The error from a wave file of one stage with the given table after it.
+    fn error(table: &str) -> String {
+        let text = format!("[[stage]]\n\n{}", table);
+        parse(&text, "x", Scripts::default()).unwrap_err()
+    }
+
+    #[test]
+    fn reads_every_key() {
+        let waves = parse(WAVES, "x", scripts()).unwrap();
+        assert_eq!(waves.stages.len(), 2);
+        assert_eq!(waves.stages[0].name, "Opening");
+        assert_eq!(waves.stages[1].name, "Stage 2");
+        assert_eq!(
+            waves.stages[0].waves[1],
+            Wave {
+                at: 10,
+                every: 0,
+                kind: Kind::Boss,
+                entry: Entry::Curve,
+                slots: vec![(9, 2)],
+                hold: 0,
+                script: None,
+                curve: Some([(1, -1), (18, 4), (2, 8)]),
+            }
+        );
+        assert_eq!(waves.stages[0].rocks[0].column, Some(9));
+        assert_eq!(waves.stages[1].rocks[0].column, None);
+        assert_eq!(waves.stages[1].waves[0].script, Some("dive".to_string()));
+    }
+
+    #[test]
+    fn round_trip_gives_the_same() {
+        let waves = parse(WAVES, "x", scripts()).unwrap();
+        let text = to_string(&waves);
+        let loaded = parse(&text, "x", waves.scripts.clone()).unwrap();
+        assert_eq!(loaded, waves);
+        assert_eq!(to_string(&loaded), text);
+    }
+
+    #[test]
+    fn quotes_in_names_round_trip() {
+        let mut waves = parse(WAVES, "x", scripts()).unwrap();
+        waves.stages[0].name = "The \"Big\" One \\ # not a comment".to_string();
+        let text = to_string(&waves);
+        assert!(text.contains(r#"name = "The \"Big\" One \\ # not a comment""#));
+        let loaded = parse(&text, "x", waves.scripts.clone()).unwrap();
+        assert_eq!(loaded, waves);
+
+        //Nothing else can be escaped.
+        assert_eq!(
+            parse("[[stage]]\nname = \"a\\tb\"\n", "x", Scripts::default()).unwrap_err(),
+            "x:2: unknown escape '\\t' in the string"
+        );
+        assert_eq!(
+            parse("[[stage]]\nname = \"a\\\"\n", "x", Scripts::default()).unwrap_err(),
+            "x:2: string is missing its closing '\"'"
+        );
+    }
+
+    #[test]
+    fn errors_say_where() {
+        assert_eq!(
+            error("[[stage.wave]]\nat = 0\nslots = [[5, 3]]\nspeed = 2\n"),
+            "x:6: unknown key 'speed' in [[stage.wave]], expected one of at, every, \
+             kind, path, slots, hold, script, curve"
+        );
+        assert_eq!(
+            error("[[stage.wave]]\nat = 0\nat = 1\n"),
+            "x:5: 'at' is set twice"
+        );
+        assert_eq!(
+            error("[[stage.wave]]\nat = 0\nslots = [[5, 3], [0, 3]]\n"),
+            "x:5: column 0 is off the screen, expected 1 to 18"
+        );
+        assert_eq!(
+            error("[[stage.wave]]\nat = 0\nslots = [[19, 3]]\n"),
+            "x:5: column 19 is off the screen, expected 1 to 18"
+        );
+        assert_eq!(
+            error("[[stage.wave]]\nat = 0\npath = \"curve\"\nslots = [[5, 3]]\n"),
+            "x:3: [[stage.wave]] is missing 'curve'"
+        );
+        assert_eq!(
+            error("[[stage.wave]]\nat = 0\nslots = [[5, 3]]\nscript = \"dive\"\n"),
+            "x:6: no script 'dive'"
+        );
+    }
+
+    #[test]
+    fn curve_ends_on_the_slot() {
+        let curve = [(1, -1), (18, 4), (2, 8)];
+        let end = (9, 2);
+        //Around the control points, the longest way each time.
+        let length = 17 + 16 + 7;
+        assert_eq!(curve_point(&curve, end, 0), curve[0]);
+        assert_eq!(curve_point(&curve, end, length), end);
+        assert_eq!(curve_point(&curve, end, length + 10), end);
+
+        //Never more than a square a tick.
+        for x in 1..=length {
+            let (a, b) = (curve_point(&curve, end, x - 1), curve_point(&curve, end, x));
+            assert!((a.0 - b.0).abs() <= 1 && (a.1 - b.1).abs() <= 1);
+        }
+    }
+}